@@ -0,0 +1,82 @@
+// Describes what this server supports, so that clients can find out when talking to an
+// older (or newer) server instead of breaking silently.
+
+// to be bumped on incompatible changes of the protocol only. Additions are listed as features.
+pub(crate) const PROTOCOL_VERSION: u32 = 1;
+
+pub(crate) const SERVER_NAME: &str = env!("CARGO_PKG_NAME");
+pub(crate) const SERVER_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// commands and the names of the parameters they take, in the order they are passed (comma
+// separated) when using the text protocol. New commands must be added here.
+pub(crate) const SUPPORTED_COMMANDS: &[(&str, &[&str])] = &[
+  ("HELLO", &[]),
+  ("CAPABILITIES", &[]),
+  ("FETCH_TICKET", &["key", "format"]),
+  ("FETCH_TICKET_LIST", &[]),
+  ("FETCH_TICKET_HISTORY", &["key"]),
+  ("FETCH_TICKET_KEY_VALUE_FIELDS", &["key"]),
+  ("FETCH_TICKETS_KEY_VALUE_FIELDS", &["keys"]),
+  ("FETCH_ATTACHMENT_LIST_FOR_TICKET", &["key"]),
+  ("FETCH_ATTACHMENT_CONTENT", &["uuid"]),
+  ("FETCH_LOGGED_TIME", &["from", "to"]),
+  ("FETCH_BOARD_LIST", &[]),
+  ("FETCH_BOARD_SPRINTS", &["board"]),
+  ("FETCH_SPRINT_TICKETS", &["sprint"]),
+  ("FETCH_PROJECT_VERSIONS", &["project"]),
+  ("FETCH_VERSION_TICKETS", &["version"]),
+  ("FETCH_PROJECT_COMPONENTS", &["project"]),
+  ("FETCH_COMPONENT_TICKETS", &["component"]),
+  ("FETCH_TICKET_STATUS_LIST", &[]),
+  ("FETCH_WATCHED_TICKETS", &[]),
+  ("FETCH_TICKET_WATCHERS", &["key"]),
+  ("FETCH_TICKET_VOTERS", &["key"]),
+  ("FETCH_TICKET_TREE", &["key"]),
+  ("ADD_COMMENT", &["key", "markdown"]),
+  ("EDIT_FIELD", &["key", "field", "value"]),
+  ("FETCH_TRANSITIONS", &["key"]),
+  ("TRANSITION", &["key", "transition", "fields"]),
+  ("CREATE_ISSUE", &["project", "issue_type", "summary", "description", "fields"]),
+  ("SYNCHRONISE_TICKET", &["key"]),
+  ("SYNCHRONISE_UPDATED", &[]),
+  ("SYNCHRONISE_ALL", &[]),
+  ("SUBSCRIBE", &[]),
+  ("SET_OFFLINE_MODE", &["mode"]),
+  ("CANCEL", &["request_id"]),
+  ("SET_PROTOCOL", &["protocol"]),
+  ("EXIT_SERVER_AFTER_REQUESTS", &[]),
+  ("EXIT_SERVER_NOW", &[]),
+];
+
+// parameters which may be left out, always the last ones of their command. A TRANSITION without
+// screen doesn't need any field.
+const OPTIONAL_PARAMETERS: &[(&str, &str)] = &[
+  ("TRANSITION", "fields"),
+  ("CREATE_ISSUE", "fields"),
+];
+
+// ticket formats accepted by FETCH_TICKET
+pub(crate) const SUPPORTED_TICKET_FORMATS: &[&str] = &["MARKDOWN", "HTML", "MARKDOWN_WITH_HISTORY", "HTML_WITH_HISTORY"];
+
+// optional parts of the protocol a client may want to rely on. New commands and ticket formats are
+// already listed above, only what they don't tell about goes here.
+pub(crate) const SUPPORTED_FEATURES: &[&str] = &[
+  "JSON_LINES",
+  "CANCEL",
+  "PROGRESS",
+  "SUBSCRIBE",
+  "OFFLINE",
+  "HTTP_API",
+];
+
+pub(crate) fn get_parameter_names(command: &str) -> &'static [&'static str] {
+  SUPPORTED_COMMANDS
+    .iter()
+    .find(|(name, _)| *name == command)
+    .map(|(_, parameters)| *parameters)
+    .unwrap_or(&[])
+}
+
+pub(crate) fn is_optional_parameter(command: &str, parameter_name: &str) -> bool {
+  OPTIONAL_PARAMETERS.contains(&(command, parameter_name))
+}
@@ -0,0 +1,250 @@
+use std::collections::HashSet;
+use serde_json::{json, Map, Value};
+use sqlx::{FromRow, Pool, Sqlite};
+use crate::edit_issue_field::{find_field_in_db, get_field_value, FieldValue};
+use crate::get_config::Config;
+use crate::get_json_from_url::post_json_to_url;
+use crate::manage_create_metadata::get_create_fields_from_db;
+use crate::markdown_to_atlassian_document_format::markdown_to_adf;
+use crate::ticket_events::{publish_ticket_changes, TicketChanges};
+
+// Creation of tickets. The ticket is checked against the create metadata of its project and issue
+// type before being sent, and is added to the local database as soon as jira created it, without
+// waiting for the next synchronisation.
+
+// fields given by their own parameter
+const BASE_FIELDS: &[&str] = &["project", "issuetype", "summary", "description"];
+
+#[derive(FromRow)]
+struct ProjectId {
+  jira_id: i64,
+}
+
+#[derive(FromRow)]
+struct IssueTypeRow {
+  jira_id: i64,
+  name: String,
+}
+
+pub(crate) struct NewIssue {
+  project_key: String,
+  project_id: i64,
+  issue_type: IssueTypeRow,
+  summary: String,
+  description: Option<Value>, // atlassian document format
+  field_values: Vec<FieldValue>,
+}
+
+fn normalise_name(name: &str) -> String {
+  name.trim().to_lowercase().replace('_', " ")
+}
+
+async fn get_project_id_from_db(project_key: &str, db_conn: &Pool<Sqlite>) -> Result<i64, String> {
+  sqlx::query_as::<_, ProjectId>("SELECT jira_id FROM Project WHERE key = ?;")
+    .bind(project_key)
+    .fetch_optional(db_conn)
+    .await
+    .map_err(|e| format!("Error occurred while trying to get project {project_key} from local database: {e}"))?
+    .map(|x| x.jira_id)
+    .ok_or_else(|| format!("Unknown project {project_key}"))
+}
+
+// issue types are given by id or by name. Like field names, underscores stand for spaces.
+async fn find_issue_type_of_project_in_db(project_key: &str, project_id: i64, issue_type: &str, db_conn: &Pool<Sqlite>) -> Result<IssueTypeRow, String> {
+  let query_str =
+    "SELECT IssueType.jira_id AS jira_id, IssueType.name AS name
+     FROM IssueType
+     JOIN IssueTypePerProject ON IssueTypePerProject.issue_type_id = IssueType.jira_id
+     WHERE IssueTypePerProject.project_id = ?
+     ORDER BY IssueType.jira_id ASC;";
+
+  let mut issue_types = sqlx::query_as::<_, IssueTypeRow>(query_str)
+    .bind(project_id)
+    .fetch_all(db_conn)
+    .await
+    .map_err(|e| format!("Error occurred while trying to get the issue types of project {project_key} from local database: {e}"))?;
+
+  let name = normalise_name(issue_type);
+  let position = issue_types
+    .iter()
+    .position(|x| x.jira_id.to_string() == issue_type)
+    .or_else(|| issue_types.iter().position(|x| normalise_name(&x.name) == name));
+  match position {
+    Some(position) => { Ok(issue_types.swap_remove(position)) }
+    None => {
+      let names = issue_types.iter().map(|x| x.name.as_str()).collect::<Vec<_>>().join(", ");
+      Err(format!("Unknown issue type {issue_type} for project {project_key}. Known issue types are: {names}"))
+    }
+  }
+}
+
+// when the create metadata of the issue type isn't in the local database yet, the checks are
+// left to the jira server
+async fn check_fields_against_create_metadata(new_issue: &NewIssue, db_conn: &Pool<Sqlite>) -> Result<(), String> {
+  let create_fields = get_create_fields_from_db(new_issue.project_id, new_issue.issue_type.jira_id, db_conn).await?;
+  if create_fields.is_empty() {
+    return Ok(());
+  }
+
+  let mut given_fields = vec!["project", "issuetype", "summary"];
+  if new_issue.description.is_some() {
+    given_fields.push("description");
+  }
+  given_fields.extend(new_issue.field_values.iter().map(|x| x.field.jira_id.as_str()));
+
+  let fields_on_screen = create_fields.iter().map(|x| x.field_id.as_str()).collect::<HashSet<_>>();
+  let fields_not_on_screen = given_fields
+    .iter()
+    .filter(|x| !["project", "issuetype"].contains(x) && !fields_on_screen.contains(**x))
+    .copied()
+    .collect::<Vec<_>>();
+  if !fields_not_on_screen.is_empty() {
+    return Err(format!("The following fields can't be set when creating a {issue_type} in project {project_key}: {fields}",
+                       issue_type = new_issue.issue_type.name, project_key = new_issue.project_key, fields = fields_not_on_screen.join(", ")));
+  }
+
+  let missing_fields = create_fields
+    .iter()
+    .filter(|x| x.is_required && !given_fields.contains(&x.field_id.as_str()))
+    .map(|x| x.field_id.as_str())
+    .collect::<Vec<_>>();
+  if !missing_fields.is_empty() {
+    return Err(format!("Creating a {issue_type} in project {project_key} needs the following fields: {fields}",
+                       issue_type = new_issue.issue_type.name, project_key = new_issue.project_key, fields = missing_fields.join(", ")));
+  }
+
+  Ok(())
+}
+
+// fields are given like for EDIT_FIELD, as pairs of field and value
+pub(crate) async fn get_new_issue(project_key: &str,
+                                  issue_type: &str,
+                                  summary: &str,
+                                  description: &str,
+                                  fields: Vec<(String, String)>,
+                                  db_conn: &Pool<Sqlite>) -> Result<NewIssue, String> {
+  if summary.trim().is_empty() {
+    return Err(String::from("The summary of a ticket can't be empty"));
+  }
+
+  let project_id = get_project_id_from_db(project_key, db_conn).await?;
+  let issue_type = find_issue_type_of_project_in_db(project_key, project_id, issue_type, db_conn).await?;
+
+  let mut field_values = Vec::<FieldValue>::new();
+  for (field, value) in fields {
+    let field = find_field_in_db(field.as_str(), db_conn).await?;
+    if BASE_FIELDS.contains(&field.jira_id.as_str()) {
+      return Err(format!("Field {name} has its own parameter", name = field.human_name));
+    }
+    if field_values.iter().any(|x| x.field.jira_id == field.jira_id) {
+      return Err(format!("Field {name} is given more than once", name = field.human_name));
+    }
+    field_values.push(get_field_value(field, value.as_str(), db_conn).await?);
+  }
+
+  let new_issue = NewIssue {
+    project_key: project_key.to_string(),
+    project_id,
+    issue_type,
+    summary: summary.trim().to_string(),
+    description: Some(description).filter(|x| !x.trim().is_empty()).map(markdown_to_adf),
+    field_values,
+  };
+  check_fields_against_create_metadata(&new_issue, db_conn).await?;
+  Ok(new_issue)
+}
+
+// the fields known right away, as they would be stored when synchronising the ticket. The next
+// synchronisation completes them.
+fn get_fields_for_db(new_issue: &NewIssue) -> Vec<(&str, String)> {
+  let mut res = vec![
+    ("project", json!({"id": new_issue.project_id.to_string(), "key": new_issue.project_key}).to_string()),
+    ("issuetype", json!({"id": new_issue.issue_type.jira_id.to_string(), "name": new_issue.issue_type.name}).to_string()),
+    ("summary", json!(new_issue.summary).to_string()),
+  ];
+  if let Some(description) = &new_issue.description {
+    res.push(("description", description.to_string()));
+  }
+  for field_value in &new_issue.field_values {
+    if !field_value.for_db.is_null() {
+      res.push((field_value.field.jira_id.as_str(), field_value.for_db.to_string()));
+    }
+  }
+  res
+}
+
+async fn add_new_issue_into_db(new_issue: &NewIssue, issue_id: i64, issue_key: &str, db_conn: &Pool<Sqlite>) -> Result<(), String> {
+  let mut tx = db_conn
+    .begin()
+    .await
+    .map_err(|e| format!("Error when starting a sql transaction to add issue {issue_key}. Err: {e:?}"))?;
+
+  sqlx::query("INSERT INTO Issue (jira_id, key, project_key) VALUES (?, ?, ?) ON CONFLICT DO NOTHING;")
+    .bind(issue_id)
+    .bind(issue_key)
+    .bind(&new_issue.project_key)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| format!("Error when adding issue {issue_key}. Err: {e:?}"))?;
+
+  // fields missing from the Field table can't be stored, they are skipped
+  let query_str =
+    "INSERT INTO IssueField (issue_id, field_id, field_value)
+     SELECT ?, jira_id, ? FROM Field WHERE jira_id = ?
+     ON CONFLICT DO NOTHING;";
+  for (field_id, value) in get_fields_for_db(new_issue) {
+    sqlx::query(query_str)
+      .bind(issue_id)
+      .bind(value)
+      .bind(field_id)
+      .execute(&mut *tx)
+      .await
+      .map_err(|e| format!("Error when adding field {field_id} of issue {issue_key}. Err: {e:?}"))?;
+  }
+
+  tx.commit()
+    .await
+    .map_err(|e| format!("Error when committing issue {issue_key}. Err: {e:?}"))
+}
+
+// returns the key of the new ticket
+pub(crate) async fn create_issue(config: &Config, new_issue: &NewIssue, db_conn: &Pool<Sqlite>) -> Result<String, String> {
+  let mut fields = Map::new();
+  fields.insert(String::from("project"), json!({"id": new_issue.project_id.to_string()}));
+  fields.insert(String::from("issuetype"), json!({"id": new_issue.issue_type.jira_id.to_string()}));
+  fields.insert(String::from("summary"), json!(new_issue.summary));
+  if let Some(description) = &new_issue.description {
+    fields.insert(String::from("description"), description.clone());
+  }
+  for field_value in &new_issue.field_values {
+    fields.insert(field_value.field.jira_id.clone(), field_value.for_jira.clone());
+  }
+
+  // not retried on gateway errors, the ticket may have been created anyway
+  let json = post_json_to_url(config, "/rest/api/3/issue", &json!({"fields": fields}))
+    .await
+    .map_err(|e| format!("Error: failed to create a {issue_type} in project {project_key} on the server. {e}",
+                         issue_type = new_issue.issue_type.name, project_key = new_issue.project_key))?;
+
+  let issue_id = json
+    .get("id")
+    .and_then(|x| x.as_str())
+    .and_then(|x| x.parse::<i64>().ok());
+  let issue_key = json
+    .get("key")
+    .and_then(|x| x.as_str());
+  let (Some(issue_id), Some(issue_key)) = (issue_id, issue_key) else {
+    return Err(format!("Error: the reply of the server to the creation of a ticket doesn't contain its id and key: {json}"));
+  };
+
+  // the ticket exists on the server anyway, the next synchronisation will add it
+  match add_new_issue_into_db(new_issue, issue_id, issue_key, db_conn).await {
+    Ok(()) => {
+      publish_ticket_changes(TicketChanges { created: vec![issue_key.to_string()], ..TicketChanges::default() });
+    }
+    Err(e) => {
+      eprintln!("{e}");
+    }
+  }
+  Ok(issue_key.to_string())
+}
@@ -12,7 +12,7 @@ BEGIN;
 
 CREATE TABLE IF NOT EXISTS People (
    accountId TEXT UNIQUE PRIMARY KEY NOT NULL,
-   displayName TEXT UNIQUE NOT NULL
+   displayName TEXT NOT NULL  -- not unique, e.g. deleted accounts are all "Former user"
 ) STRICT;
 
 CREATE TABLE IF NOT EXISTS Project (
@@ -47,15 +47,55 @@ CREATE TABLE IF NOT EXISTS IssueTypePerProject (
    UNIQUE(project_id, issue_type_id)
 ) STRICT;
 
+-- fields of the create screen of each issue type of the interesting projects. Not foreign keys,
+-- like IssueTypePerProject the create metadata is replaced when synchronising the projects.
+CREATE TABLE IF NOT EXISTS CreateMetadata (
+   project_id INTEGER NOT NULL,
+   issue_type_id INTEGER NOT NULL,
+   field_id TEXT NOT NULL,          -- like customfield_12345
+   is_required INTEGER NOT NULL,    -- required and without default value
+   PRIMARY KEY (project_id, issue_type_id, field_id)
+) STRICT;
+
+CREATE TABLE IF NOT EXISTS Status (
+   jira_id INTEGER UNIQUE PRIMARY KEY NOT NULL,
+   name TEXT NOT NULL,
+   description TEXT,
+   category_key TEXT NOT NULL,   -- new, indeterminate or done
+   category_name TEXT NOT NULL   -- like To Do, In Progress or Done
+) STRICT;
+
+CREATE TABLE IF NOT EXISTS Priority (
+   jira_id INTEGER UNIQUE PRIMARY KEY NOT NULL,
+   position INTEGER NOT NULL,    -- 0 for the highest priority
+   name TEXT NOT NULL,
+   description TEXT
+) STRICT;
+
+CREATE TABLE IF NOT EXISTS Resolution (
+   jira_id INTEGER UNIQUE PRIMARY KEY NOT NULL,
+   name TEXT NOT NULL,
+   description TEXT
+) STRICT;
+
+-- status_id, priority_id and resolution_id aren't foreign keys: the catalogues are synchronised
+-- separately from the tickets and may lag behind. They are added to older databases when
+-- opening them, see add_missing_columns.
 CREATE TABLE IF NOT EXISTS Issue (
    jira_id INTEGER UNIQUE PRIMARY KEY NOT NULL,
    key TEXT UNIQUE NOT NULL,  -- something like COMPANYPROJ-1234
    project_key TEXT NOT NULL,
+   status_id INTEGER,
+   priority_id INTEGER,
+   resolution_id INTEGER,     -- null while the ticket isn't resolved
    FOREIGN KEY (project_key) REFERENCES Project(key),
    UNIQUE(key, project_key)
 ) STRICT;
 
 CREATE INDEX IF NOT EXISTS issue_key ON Issue(key);
+CREATE INDEX IF NOT EXISTS issue_status ON Issue(status_id);
+CREATE INDEX IF NOT EXISTS issue_priority ON Issue(priority_id);
+CREATE INDEX IF NOT EXISTS issue_resolution ON Issue(resolution_id);
 
 CREATE TABLE IF NOT EXISTS IssueLinkType (
    jira_id INTEGER UNIQUE PRIMARY KEY NOT NULL,
@@ -87,6 +127,29 @@ CREATE TABLE IF NOT EXISTS IssueField (
    UNIQUE(issue_id, field_id)
 ) STRICT;
 
+-- parent of each ticket having one: sub-tasks and children of epics. Not foreign keys, the parent
+-- may be in a project which isn't synchronised, and sub-tasks are listed before being synchronised.
+CREATE TABLE IF NOT EXISTS IssueHierarchy (
+   child_id INTEGER UNIQUE PRIMARY KEY NOT NULL,
+   parent_id INTEGER NOT NULL
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS issue_hierarchy_parent ON IssueHierarchy(parent_id);
+
+-- workflow transitions available on each ticket for the user whose credentials are in the
+-- configuration, as listed by jira. Replaced as a whole when the ticket is synchronised.
+CREATE TABLE IF NOT EXISTS IssueTransition (
+   issue_id INTEGER NOT NULL,
+   transition_id INTEGER NOT NULL,
+   position INTEGER NOT NULL,        -- order given by jira
+   name TEXT NOT NULL,               -- like Start progress
+   to_status_id INTEGER,             -- not a foreign key, like Issue.status_id
+   required_fields TEXT NOT NULL,    -- json array of the ids of the fields to give, like ["resolution"]
+
+   FOREIGN KEY (issue_id) REFERENCES Issue(jira_id),
+   PRIMARY KEY (issue_id, transition_id)
+) STRICT;
+
 CREATE TABLE IF NOT EXISTS watcher (
     person TEXT,
     Issue INTEGER,
@@ -94,6 +157,26 @@ CREATE TABLE IF NOT EXISTS watcher (
     FOREIGN KEY (Issue) REFERENCES Issue(jira_id)
 ) STRICT;
 
+CREATE UNIQUE INDEX IF NOT EXISTS watcher_issue_person ON watcher(Issue, person);
+CREATE INDEX IF NOT EXISTS watcher_person ON watcher(person);
+
+-- same as watcher, for the people who voted for the issue
+CREATE TABLE IF NOT EXISTS voter (
+    person TEXT,
+    Issue INTEGER,
+    FOREIGN KEY (person) REFERENCES People(accountId),
+    FOREIGN KEY (Issue) REFERENCES Issue(jira_id)
+) STRICT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS voter_issue_person ON voter(Issue, person);
+
+-- the jira user whose credentials are in the configuration. Single row.
+CREATE TABLE IF NOT EXISTS CurrentUser (
+    id INTEGER PRIMARY KEY NOT NULL CHECK (id = 0),
+    account_id TEXT NOT NULL,
+    FOREIGN KEY (account_id) REFERENCES People(accountId)
+) STRICT;
+
 CREATE TABLE IF NOT EXISTS Attachment (
   uuid TEXT UNIQUE,
   id INTEGER UNIQUE PRIMARY KEY NOT NULL,
@@ -122,4 +205,135 @@ CREATE TABLE IF NOT EXISTS Comment (
 
 CREATE INDEX IF NOT EXISTS comment_issue ON Comment(issue_id, position_in_array);
 
+-- one row per field changed, as listed in the changelog of the issue. A history groups the
+-- fields changed at once by someone.
+CREATE TABLE IF NOT EXISTS IssueHistory (
+  history_id INTEGER NOT NULL,
+  position_in_history INTEGER NOT NULL,
+  issue_id INTEGER NOT NULL,
+  author TEXT,                  -- null for changes made by jira itself
+  creation_time TEXT NOT NULL,
+  field TEXT NOT NULL,          -- like status
+  from_value TEXT,              -- human readable value before the change
+  to_value TEXT,                -- human readable value after the change
+
+  FOREIGN KEY (issue_id) REFERENCES Issue(jira_id),
+  FOREIGN KEY (author) REFERENCES People(accountId),
+  PRIMARY KEY (history_id, position_in_history)
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS issue_history_issue ON IssueHistory(issue_id);
+
+CREATE TABLE IF NOT EXISTS Worklog (
+  id INTEGER UNIQUE PRIMARY KEY NOT NULL,
+  issue_id INTEGER NOT NULL,
+  author TEXT,
+  started TEXT NOT NULL,              -- like 2024-01-31T09:00:00.000+0100, in the author's timezone
+  time_spent_seconds INTEGER NOT NULL,
+  comment_data TEXT,                  -- atlassian document format
+  creation_time TEXT NOT NULL,
+  last_modification_time TEXT NOT NULL,
+
+  FOREIGN KEY (issue_id) REFERENCES Issue(jira_id),
+  FOREIGN KEY (author) REFERENCES People(accountId)
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS worklog_issue ON Worklog(issue_id);
+CREATE INDEX IF NOT EXISTS worklog_started ON Worklog(substr(started, 1, 10));
+
+-- versions and components of the interesting projects
+CREATE TABLE IF NOT EXISTS ProjectVersion (
+  id INTEGER UNIQUE PRIMARY KEY NOT NULL,
+  project_key TEXT NOT NULL,
+  position_in_project INTEGER NOT NULL, -- versions are ordered by hand in jira
+  name TEXT NOT NULL,                   -- like 4.2
+  description TEXT,
+  is_released INTEGER NOT NULL,
+  is_archived INTEGER NOT NULL,
+  release_date TEXT,                    -- like 2024-01-31
+
+  FOREIGN KEY (project_key) REFERENCES Project(key)
+) STRICT;
+
+CREATE TABLE IF NOT EXISTS ProjectComponent (
+  id INTEGER UNIQUE PRIMARY KEY NOT NULL,
+  project_key TEXT NOT NULL,
+  name TEXT NOT NULL,
+  description TEXT,
+  lead TEXT,
+
+  FOREIGN KEY (project_key) REFERENCES Project(key),
+  FOREIGN KEY (lead) REFERENCES People(accountId)
+) STRICT;
+
+CREATE TABLE IF NOT EXISTS IssueFixVersion (
+  issue_id INTEGER NOT NULL,
+  version_id INTEGER NOT NULL,
+
+  FOREIGN KEY (issue_id) REFERENCES Issue(jira_id),
+  FOREIGN KEY (version_id) REFERENCES ProjectVersion(id),
+  PRIMARY KEY (issue_id, version_id)
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS issue_fix_version_version ON IssueFixVersion(version_id);
+
+CREATE TABLE IF NOT EXISTS IssueComponent (
+  issue_id INTEGER NOT NULL,
+  component_id INTEGER NOT NULL,
+
+  FOREIGN KEY (issue_id) REFERENCES Issue(jira_id),
+  FOREIGN KEY (component_id) REFERENCES ProjectComponent(id),
+  PRIMARY KEY (issue_id, component_id)
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS issue_component_component ON IssueComponent(component_id);
+
+-- agile boards of the interesting projects, as listed by the jira agile api
+CREATE TABLE IF NOT EXISTS Board (
+  id INTEGER UNIQUE PRIMARY KEY NOT NULL,
+  name TEXT NOT NULL,
+  type TEXT NOT NULL,          -- scrum, kanban or simple
+  project_key TEXT NOT NULL    -- interesting project the board was found in
+) STRICT;
+
+CREATE TABLE IF NOT EXISTS Sprint (
+  id INTEGER UNIQUE PRIMARY KEY NOT NULL,
+  name TEXT NOT NULL,
+  state TEXT NOT NULL,         -- future, active or closed
+  start_date TEXT,             -- like 2024-01-29T09:00:00.000Z, none for future sprints
+  end_date TEXT,
+  complete_date TEXT,          -- only for closed sprints
+  goal TEXT
+) STRICT;
+
+-- a board shows the sprints of the tickets matching its filter, which can come from other boards.
+-- Hence a sprint can be on several boards.
+CREATE TABLE IF NOT EXISTS BoardSprint (
+  board_id INTEGER NOT NULL,
+  sprint_id INTEGER NOT NULL,
+
+  FOREIGN KEY (board_id) REFERENCES Board(id),
+  FOREIGN KEY (sprint_id) REFERENCES Sprint(id),
+  PRIMARY KEY (board_id, sprint_id)
+) STRICT;
+
+-- the issue isn't a foreign key: the membership of a sprint is recorded even for tickets not
+-- synchronised yet, or not part of an interesting project. Requests join with Issue.
+CREATE TABLE IF NOT EXISTS SprintIssue (
+  sprint_id INTEGER NOT NULL,
+  issue_id INTEGER NOT NULL,
+
+  FOREIGN KEY (sprint_id) REFERENCES Sprint(id),
+  PRIMARY KEY (sprint_id, issue_id)
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS sprint_issue_issue ON SprintIssue(issue_id);
+
+-- high-water mark of the synchronisation of each interesting project. Only tickets updated
+-- since then are queried from the jira server.
+CREATE TABLE IF NOT EXISTS ProjectSynchronisation (
+  project_key TEXT UNIQUE PRIMARY KEY NOT NULL,
+  last_synchronisation INTEGER NOT NULL -- seconds since unix epoch, local clock
+) STRICT;
+
 COMMIT;
\ No newline at end of file
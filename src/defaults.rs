@@ -1,6 +1,9 @@
 pub(crate) const DEFAULT_CONFIG_FILE_PATH: &'static str = "local_jira/local_jira.toml";
 pub(crate) const DEFAULT_DB_NAME: &'static str = "local_jira.sqlite";
 pub(crate) const JIRA_API_TOKEN_ENV_VAR: &'static str = "JIRA_API_TOKEN";
+pub(crate) const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 4;
+pub(crate) const DEFAULT_MAX_REQUESTS_PER_SECOND: f64 = 10.0;
+pub(crate) const DEFAULT_REQUEST_TIMEOUT_IN_SECONDS: u64 = 30;
 
 pub(crate) const EXAMPLE_CONFIG_FILE: &'static str =
 r##"# Example configuration file
@@ -25,4 +28,27 @@ interesting_projects = [ "PRJKEYONE", "PRJKEYTWO", "PRJKEYTHREE" ]
 # session cookie. local_jira will retrieve that cookie and download attachment files
 # with it. Without this cookie, No attachment file will be downloaded.
 mozilla_cookies_db = "/Path/to/Mozilla/Firefox/Profiles/<profile key>/cookies.sqlite"
+
+# Optional. When given, the server doesn't read requests from its stdin. Instead it listens on
+# a unix domain socket at that path, and several clients (gui, editor plugin, scripts, ...) can
+# connect to it at the same time. They all share the same database and background synchronisation.
+# unix_socket = "/run/user/1000/local_jira.sock"
+
+# Optional. When given, the server doesn't read requests from its stdin either. Instead it serves
+# an http api returning json on that address, for scripts and browser based tools. Only loopback
+# addresses are accepted since the api gives access to the whole database without authentication.
+# Can't be used together with unix_socket.
+# http_api = "127.0.0.1:8080"
+
+# Optional. When true, the server starts in offline mode: requests are answered from the local
+# database only, and the background synchronisation is paused until offline mode is switched off
+# with a SET_OFFLINE_MODE request. Useful when the jira server isn't reachable, e.g. on a train.
+# offline = false
+
+# Optional. Limits on the requests sent to the jira server, to stay below its rate limits. When
+# the server still answers with http 429 or 503, the request is retried later, waiting as long as
+# the server asks to in its Retry-After header.
+# max_concurrent_requests = 4
+# max_requests_per_second = 10.0
+# request_timeout_in_seconds = 30
 "##;
\ No newline at end of file
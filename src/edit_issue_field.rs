@@ -0,0 +1,399 @@
+use serde_json::{json, Value};
+use sqlx::{FromRow, Pool, Sqlite};
+use crate::get_config::Config;
+use crate::get_json_from_url::{get_json_from_url, put_json_to_url};
+use crate::manage_field_table::{get_fields_from_database, FieldMetadata};
+use crate::manage_status_priority_resolution_tables::update_status_priority_and_resolution_of_issue_in_db;
+use crate::markdown_to_atlassian_document_format::markdown_to_adf;
+use crate::server::KeyValueField;
+use crate::ticket_events::{publish_ticket_changes, TicketChanges, UpdatedTicket};
+
+// Edition of a single field of a ticket. The new value is written to the local database before
+// being sent to the jira server, so that clients see it right away, and the previous value is put
+// back when the server rejects it. Once accepted, the field is fetched again from the server since
+// jira stores values in a richer form than the one sent, e.g. users along with their display name.
+//
+// Values are given as text, and converted to the json expected by jira according to the schema of
+// the field:
+// - rich text fields (description, environment, multi-line custom fields) are written in markdown
+// - numbers, dates (like 2024-01-31) and date-times are checked before being sent
+// - users are given by display name or account id, priorities and resolutions by name
+// - lists (labels, versions, components, multiple choices...) are comma separated
+// An empty value clears the field.
+
+pub(crate) struct FieldValue {
+  pub(crate) field: FieldMetadata,
+  pub(crate) for_jira: Value,
+  pub(crate) for_db: Value, // stored until the value is fetched back from the server
+}
+
+fn normalise_field_name(name: &str) -> String {
+  name.trim().to_lowercase().replace('_', " ")
+}
+
+// fields are given by id (like customfield_10016) or by human name (like Story Points). Since the
+// text protocol doesn't allow spaces in parameters, underscores in human names stand for spaces.
+pub(crate) async fn find_field_in_db(field: &str, db_conn: &Pool<Sqlite>) -> Result<FieldMetadata, String> {
+  let fields = get_fields_from_database(db_conn).await;
+
+  let mut matching_fields = fields
+    .into_iter()
+    .filter(|x| x.jira_id == field || x.key == field || normalise_field_name(&x.human_name) == normalise_field_name(field))
+    .collect::<Vec<_>>();
+
+  // an id wins over a human name which happens to be the same
+  if let Some(pos) = matching_fields.iter().position(|x| x.jira_id == field) {
+    return Ok(matching_fields.swap_remove(pos));
+  }
+
+  match matching_fields.len() {
+    0 => { Err(format!("Unknown field {field}. Fields are given by id (like customfield_10016) or by name (like Story_Points)")) }
+    1 => { Ok(matching_fields.remove(0)) }
+    _ => {
+      let ids = matching_fields
+        .iter()
+        .map(|x| x.jira_id.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+      Err(format!("Several fields are named {field}: {ids}. Use the id of the field instead"))
+    }
+  }
+}
+
+fn is_rich_text(schema: &Value) -> bool {
+  let system = schema.get("system").and_then(|x| x.as_str()).unwrap_or_default();
+  let custom = schema.get("custom").and_then(|x| x.as_str()).unwrap_or_default();
+  system == "description" || system == "environment" || custom.ends_with(":textarea")
+}
+
+fn is_date(value: &str) -> bool {
+  let bytes = value.as_bytes();
+  bytes.len() == 10
+    && bytes
+      .iter()
+      .enumerate()
+      .all(|(i, x)| if i == 4 || i == 7 { *x == b'-' } else { x.is_ascii_digit() })
+}
+
+// like 2024-01-31T17:30:00.000+0100, the server checks the rest
+fn is_date_time(value: &str) -> bool {
+  value.len() > 11 && value.get(..10).is_some_and(is_date) && value.as_bytes()[10] == b'T'
+}
+
+#[derive(FromRow)]
+struct PersonRow {
+  account_id: String,
+  display_name: String,
+}
+
+// display names aren't unique (e.g. every deleted account is a "Former user"), the account id
+// is then needed.
+async fn find_person_in_db(person: &str, db_conn: &Pool<Sqlite>) -> Result<PersonRow, String> {
+  let query_str =
+    "SELECT accountId AS account_id, displayName AS display_name
+     FROM People
+     WHERE accountId = ? OR displayName = ? COLLATE NOCASE
+     ORDER BY accountId ASC;";
+
+  let mut people = sqlx::query_as::<_, PersonRow>(query_str)
+    .bind(person)
+    .bind(person)
+    .fetch_all(db_conn)
+    .await
+    .map_err(|e| format!("Error occurred while trying to find {person} in local database: {e}"))?;
+
+  if let Some(position) = people.iter().position(|x| x.account_id == person) {
+    return Ok(people.swap_remove(position));
+  }
+  match people.len() {
+    0 => { Err(format!("Unknown person {person}. People are given by display name or account id")) }
+    1 => { Ok(people.swap_remove(0)) }
+    _ => {
+      let account_ids = people.iter().map(|x| x.account_id.as_str()).collect::<Vec<_>>().join(", ");
+      Err(format!("Several people are named {person}, give one of their account ids instead: {account_ids}"))
+    }
+  }
+}
+
+#[derive(FromRow)]
+struct CatalogueEntry {
+  jira_id: i64,
+  name: String,
+}
+
+// table is Priority or Resolution
+async fn find_in_catalogue(table: &str, name: &str, db_conn: &Pool<Sqlite>) -> Result<CatalogueEntry, String> {
+  let query_str = format!(
+    "SELECT jira_id, name
+     FROM {table}
+     WHERE name = ? COLLATE NOCASE OR CAST(jira_id AS TEXT) = ?;");
+
+  sqlx::query_as::<_, CatalogueEntry>(query_str.as_str())
+    .bind(name)
+    .bind(name)
+    .fetch_optional(db_conn)
+    .await
+    .map_err(|e| format!("Error occurred while trying to find {name} in table {table} of local database: {e}"))?
+    .ok_or_else(|| format!("Unknown {kind} {name}", kind = table.to_lowercase()))
+}
+
+// returns the value to send to jira, and the one to store locally
+async fn get_single_value(value_type: &str, value: &str, field_name: &str, db_conn: &Pool<Sqlite>) -> Result<(Value, Value), String> {
+  match value_type {
+    "string" => { Ok((json!(value), json!(value))) }
+    "number" => {
+      let number = value
+        .parse::<f64>()
+        .map_err(|_| format!("Field {field_name} takes a number, got [{value}]"))?;
+      Ok((json!(number), json!(number)))
+    }
+    "date" if is_date(value) => { Ok((json!(value), json!(value))) }
+    "date" => { Err(format!("Field {field_name} takes a date like 2024-01-31, got [{value}]")) }
+    "datetime" if is_date_time(value) => { Ok((json!(value), json!(value))) }
+    "datetime" => { Err(format!("Field {field_name} takes a date and time like 2024-01-31T17:30:00.000+0100, got [{value}]")) }
+    "option" => { Ok((json!({"value": value}), json!({"value": value}))) }
+    "version" | "component" => { Ok((json!({"name": value}), json!({"name": value}))) }
+    "user" => {
+      let person = find_person_in_db(value, db_conn).await?;
+      Ok((json!({"accountId": person.account_id}),
+          json!({"accountId": person.account_id, "displayName": person.display_name})))
+    }
+    "priority" | "resolution" => {
+      let table = if value_type == "priority" { "Priority" } else { "Resolution" };
+      let entry = find_in_catalogue(table, value, db_conn).await?;
+      let id = entry.jira_id.to_string();
+      Ok((json!({"id": id}), json!({"id": id, "name": entry.name})))
+    }
+    "status" => { Err(format!("Field {field_name} can't be edited, the status of a ticket changes through workflow transitions")) }
+    _ => { Err(format!("Field {field_name} can't be edited, values of type {value_type} aren't supported")) }
+  }
+}
+
+pub(crate) async fn get_field_value(field: FieldMetadata, value: &str, db_conn: &Pool<Sqlite>) -> Result<FieldValue, String> {
+  let schema = serde_json::from_str::<Value>(field.schema.as_str())
+    .map_err(|e| format!("Field {name} has an invalid schema in local database: {e}", name = field.human_name))?;
+  let get_schema_str = |name: &str| schema.get(name).and_then(|x| x.as_str()).unwrap_or_default();
+  let field_name = field.human_name.as_str();
+
+  let (for_jira, for_db) = match get_schema_str("type") {
+    "array" => {
+      let items_type = get_schema_str("items");
+      let mut for_jira = Vec::new();
+      let mut for_db = Vec::new();
+      for item in value.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+        // like labels
+        if items_type == "string" && item.contains(char::is_whitespace) {
+          return Err(format!("Values of field {field_name} can't contain spaces, got [{item}]"));
+        }
+        let (item_for_jira, item_for_db) = get_single_value(items_type, item, field_name, db_conn).await?;
+        for_jira.push(item_for_jira);
+        for_db.push(item_for_db);
+      }
+      (Value::Array(for_jira), Value::Array(for_db))
+    }
+    _ if value.trim().is_empty() => { (Value::Null, Value::Null) }
+    "string" if is_rich_text(&schema) => {
+      let adf = markdown_to_adf(value);
+      (adf.clone(), adf)
+    }
+    "string" => { (json!(value), json!(value)) }
+    value_type => { get_single_value(value_type, value.trim(), field_name, db_conn).await? }
+  };
+
+  Ok(FieldValue { field, for_jira, for_db })
+}
+
+#[derive(FromRow)]
+struct IssueId {
+  id: i64,
+}
+
+#[derive(FromRow)]
+struct IssueFieldValue {
+  field_value: String,
+}
+
+async fn get_issue_field_from_db(issue_id: i64, field_id: &str, db_conn: &Pool<Sqlite>) -> Result<Option<String>, String> {
+  sqlx::query_as::<_, IssueFieldValue>("SELECT field_value FROM IssueField WHERE issue_id = ? AND field_id = ?;")
+    .bind(issue_id)
+    .bind(field_id)
+    .fetch_optional(db_conn)
+    .await
+    .map(|x| x.map(|x| x.field_value))
+    .map_err(|e| format!("Error occurred while trying to get field {field_id} of issue with id {issue_id} from local database: {e}"))
+}
+
+// empty fields aren't stored, like when synchronising
+async fn store_issue_field_in_db(issue_key: &str, issue_id: i64, field_id: &str, value: Option<&str>, db_conn: &Pool<Sqlite>) {
+  let res = match value {
+    None => {
+      sqlx::query("DELETE FROM IssueField WHERE issue_id = ? AND field_id = ?;")
+        .bind(issue_id)
+        .bind(field_id)
+        .execute(db_conn)
+        .await
+    }
+    Some(value) => {
+      sqlx::query(
+        "INSERT INTO IssueField (issue_id, field_id, field_value) VALUES (?, ?, ?)
+         ON CONFLICT DO UPDATE SET field_value = excluded.field_value;")
+        .bind(issue_id)
+        .bind(field_id)
+        .bind(value)
+        .execute(db_conn)
+        .await
+    }
+  };
+
+  match res {
+    Ok(_) => {
+      publish_ticket_changes(TicketChanges {
+        updated: vec![UpdatedTicket { key: issue_key.to_string(), fields: vec![field_id.to_string()] }],
+        ..TicketChanges::default()
+      });
+    }
+    Err(e) => {
+      eprintln!("Error when storing field {field_id} of issue {issue_key} in local database. Err: {e:?}");
+    }
+  }
+}
+
+// stores the fields as jira returns them, for issues in the local database, and gives back their
+// json values. Status, priority and resolution are always refreshed since they are also indexed on
+// the Issue table, and may change along with other fields (e.g. during a transition).
+pub(crate) async fn refresh_issue_fields_from_server(config: &Config,
+                                                     issue_key: &str,
+                                                     issue_id: Option<i64>,
+                                                     field_ids: &[&str],
+                                                     db_conn: &Pool<Sqlite>) -> Result<Vec<(String, Option<String>)>, String> {
+  let mut fields_to_get = field_ids.to_vec();
+  for field_id in ["status", "priority", "resolution"] {
+    if !fields_to_get.contains(&field_id) {
+      fields_to_get.push(field_id);
+    }
+  }
+
+  let query = format!("/rest/api/3/issue/{issue_key}?fields={fields}", fields = fields_to_get.join(","));
+  let json_data = get_json_from_url(config, query.as_str())
+    .await
+    .map_err(|e| format!("Error: failed to get fields of issue {issue_key} back from the server. The next synchronisation will catch up. {e}"))?;
+
+  let mut res = Vec::new();
+  for field_id in field_ids {
+    let value = json_data
+      .get("fields")
+      .and_then(|x| x.get(*field_id))
+      .filter(|x| !x.is_null())
+      .map(|x| x.to_string());
+    if let Some(issue_id) = issue_id {
+      store_issue_field_in_db(issue_key, issue_id, field_id, value.as_deref(), db_conn).await;
+    }
+    res.push((field_id.to_string(), value));
+  }
+
+  if let Some(issue_id) = issue_id {
+    update_status_priority_and_resolution_of_issue_in_db(issue_key, issue_id as u32, &json_data, db_conn).await;
+  }
+  Ok(res)
+}
+
+pub(crate) async fn get_issue_id_from_db(issue_key: &str, db_conn: &Pool<Sqlite>) -> Option<i64> {
+  let issue_id = sqlx::query_as::<_, IssueId>("SELECT jira_id AS id FROM Issue WHERE key = ?;")
+    .bind(issue_key)
+    .fetch_optional(db_conn)
+    .await;
+  match issue_id {
+    Ok(v) => { v.map(|x| x.id) }
+    Err(e) => {
+      eprintln!("Error occurred while trying to get the id of issue {issue_key} from local database: {e}");
+      None
+    }
+  }
+}
+
+// the updated field of the issue isn't changed: the next synchronisation gets the rest of the
+// changes done by the server, like the history of the issue.
+pub(crate) async fn edit_field_of_issue(config: &Config, issue_key: &str, field_value: &FieldValue, db_conn: &Pool<Sqlite>) -> Result<KeyValueField, String> {
+  let field_id = field_value.field.jira_id.as_str();
+  let new_value = Some(field_value.for_db.to_string()).filter(|_| !field_value.for_db.is_null());
+
+  let issue_id = get_issue_id_from_db(issue_key, db_conn).await;
+
+  // issues which aren't synchronised are only edited on the server. Without the previous value,
+  // the local database couldn't be restored, it is then left untouched.
+  let previous_value = match issue_id {
+    None => { None }
+    Some(issue_id) => {
+      match get_issue_field_from_db(issue_id, field_id, db_conn).await {
+        Ok(v) => { Some((issue_id, v)) }
+        Err(e) => {
+          eprintln!("{e}");
+          None
+        }
+      }
+    }
+  };
+
+  if let Some((issue_id, _)) = previous_value {
+    store_issue_field_in_db(issue_key, issue_id, field_id, new_value.as_deref(), db_conn).await;
+  }
+
+  let body = json!({"fields": {field_id: field_value.for_jira}});
+  let query = format!("/rest/api/3/issue/{issue_key}");
+  if let Err(e) = put_json_to_url(config, query.as_str(), &body).await {
+    if let Some((issue_id, previous_value)) = previous_value {
+      store_issue_field_in_db(issue_key, issue_id, field_id, previous_value.as_deref(), db_conn).await;
+    }
+    return Err(format!("Error: failed to edit field {name} of issue {issue_key} on the server. {e}", name = field_value.field.human_name));
+  }
+
+  let value = match previous_value {
+    None => { new_value }
+    Some((issue_id, _)) => {
+      match refresh_issue_fields_from_server(config, issue_key, Some(issue_id), &[field_id], db_conn).await {
+        Ok(mut values) => { values.pop().and_then(|(_, value)| value) }
+        Err(e) => {
+          eprintln!("{e}");
+          new_value
+        }
+      }
+    }
+  };
+
+  Ok(KeyValueField {
+    key: field_id.to_string(),
+    name: field_value.field.human_name.clone(),
+    value: value.unwrap_or_else(|| Value::Null.to_string()),
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn dates() {
+    assert!(is_date("2024-01-31"));
+    assert!(!is_date(""));
+    assert!(!is_date("2024-1-31"));
+    assert!(!is_date("2024/01/31"));
+    assert!(!is_date("2024-01-31T17:30:00.000+0100"));
+    assert!(!is_date("31-01-2024"));
+  }
+
+  #[test]
+  fn date_times() {
+    assert!(is_date_time("2024-01-31T17:30:00.000+0100"));
+    assert!(is_date_time("2024-01-31T17:30"));
+    assert!(!is_date_time("2024-01-31"));
+    assert!(!is_date_time("2024-01-31T"));
+    assert!(!is_date_time("2024-01-31 17:30:00"));
+    assert!(!is_date_time("tomorrow at noon"));
+  }
+
+  #[test]
+  fn date_times_with_non_ascii_characters() {
+    assert!(!is_date_time("2024-01-3é17:30:00"));
+    assert!(!is_date_time("é2024-01-31T17:30:00"));
+  }
+}
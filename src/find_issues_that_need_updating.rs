@@ -1,5 +1,4 @@
 use std::cmp::Ordering::{Equal, Greater, Less};
-use crate::find_issues_that_need_updating::FoundIssueUpToDate::ONE_ISSUE_IS_UP_TO_DATE;
 use crate::get_config::Config;
 use crate::get_json_from_url::get_json_from_url;
 use crate::manage_issuelinktype_table::IssueLinkType;
@@ -11,15 +10,37 @@ use crate::get_issue_details::add_details_to_issue_in_db;
 use crate::get_project_tasks_from_server::get_project_tasks_from_server;
 use crate::manage_interesting_projects::{get_issue_links_from_json, Issue, IssueLink, update_issue_links_in_db, update_issues_in_db};
 use crate::manage_issue_field::{fill_issues_fields, fill_issues_fields_from_json, IssueProperties, KeyValueProperty};
+use crate::manage_synchronisation_table::{finish_synchronisation_of_project, get_current_timestamp, get_last_synchronisation_of_project};
+use crate::sync_progress::{get_number_of_pages, ProgressReporter};
 use crate::utils::get_str_without_surrounding_quotes;
 
+// the local clock and the jira server's clock may differ a bit. Fetching a few tickets twice
+// is harmless, they are then found to be up to date.
+const CLOCK_DIFFERENCE_MARGIN_IN_MINUTES: i64 = 5;
+
+// jql interprets absolute dates in the timezone of the jira user's profile, which isn't known
+// here. Relative dates like "-15m" don't depend on it.
+fn get_jql_for_updated_tickets(project_key: &str, last_synchronisation: Option<i64>) -> String {
+    match last_synchronisation {
+        None => {
+            format!("project%3D%22{project_key}%22+ORDER+BY+updated+ASC")
+        }
+        Some(last_synchronisation) => {
+            let elapsed_seconds = (get_current_timestamp() - last_synchronisation).max(0);
+            let minutes = elapsed_seconds / 60 + 1 + CLOCK_DIFFERENCE_MARGIN_IN_MINUTES;
+            format!("project%3D%22{project_key}%22+AND+updated+%3E%3D+%22-{minutes}m%22+ORDER+BY+updated+ASC")
+        }
+    }
+}
+
 async fn get_one_json(
     project_key: &str,
+    jql: &str,
     config: &Config,
     start: i64,
     max_result_per_query: i32,
 ) -> Result<JsonValue, String> {
-    let query = format!("/rest/api/3/search?jql=project%3D%22{project_key}%22+ORDER+BY+updated+DESC&startAt={start}&maxResults={max_result_per_query}");
+    let query = format!("/rest/api/3/search?jql={jql}&startAt={start}&maxResults={max_result_per_query}");
     let json_data = get_json_from_url(config, query.as_str()).await;
     let Ok(json_data) = json_data else {
         return Err(format!(
@@ -44,20 +65,11 @@ pub struct issue_and_links {
     links: Vec<IssueLink>,
 }
 
-#[derive(PartialEq, Debug)]
-enum FoundIssueUpToDate {
-    NO_ISSUE_IS_UP_TO_DATE,
-    ONE_ISSUE_IS_UP_TO_DATE,
-}
-
-// returns a list of issues and links plus a boolean indicating if there might be more issues to update.
-// In other words, the boolean tells if it found an issue which is already up-to-date.
-// in such case, since loading must be done from oldest to newest, any further issue should
-// normally be up-to-date already
+// returns the issues and links of the json which aren't up-to-date in the local database.
 async fn get_issues_and_link_from_json_that_need_updating(
     json_data: &Value,
     db_conn: &Pool<Sqlite>,
-) -> Result<(issue_and_links, FoundIssueUpToDate), String> {
+) -> Result<issue_and_links, String> {
     let links = get_issue_links_from_json(json_data);
     let links = match links {
         Ok(v) => {v}
@@ -156,17 +168,9 @@ async fn get_issues_and_link_from_json_that_need_updating(
                     a = timestamp_to_use,
                     b = issue.last_updated
                 );
-                if timestamp_to_use == issue.last_updated {
-                    return Ok((
-                        issue_and_links {
-                            issues: issues_to_update,
-                            links,
-                        },
-
-                        FoundIssueUpToDate::ONE_ISSUE_IS_UP_TO_DATE,
-                    ));
+                if timestamp_to_use != issue.last_updated {
+                    issues_to_update.push(issue)
                 }
-                issues_to_update.push(issue)
             }
             Ok(None) => {
                 eprintln!("Ticket is not in database yet");
@@ -177,28 +181,29 @@ async fn get_issues_and_link_from_json_that_need_updating(
             }
         }
     }
-    Ok((issue_and_links {
+    Ok(issue_and_links {
         issues: issues_to_update,
         links,
-    }, FoundIssueUpToDate::NO_ISSUE_IS_UP_TO_DATE))
+    })
 }
 
-// this here assumes that we load new tickets or update existing ones from oldest to newest.
-// Consequently, if PROJ-AAA is in database and up to date, so shall be PROJ-BBB
-// for all BBB that are lower than AAA. The consequence here is that we can stop looking
-// for tickets which are out of date as soon as we find out where its update time
-// on the server matches its update time field on the local database
+// Only queries the tickets updated since the last synchronisation of the project (all of them
+// when it was never synchronised). When a ticket got refreshed on its own in the meantime, it
+// is simply found to be up to date.
 async fn get_issues_and_links_that_need_updating(
     project_key: &str,
     config: &Config,
     db_conn: &Pool<Sqlite>,
+    progress: &mut ProgressReporter,
+    last_synchronisation: Option<i64>,
 ) -> Result<issue_and_links, String> {
     eprintln!(
         "Querying issues/tasks for project {project_key} in search of tickets that need updating"
     );
+    let jql = get_jql_for_updated_tickets(project_key, last_synchronisation);
     let max_result_per_query = -1; // -1 is a special value telling jira "no limit"
                                    // the returned json will tell us what is the configured limit
-    let first_json = get_one_json(project_key, &config, 0, max_result_per_query).await;
+    let first_json = get_one_json(project_key, jql.as_str(), &config, 0, max_result_per_query).await;
     let Ok(first_json) = first_json else {
         return Err(first_json.err().unwrap());
     };
@@ -219,6 +224,9 @@ async fn get_issues_and_links_that_need_updating(
         .and_then(|x| x.get("total"))
         .and_then(|x| x.as_i64());
 
+    let nr_pages = get_number_of_pages(total, max_result_per_query);
+    progress.pages(1, nr_pages).await;
+
     let first_issues_to_update =
         get_issues_and_link_from_json_that_need_updating(&first_json, db_conn).await;
     let first_issues_to_update = match first_issues_to_update {
@@ -228,11 +236,8 @@ async fn get_issues_and_links_that_need_updating(
             return Err(e);
         }
     };
-    if first_issues_to_update.1 == ONE_ISSUE_IS_UP_TO_DATE {
-        return Ok(first_issues_to_update.0);
-    }
 
-    let mut res = first_issues_to_update.0;
+    let mut res = first_issues_to_update;
 
     let Some(total) = total else {
         return Ok(res);
@@ -242,25 +247,21 @@ async fn get_issues_and_links_that_need_updating(
         return Ok(res);
     }
 
-    for i in 0..(total / max_result_per_query) {
+    for i in 0..(nr_pages as i64 - 1) {
         let start = max_result_per_query * (i + 1);
         eprintln!(
             "Querying issues/tasks starting from {start} out of {total} for project {project_key}"
         );
-        let next_json = get_one_json(project_key, config, start, max_result_per_query as i32).await;
+        let next_json = get_one_json(project_key, jql.as_str(), config, start, max_result_per_query as i32).await;
+        progress.pages(i as usize + 2, nr_pages).await;
         match next_json {
             Ok(next_json) => {
                 let new_issues_to_update =
                     get_issues_and_link_from_json_that_need_updating(&next_json, db_conn).await;
                 match new_issues_to_update {
-                    Ok(mut v) => {
-                        let mut issues_and_links_from_this_json = v.0;
+                    Ok(mut issues_and_links_from_this_json) => {
                         res.links.append(&mut issues_and_links_from_this_json.links);
                         res.issues.append(&mut issues_and_links_from_this_json.issues);
-
-                        if v.1 == ONE_ISSUE_IS_UP_TO_DATE {
-                            return Ok(res);
-                        }
                     }
                     Err(e) => {
                         return Err(e);
@@ -277,8 +278,11 @@ async fn get_issues_and_links_that_need_updating(
 }
 
 
-async fn update_given_project_in_db(config: Config, project_key: String, mut db_conn: Pool<Sqlite>) {
-    let issues_and_links_to_update = get_issues_and_links_that_need_updating(project_key.as_str(), &config, &db_conn).await;
+async fn update_given_project_in_db(config: Config, project_key: String, mut db_conn: Pool<Sqlite>, mut progress: ProgressReporter) {
+    let synchronisation_start = get_current_timestamp();
+    let last_synchronisation = get_last_synchronisation_of_project(project_key.as_str(), &db_conn).await;
+    let issues_and_links_to_update = get_issues_and_links_that_need_updating(project_key.as_str(), &config, &db_conn, &mut progress, last_synchronisation).await;
+    progress.all_pages_fetched();
 
     if let Ok(issues_and_links_to_update) = issues_and_links_to_update {
         // First insert all issues in the db, and then insert the links between issues.
@@ -339,22 +343,58 @@ async fn update_given_project_in_db(config: Config, project_key: String, mut db_
           .map(|x| x.key.as_str())
           .collect::<Vec<_>>();
 
-        for key in issues_keys {
-            add_details_to_issue_in_db(&config,
-                                       key,
-                                       &mut db_conn).await
+        let nr_tickets = issues_keys.len();
+        progress.tickets(0, nr_tickets).await;
+        let mut failed_keys = Vec::new();
+        for (i, key) in issues_keys.into_iter().enumerate() {
+            if let Err(e) = add_details_to_issue_in_db(&config, key, &mut db_conn).await {
+                eprintln!("{e}");
+                failed_keys.push(key);
+            }
+            progress.tickets(i + 1, nr_tickets).await;
         }
+
+        finish_synchronisation_of_project(project_key.as_str(), synchronisation_start, failed_keys.as_slice(), &db_conn).await;
     }
 }
 
-pub(crate) async fn update_interesting_projects_in_db(config: &Config, db_conn: &Pool<Sqlite>) {
+pub(crate) async fn update_interesting_projects_in_db(config: &Config, db_conn: &Pool<Sqlite>, progress: &ProgressReporter) {
     let interesting_projects = config.interesting_projects();
 
     let mut tasks = interesting_projects
       .iter()
-      .map(|x| tokio::spawn(update_given_project_in_db(config.clone(), x.clone(), db_conn.clone())))
+      .map(|x| tokio::spawn(update_given_project_in_db(config.clone(), x.clone(), db_conn.clone(), progress.for_project(x))))
       .collect::<JoinSet<_>>();
 
     while let Some(res) = tasks.join_next().await {
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jql_without_previous_synchronisation() {
+        assert_eq!(get_jql_for_updated_tickets("PROJ", None), "project%3D%22PROJ%22+ORDER+BY+updated+ASC");
+    }
+
+    #[test]
+    fn jql_with_previous_synchronisation() {
+        // 2 minutes elapsed, plus 1 minute of rounding and the clock difference margin
+        let two_minutes_ago = get_current_timestamp() - 120;
+        assert_eq!(
+            get_jql_for_updated_tickets("PROJ", Some(two_minutes_ago)),
+            "project%3D%22PROJ%22+AND+updated+%3E%3D+%22-8m%22+ORDER+BY+updated+ASC"
+        );
+    }
+
+    #[test]
+    fn jql_with_synchronisation_in_the_future() {
+        let in_an_hour = get_current_timestamp() + 3600;
+        assert_eq!(
+            get_jql_for_updated_tickets("PROJ", Some(in_an_hour)),
+            "project%3D%22PROJ%22+AND+updated+%3E%3D+%22-6m%22+ORDER+BY+updated+ASC"
+        );
+    }
+}
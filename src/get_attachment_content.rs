@@ -7,6 +7,10 @@ use reqwest::Response;
 use tempfile;
 use toml::to_string;
 use crate::get_config::Config;
+use crate::jira_http_client::send_request_to_jira;
+
+// attachments can be big, the usual timeout of requests to the jira server is too short for them
+const ATTACHMENT_DOWNLOAD_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(600);
 
 #[derive(FromRow)]
 struct cookie_expiration {
@@ -114,26 +118,20 @@ async fn download_url(attachment_id: i64, config: &Config, cookie: &str) -> file
     let server = config.server_address();
     let url = format!("{server}/rest/api/3/attachment/content/{attachment_id}");
 
-    let client = reqwest::Client::new();
-    let response = client.get(url.as_str())
-      .header("Cookie", format!("tenant.session.token={cookie}"))
-      .send()
-      .await;
+    let response = send_request_to_jira(config, |client| {
+        client.get(url.as_str())
+          .header("Cookie", format!("tenant.session.token={cookie}"))
+          .timeout(ATTACHMENT_DOWNLOAD_TIMEOUT)
+    }).await;
 
     let response = match response {
         Ok(v) => {v}
         Err(e) => {
-            eprintln!("Error while downloading content for attachment with id {attachment_id}: {}", e.to_string());
+            eprintln!("Error while downloading content for attachment with id {attachment_id}: {e}");
             return file_data{ uuid: None, bytes: None };
         }
     };
 
-    if !response.status().is_success() {
-        eprintln!("Error while downloading content for attachment with id {attachment_id}. Returned status code is {x}",
-        x=response.status().to_string());
-        return file_data{ uuid: None, bytes: None };
-    }
-
     let path = response.url().path();
     let splitted_path = path
       .split('/')
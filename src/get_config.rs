@@ -15,6 +15,12 @@ struct FileOnDiskConfig {
     interesting_projects: Option<Vec<String>>,
     max_file_size_to_download: Option<i64>,
     mozilla_cookies_db: Option<std::path::PathBuf>,
+    unix_socket: Option<std::path::PathBuf>,
+    http_api: Option<std::net::SocketAddr>,
+    offline: Option<bool>,
+    max_concurrent_requests: Option<usize>,
+    max_requests_per_second: Option<f64>,
+    request_timeout_in_seconds: Option<u64>,
 }
 
 
@@ -27,6 +33,12 @@ pub(crate) struct Config {
     local_database: std::path::PathBuf,
     interesting_projects: Vec<String>,
     mozilla_cookies_db: Option<std::path::PathBuf>,
+    unix_socket: Option<std::path::PathBuf>, // listen on this socket instead of stdin/stdout when set
+    http_api: Option<std::net::SocketAddr>, // serve an http api on this loopback address instead of stdin/stdout when set
+    offline: bool, // start in offline mode: answer from the local database only, without synchronising
+    max_concurrent_requests: usize, // requests to the jira server in flight at the same time
+    max_requests_per_second: f64, // requests sent to the jira server per second, spread evenly
+    request_timeout: std::time::Duration, // for one request to the jira server, retries not included
 }
 
 impl Config {
@@ -49,6 +61,12 @@ impl Config {
         &self.auth_token
     }
     pub fn get_mozilla_cookies_db(&self) -> &Option<std::path::PathBuf> { &self.mozilla_cookies_db }
+    pub fn unix_socket(&self) -> &Option<std::path::PathBuf> { &self.unix_socket }
+    pub fn http_api(&self) -> &Option<std::net::SocketAddr> { &self.http_api }
+    pub fn offline(&self) -> bool { self.offline }
+    pub fn max_concurrent_requests(&self) -> usize { self.max_concurrent_requests }
+    pub fn max_requests_per_second(&self) -> f64 { self.max_requests_per_second }
+    pub fn request_timeout(&self) -> std::time::Duration { self.request_timeout }
 }
 
 fn api_token_from_env() -> Result<String, String> {
@@ -95,6 +113,34 @@ pub(crate) fn get_config(filepath: &std::path::Path) -> Result<Config, String> {
     };
 
     let mozilla_cookies_db = conf.mozilla_cookies_db;
+    let unix_socket = conf.unix_socket;
+
+    let http_api = conf.http_api;
+    if let Some(address) = http_api {
+        // the api gives access to the whole local database without any authentication.
+        if !address.ip().is_loopback() {
+            return Err(format!("http_api must be a loopback address (like 127.0.0.1:8080). Got {address}"));
+        }
+    }
+    if unix_socket.is_some() && http_api.is_some() {
+        return Err(String::from("unix_socket and http_api can't be both set. The server serves only one of them"));
+    }
+
+    let offline = conf.offline.unwrap_or(false);
+
+    let max_concurrent_requests = conf.max_concurrent_requests.unwrap_or(defaults::DEFAULT_MAX_CONCURRENT_REQUESTS);
+    if max_concurrent_requests == 0 {
+        return Err(String::from("max_concurrent_requests must be at least 1"));
+    }
+    let max_requests_per_second = conf.max_requests_per_second.unwrap_or(defaults::DEFAULT_MAX_REQUESTS_PER_SECOND);
+    if max_requests_per_second.is_nan() || max_requests_per_second <= 0.0 {
+        return Err(format!("max_requests_per_second must be strictly positive. Got {max_requests_per_second}"));
+    }
+    let request_timeout_in_seconds = conf.request_timeout_in_seconds.unwrap_or(defaults::DEFAULT_REQUEST_TIMEOUT_IN_SECONDS);
+    if request_timeout_in_seconds == 0 {
+        return Err(String::from("request_timeout_in_seconds must be at least 1"));
+    }
+    let request_timeout = std::time::Duration::from_secs(request_timeout_in_seconds);
 
     let server_address = conf.server_address;
     let user_login = conf.user_login;
@@ -107,7 +153,13 @@ pub(crate) fn get_config(filepath: &std::path::Path) -> Result<Config, String> {
         local_database,
         interesting_projects,
         auth_token,
-        mozilla_cookies_db
+        mozilla_cookies_db,
+        unix_socket,
+        http_api,
+        offline,
+        max_concurrent_requests,
+        max_requests_per_second,
+        request_timeout,
     };
 
     Ok(conf)
@@ -5,6 +5,13 @@ use crate::get_config::Config;
 use crate::get_json_from_url::get_json_from_url;
 use crate::manage_interesting_projects::{get_id, Issue};
 use crate::manage_issue_comments::add_comments_for_issue_into_db;
+use crate::manage_issue_hierarchy::update_hierarchy_of_issue_in_db;
+use crate::manage_issue_history::add_history_for_issue_into_db;
+use crate::manage_issue_transitions::add_transitions_for_issue_into_db;
+use crate::manage_issue_watchers::add_watchers_and_voters_for_issue_into_db;
+use crate::manage_issue_worklogs::add_worklogs_for_issue_into_db;
+use crate::manage_status_priority_resolution_tables::update_status_priority_and_resolution_of_issue_in_db;
+use crate::manage_versions_and_components::update_versions_and_components_of_issue_in_db;
 use crate::manage_project_table::Project;
 use crate::utils::{get_inputs_in_db_not_in_remote, get_inputs_in_remote_not_in_db};
 use html2text::parse;
@@ -17,6 +24,7 @@ use std::fmt::format;
 use std::io::Read;
 use std::num::ParseIntError;
 use crate::find_issues_that_need_updating::issue_data;
+use crate::ticket_events::{publish_ticket_changes, TicketChanges, UpdatedTicket};
 
 pub(crate) async fn get_json_for_issue(config: &Config, issue_key: &str) -> Result<JsonValue, String> {
     let query = format!("/rest/api/3/issue/{issue_key}");
@@ -30,6 +38,15 @@ pub(crate) async fn get_json_for_issue(config: &Config, issue_key: &str) -> Resu
     Ok(json_data)
 }
 
+// the changelog is needed when synchronising the whole ticket. Asking for it along with the
+// fields saves a request per ticket.
+async fn get_json_for_issue_with_changelog(config: &Config, issue_key: &str) -> Result<JsonValue, String> {
+    let query = format!("/rest/api/3/issue/{issue_key}?expand=changelog");
+    get_json_from_url(config, query.as_str())
+        .await
+        .map_err(|e| format!("Error: failed to get detail for issue {issue_key} from server.\n{e}"))
+}
+
 async fn get_properties_from_json(
     issue_key: &str,
     json_data: &Value,
@@ -154,9 +171,26 @@ async fn update_properties_in_db_for_issue(
     // dbg!(&issue_properties_to_insert);
     // dbg!(&issue_properties_to_remove);
 
-    match issue_properties_to_remove.is_empty() {
+    let mut changed_fields = issue_properties_to_remove
+        .iter()
+        .chain(issue_properties_to_insert.iter())
+        .map(|x| x.key.clone())
+        .collect::<Vec<_>>();
+    changed_fields.sort();
+    changed_fields.dedup();
+    let changes = match (issue_properties_in_db.is_empty(), changed_fields.is_empty()) {
+        (_, true) => TicketChanges::default(),
+        (true, false) => TicketChanges { created: vec![issue_key.to_string()], ..TicketChanges::default() },
+        (false, false) => TicketChanges {
+            updated: vec![UpdatedTicket { key: issue_key.to_string(), fields: changed_fields }],
+            ..TicketChanges::default()
+        },
+    };
+
+    let is_removal_successful = match issue_properties_to_remove.is_empty() {
         true => {
-            eprintln!("No properties for issue {issue_key} (issue_id: {issue_id}) found in local db that isn't also in remote")
+            eprintln!("No properties for issue {issue_key} (issue_id: {issue_id}) found in local db that isn't also in remote");
+            true
         }
         false => {
             let query_str =
@@ -194,12 +228,14 @@ async fn update_properties_in_db_for_issue(
             } else {
                 eprintln!("updated Issue properties in database: {row_affected} rows were deleted")
             }
+            !has_error
         }
-    }
+    };
 
-    match issue_properties_to_insert.is_empty() {
+    let is_insertion_successful = match issue_properties_to_insert.is_empty() {
         true => {
-          eprintln!("No new property (or changed) for issue {issue_key} ((issue_id: {issue_id}) found in remote")
+          eprintln!("No new property (or changed) for issue {issue_key} ((issue_id: {issue_id}) found in remote");
+          true
         }
         false => {
             let query_str = "INSERT INTO IssueField (issue_id, field_id, field_value)
@@ -238,7 +274,12 @@ async fn update_properties_in_db_for_issue(
             } else {
                 eprintln!("updated Issue properties for issue {issue_key} (issue_id: {issue_id}) in database: {row_affected} rows were updated")
             }
+            !has_error
         }
+    };
+
+    if is_removal_successful && is_insertion_successful {
+        publish_ticket_changes(changes);
     }
 }
 
@@ -740,16 +781,11 @@ pub(crate) async fn add_details_to_issue_in_db(
     config: &Config,
     issue_key: &str,
     db_conn: &Pool<Sqlite>,
-) {
-    let json = get_json_for_issue(&config, issue_key).await;
-    let Ok(json) = json else {
-        eprintln!("{}\n", json.err().unwrap());
-        return;
-    };
+) -> Result<(), String> {
+    let json = get_json_for_issue_with_changelog(config, issue_key).await?;
     let issue_id = get_id(&json);
     let Some(issue_id) = issue_id else {
-        eprintln!("error: the json data for {issue_key} does not contain an \"id\" field");
-        return;
+        return Err(format!("error: the json data for {issue_key} does not contain an \"id\" field"));
     };
 
     {
@@ -769,13 +805,21 @@ pub(crate) async fn add_details_to_issue_in_db(
             &mut db_conn_for_update_attachment,
         )
         .await;
-        tokio::join!(
+        let (_, comments, history, worklogs, watchers, transitions, _, _, _) = tokio::join!(
             download_attachments_for_missing_content(
                 &config,
                 issue_id,
                 &mut db_conn_for_download_attachment,
             ),
-            add_comments_for_issue_into_db(&config, issue_id, &mut db_conn_for_comment)
+            add_comments_for_issue_into_db(&config, issue_id, &mut db_conn_for_comment),
+            add_history_for_issue_into_db(config, issue_key, issue_id, &json, db_conn),
+            add_worklogs_for_issue_into_db(config, issue_key, issue_id, &json, db_conn),
+            add_watchers_and_voters_for_issue_into_db(config, issue_key, issue_id, &json, db_conn),
+            add_transitions_for_issue_into_db(config, issue_key, issue_id, db_conn),
+            update_versions_and_components_of_issue_in_db(issue_key, issue_id, &json, db_conn),
+            update_status_priority_and_resolution_of_issue_in_db(issue_key, issue_id, &json, db_conn),
+            update_hierarchy_of_issue_in_db(issue_key, issue_id, &json, db_conn)
         );
+        comments.and(history).and(worklogs).and(watchers).and(transitions)
     }
 }
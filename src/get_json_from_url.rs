@@ -1,30 +1,99 @@
-use base64::Engine;
+use serde_json::Value;
 use sqlx::types::JsonValue;
 use crate::get_config::Config;
+use crate::jira_http_client::{send_non_idempotent_request_to_jira, send_request_to_jira, HttpError};
 
-pub(crate) async fn get_json_from_url(conf: &Config, get_part: &str) -> Result<JsonValue, String> {
+const MAX_RESULTS_PER_PAGE: i64 = 50;
+
+pub(crate) async fn get_json_from_url(conf: &Config, get_part: &str) -> Result<JsonValue, HttpError> {
     let url = format!("{server}/{query}", server = conf.server_address(), query = get_part);
     let auth_token = conf.auth_token();
 
-    let client = reqwest::Client::new();
-    let response = client.get(url.as_str())
-        .header("Authorization", format!("Basic {auth_token}"))
-        .header("Accept", "application/json")
-        .header("Content-Type", "application/json")
-        .send()
-        .await;
-
-    let Ok(response) = response else {
-        return Err(format!("Error: failed to get projects. Msg={e}", e = response.err().unwrap().to_string()));
-    };
-
-    let Ok(text) = response.text().await else {
-        return Err("Error: failed to get text out of response".to_string());
-    };
-
-    let json_data = serde_json::from_str::<serde_json::Value>(text.as_str());
-    match json_data {
-        Ok(v) => Ok(v),
-        Err(e) => Err(format!("Error: Failed to parse response as json. Text is [{e}]")),
+    let response = send_request_to_jira(conf, |client| {
+        client.get(url.as_str())
+            .header("Authorization", format!("Basic {auth_token}"))
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json")
+    }).await?;
+
+    let text = response.text().await?;
+
+    serde_json::from_str::<serde_json::Value>(text.as_str())
+        .map_err(|e| HttpError::InvalidJson(e.to_string()))
+}
+
+pub(crate) async fn post_json_to_url(conf: &Config, post_part: &str, body: &JsonValue) -> Result<JsonValue, HttpError> {
+    let url = format!("{server}/{query}", server = conf.server_address(), query = post_part);
+    let auth_token = conf.auth_token();
+    let body = body.to_string();
+
+    let response = send_non_idempotent_request_to_jira(conf, |client| {
+        client.post(url.as_str())
+            .header("Authorization", format!("Basic {auth_token}"))
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json")
+            .body(body.clone())
+    }).await?;
+
+    // some requests, like transitions, are answered with 204 No Content
+    let text = response.text().await?;
+    if text.is_empty() {
+        return Ok(JsonValue::Null);
+    }
+
+    serde_json::from_str::<serde_json::Value>(text.as_str())
+        .map_err(|e| HttpError::InvalidJson(e.to_string()))
+}
+
+// jira answers edits with 204 No Content. Setting a field to the same value twice is harmless, the
+// request is retried like the ones reading data.
+pub(crate) async fn put_json_to_url(conf: &Config, put_part: &str, body: &JsonValue) -> Result<(), HttpError> {
+    let url = format!("{server}/{query}", server = conf.server_address(), query = put_part);
+    let auth_token = conf.auth_token();
+    let body = body.to_string();
+
+    send_request_to_jira(conf, |client| {
+        client.put(url.as_str())
+            .header("Authorization", format!("Basic {auth_token}"))
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json")
+            .body(body.clone())
+    }).await?;
+
+    Ok(())
+}
+
+// paginated lists either tell when the last page is reached, like the boards and sprints of the
+// agile api, or come along with their total, like issues and create metadata.
+// The error of the jira server is returned as is, so that callers can tell refusals they expect
+// apart from failures.
+pub(crate) async fn get_all_pages_from_server(config: &Config, query: &str, list_name: &str) -> Result<Vec<Value>, HttpError> {
+    let mut res = Vec::new();
+    let mut start = 0;
+    loop {
+        let separator = if query.contains('?') { '&' } else { '?' };
+        let paginated_query = format!("{query}{separator}startAt={start}&maxResults={MAX_RESULTS_PER_PAGE}");
+        let json = get_json_from_url(config, paginated_query.as_str()).await?;
+
+        let Some(values) = json.get(list_name).and_then(|x| x.as_array()) else {
+            return Err(HttpError::UnexpectedJson(format!("Reply to {query} doesn't contain a list named {list_name}")));
+        };
+        res.extend(values.iter().cloned());
+
+        start += values.len() as i64;
+        let is_last = json
+            .get("isLast")
+            .and_then(|x| x.as_bool());
+        let total = json
+            .get("total")
+            .and_then(|x| x.as_i64());
+        let is_done = match (is_last, total) {
+            (Some(is_last), _) => { is_last }
+            (None, Some(total)) => { start >= total }
+            (None, None) => { true }
+        };
+        if is_done || values.is_empty() {
+            return Ok(res);
+        }
     }
-}
\ No newline at end of file
+}
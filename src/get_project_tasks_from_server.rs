@@ -2,6 +2,7 @@ use crate::get_config::Config;
 use crate::get_json_from_url::get_json_from_url;
 use serde_json::{Map, Value};
 use sqlx::types::JsonValue;
+use crate::sync_progress::{get_number_of_pages, ProgressReporter};
 
 async fn get_one_json(
     project_key: String,
@@ -23,6 +24,7 @@ async fn get_one_json(
 pub(crate) async fn get_project_tasks_from_server(
     project_key: &str,
     config: &Config,
+    progress: &mut ProgressReporter,
 ) -> Result<Vec<JsonValue>, String> {
     eprintln!("Querying issues/tasks for project {project_key} starting from 0");
     let max_result_per_query = -1; // -1 is a special value telling jira "no limit"
@@ -61,6 +63,10 @@ pub(crate) async fn get_project_tasks_from_server(
 
     let mut res: Vec<_> = vec![first_json];
 
+    let nr_pages = get_number_of_pages(total, max_result_per_query);
+    let mut nr_pages_fetched = 1;
+    progress.pages(nr_pages_fetched, nr_pages).await;
+
     let Some(total) = total else {
         return Ok(res);
     };
@@ -69,8 +75,7 @@ pub(crate) async fn get_project_tasks_from_server(
         return Ok(res);
     }
 
-    let mut handles = (0..(total / max_result_per_query))
-      .into_iter()
+    let mut handles = (0..(nr_pages as i64 - 1))
       .map(|i| {
           let start = max_result_per_query * (i + 1);
           eprintln!("Querying issues/tasks starting from {start} out of {total} for project {project_key}");
@@ -79,6 +84,8 @@ pub(crate) async fn get_project_tasks_from_server(
       .collect::<tokio::task::JoinSet<_>>();
 
     while let Some(v) = handles.join_next().await {
+        nr_pages_fetched += 1;
+        progress.pages(nr_pages_fetched, nr_pages).await;
         match v {
             Ok(Ok(Ok(v))) => {
                 res.push(v)
@@ -0,0 +1,617 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::body::{Bytes, Incoming};
+use hyper::header::{HeaderMap, HeaderValue, CONTENT_DISPOSITION, CONTENT_SECURITY_POLICY, CONTENT_TYPE, HOST, ORIGIN, X_CONTENT_TYPE_OPTIONS};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde_json::{json, Map, Value};
+use sqlx::{Pool, Sqlite};
+use tokio::net::TcpListener;
+use tokio::task::JoinSet;
+use crate::get_config::Config;
+use crate::json_protocol::result_data_to_json;
+use crate::server::{log_task_failure, serve_request, ErrorCode, Reply, ReplyKind, Request, RequestKind, ResultData};
+use crate::srv_add_comment::AddCommentParams;
+use crate::srv_create_issue::CreateIssueParams;
+use crate::srv_edit_field::EditFieldParams;
+use crate::srv_transition::TransitionParams;
+
+const X_REQUESTED_WITH: &str = "x-requested-with";
+const MAX_BODY_LENGTH: usize = 1024 * 1024; // more than enough for a comment or a field value
+
+// The http api is a thin layer over the text protocol: each http request is turned into the
+// equivalent command, served by the same handlers, and the replies are converted to json.
+//
+//   GET  /hello                      -> HELLO
+//   GET  /capabilities               -> CAPABILITIES
+//   GET  /tickets                    -> FETCH_TICKET_LIST
+//   GET  /tickets/statuses           -> FETCH_TICKET_STATUS_LIST
+//   GET  /tickets/watched            -> FETCH_WATCHED_TICKETS
+//   GET  /tickets/<key>?format=<fmt> -> FETCH_TICKET (format defaults to HTML)
+//   GET  /tickets/<key>/fields       -> FETCH_TICKET_KEY_VALUE_FIELDS
+//   GET  /tickets/<key>/history      -> FETCH_TICKET_HISTORY
+//   GET  /tickets/<key>/attachments  -> FETCH_ATTACHMENT_LIST_FOR_TICKET
+//   GET  /tickets/<key>/watchers     -> FETCH_TICKET_WATCHERS
+//   GET  /tickets/<key>/voters       -> FETCH_TICKET_VOTERS
+//   GET  /tickets/<key>/tree         -> FETCH_TICKET_TREE
+//   GET  /attachments/<uuid>         -> FETCH_ATTACHMENT_CONTENT (raw bytes)
+//   GET  /logged-time?from=<day>&to=<day> -> FETCH_LOGGED_TIME
+//   GET  /boards                     -> FETCH_BOARD_LIST
+//   GET  /boards/<id>/sprints        -> FETCH_BOARD_SPRINTS
+//   GET  /sprints/<id>/tickets       -> FETCH_SPRINT_TICKETS
+//   GET  /projects/<key>/versions    -> FETCH_PROJECT_VERSIONS
+//   GET  /versions/<id>/tickets      -> FETCH_VERSION_TICKETS
+//   GET  /projects/<key>/components  -> FETCH_PROJECT_COMPONENTS
+//   GET  /components/<id>/tickets    -> FETCH_COMPONENT_TICKETS
+//   POST /tickets/<key>/comments     -> ADD_COMMENT (markdown in the body)
+//   PUT  /tickets/<key>/fields/<field> -> EDIT_FIELD (value in the body)
+//   GET  /tickets/<key>/transitions  -> FETCH_TRANSITIONS
+//   POST /tickets/<key>/transitions/<transition> -> TRANSITION (fields as a json object in the body)
+//   POST /projects/<key>/tickets     -> CREATE_ISSUE (issue type, summary, description and fields as a json object in the body)
+//   POST /tickets/<key>/synchronise  -> SYNCHRONISE_TICKET
+//   POST /synchronise/updated        -> SYNCHRONISE_UPDATED
+//   POST /synchronise/all            -> SYNCHRONISE_ALL
+//   POST /offline-mode/on            -> SET_OFFLINE_MODE ON
+//   POST /offline-mode/off           -> SET_OFFLINE_MODE OFF
+fn get_command(method: &Method, path: &str, query: Option<&str>) -> Option<(&'static str, Vec<String>)> {
+  let path = path
+    .trim_matches('/')
+    .split('/')
+    .collect::<Vec<_>>();
+
+  let get_query_parameter = |parameter_name: &str| {
+    query
+      .unwrap_or("")
+      .split('&')
+      .filter_map(|x| x.split_once('='))
+      .find(|(name, _)| *name == parameter_name)
+      .map(|(_, value)| percent_decode(value))
+  };
+  let format = get_query_parameter("format").unwrap_or_else(|| String::from("HTML"));
+
+  let res = match (method, path.as_slice()) {
+    (&Method::GET, ["hello"]) => ("HELLO", Vec::new()),
+    (&Method::GET, ["capabilities"]) => ("CAPABILITIES", Vec::new()),
+    (&Method::GET, ["tickets"]) => ("FETCH_TICKET_LIST", Vec::new()),
+    (&Method::GET, ["tickets", "statuses"]) => ("FETCH_TICKET_STATUS_LIST", Vec::new()),
+    (&Method::GET, ["tickets", "watched"]) => ("FETCH_WATCHED_TICKETS", Vec::new()),
+    (&Method::GET, ["tickets", key]) => ("FETCH_TICKET", vec![key.to_string(), format]),
+    (&Method::GET, ["tickets", key, "fields"]) => ("FETCH_TICKET_KEY_VALUE_FIELDS", vec![key.to_string()]),
+    (&Method::GET, ["tickets", key, "history"]) => ("FETCH_TICKET_HISTORY", vec![key.to_string()]),
+    (&Method::GET, ["tickets", key, "attachments"]) => ("FETCH_ATTACHMENT_LIST_FOR_TICKET", vec![key.to_string()]),
+    (&Method::GET, ["tickets", key, "watchers"]) => ("FETCH_TICKET_WATCHERS", vec![key.to_string()]),
+    (&Method::GET, ["tickets", key, "voters"]) => ("FETCH_TICKET_VOTERS", vec![key.to_string()]),
+    (&Method::GET, ["tickets", key, "tree"]) => ("FETCH_TICKET_TREE", vec![key.to_string()]),
+    (&Method::GET, ["attachments", uuid]) => ("FETCH_ATTACHMENT_CONTENT", vec![uuid.to_string()]),
+    (&Method::GET, ["logged-time"]) => {
+      let first_day = get_query_parameter("from").unwrap_or_default();
+      let last_day = get_query_parameter("to").unwrap_or_default();
+      ("FETCH_LOGGED_TIME", vec![first_day, last_day])
+    }
+    (&Method::GET, ["boards"]) => ("FETCH_BOARD_LIST", Vec::new()),
+    (&Method::GET, ["boards", board_id, "sprints"]) => ("FETCH_BOARD_SPRINTS", vec![board_id.to_string()]),
+    (&Method::GET, ["sprints", sprint_id, "tickets"]) => ("FETCH_SPRINT_TICKETS", vec![sprint_id.to_string()]),
+    (&Method::GET, ["projects", project_key, "versions"]) => ("FETCH_PROJECT_VERSIONS", vec![project_key.to_string()]),
+    (&Method::GET, ["versions", version_id, "tickets"]) => ("FETCH_VERSION_TICKETS", vec![version_id.to_string()]),
+    (&Method::GET, ["projects", project_key, "components"]) => ("FETCH_PROJECT_COMPONENTS", vec![project_key.to_string()]),
+    (&Method::GET, ["components", component_id, "tickets"]) => ("FETCH_COMPONENT_TICKETS", vec![component_id.to_string()]),
+    (&Method::POST, ["tickets", key, "comments"]) => ("ADD_COMMENT", vec![key.to_string()]),
+    (&Method::PUT, ["tickets", key, "fields", field]) => ("EDIT_FIELD", vec![key.to_string(), percent_decode(field)]),
+    (&Method::GET, ["tickets", key, "transitions"]) => ("FETCH_TRANSITIONS", vec![key.to_string()]),
+    (&Method::POST, ["tickets", key, "transitions", transition]) => ("TRANSITION", vec![key.to_string(), percent_decode(transition)]),
+    (&Method::POST, ["projects", project_key, "tickets"]) => ("CREATE_ISSUE", vec![project_key.to_string()]),
+    (&Method::POST, ["tickets", key, "synchronise"]) => ("SYNCHRONISE_TICKET", vec![key.to_string()]),
+    (&Method::POST, ["synchronise", "updated"]) => ("SYNCHRONISE_UPDATED", Vec::new()),
+    (&Method::POST, ["synchronise", "all"]) => ("SYNCHRONISE_ALL", Vec::new()),
+    (&Method::POST, ["offline-mode", "on"]) => ("SET_OFFLINE_MODE", vec![String::from("ON")]),
+    (&Method::POST, ["offline-mode", "off"]) => ("SET_OFFLINE_MODE", vec![String::from("OFF")]),
+    _ => { return None }
+  };
+
+  Some(res)
+}
+
+// human field names may contain spaces, like Story%20Points
+fn percent_decode(text: &str) -> String {
+  let bytes = text.as_bytes();
+  let mut res = Vec::with_capacity(bytes.len());
+  let mut i = 0;
+  while i < bytes.len() {
+    let hex = bytes
+      .get(i + 1..i + 3)
+      .filter(|x| bytes[i] == b'%' && x.iter().all(u8::is_ascii_hexdigit))
+      .and_then(|x| std::str::from_utf8(x).ok())
+      .and_then(|x| u8::from_str_radix(x, 16).ok());
+    match hex {
+      Some(decoded) => {
+        res.push(decoded);
+        i += 3;
+      }
+      None => {
+        res.push(bytes[i]);
+        i += 1;
+      }
+    }
+  }
+  String::from_utf8_lossy(&res).into_owned()
+}
+
+// a web page can make the browser send requests to a loopback address (dns rebinding).
+// Only answer requests explicitly addressed to the local machine.
+fn is_loopback_host(host: &str) -> bool {
+  let host = match host.strip_prefix('[') {
+    Some(ipv6_and_port) => { ipv6_and_port.split(']').next().unwrap_or("") }
+    None => { host.split(':').next().unwrap_or("") }
+  };
+
+  host == "localhost" || host.parse::<IpAddr>().is_ok_and(|x| x.is_loopback())
+}
+
+// like http://127.0.0.1:8765. Sandboxed pages and local files send "null".
+fn is_loopback_origin(origin: &str) -> bool {
+  origin
+    .split_once("://")
+    .is_some_and(|(_scheme, host)| is_loopback_host(host))
+}
+
+// like application/json; charset=utf-8
+fn is_json_content_type(content_type: &str) -> bool {
+  content_type
+    .split(';')
+    .next()
+    .is_some_and(|x| x.trim().eq_ignore_ascii_case("application/json"))
+}
+
+// any web page can make the browser send requests to the api, and the browser then sends the
+// page's origin along. Requests from pages not served by the local machine are rejected.
+// Browsers only send requests modifying data with a json content type or a custom header
+// after asking the server whether it accepts them (preflight), which the api never answers.
+fn check_request_headers(method: &Method, headers: &HeaderMap) -> Result<(), String> {
+  let get_header = |name| {
+    headers
+      .get(name)
+      .map(|x: &HeaderValue| x.to_str().unwrap_or(""))
+  };
+
+  let host = get_header(HOST).unwrap_or("");
+  if !is_loopback_host(host) {
+    return Err(format!("Requests must be addressed to localhost. Got host [{host}]"));
+  }
+
+  if let Some(origin) = get_header(ORIGIN) {
+    if !is_loopback_origin(origin) {
+      return Err(format!("Requests must come from pages served by localhost. Got origin [{origin}]"));
+    }
+  }
+
+  let is_modifying_data = (method == Method::POST) || (method == Method::PUT);
+  let content_type = get_header(CONTENT_TYPE).unwrap_or("");
+  if is_modifying_data && !is_json_content_type(content_type) && !headers.contains_key(X_REQUESTED_WITH) {
+    return Err(format!("{method} requests must have a Content-Type of application/json or a X-Requested-With header. Got content type [{content_type}]"));
+  }
+
+  Ok(())
+}
+
+async fn get_body(body: Incoming) -> Result<Bytes, String> {
+  let body = Limited::new(body, MAX_BODY_LENGTH)
+    .collect()
+    .await
+    .map_err(|e| format!("Failed to read the body of the request. Err: {e}"))?
+    .to_bytes();
+  Ok(body)
+}
+
+async fn get_body_as_text(body: Incoming) -> Result<String, String> {
+  let body = get_body(body).await?;
+  String::from_utf8(body.to_vec())
+    .map_err(|e| format!("The body of the request must be utf-8 text. Err: {e}"))
+}
+
+// like {"resolution": "Done"}
+fn get_field_values(fields: &Map<String, Value>) -> Result<Vec<(String, String)>, String> {
+  fields
+    .iter()
+    .map(|(field, value)| {
+      let Some(value) = value.as_str() else {
+        return Err(format!("The value of field {field} must be a string. Got [{value}]"));
+      };
+      Ok((field.clone(), value.to_string()))
+    })
+    .collect()
+}
+
+// an empty body means no field
+async fn get_body_as_fields(body: Incoming) -> Result<Vec<(String, String)>, String> {
+  let body = get_body(body).await?;
+  if body.iter().all(|x| x.is_ascii_whitespace()) {
+    return Ok(Vec::new());
+  }
+
+  let fields = serde_json::from_slice::<Map<String, Value>>(&body)
+    .map_err(|e| format!("The body of the request must be a json object of field values. Err: {e}"))?;
+  get_field_values(&fields)
+}
+
+// like {"issue_type": "Bug", "summary": "...", "description": "...", "fields": {"priority": "High"}},
+// only the issue type and the summary are mandatory.
+async fn get_body_as_new_ticket(project_key: &str, body: Incoming) -> Result<CreateIssueParams, String> {
+  let body = get_body(body).await?;
+  let new_ticket = serde_json::from_slice::<Map<String, Value>>(&body)
+    .map_err(|e| format!("The body of the request must be a json object with the issue_type, the summary, and optionally the description and the fields of the ticket. Err: {e}"))?;
+
+  let get_text = |name: &str| {
+    match new_ticket.get(name) {
+      None | Some(Value::Null) => { Ok("") }
+      Some(Value::String(text)) => { Ok(text.as_str()) }
+      Some(value) => { Err(format!("The {name} of the ticket must be a string. Got [{value}]")) }
+    }
+  };
+  let issue_type = get_text("issue_type")?;
+  if issue_type.is_empty() {
+    return Err(format!("Invalid issue_type [{issue_type}]"));
+  }
+
+  let fields = match new_ticket.get("fields") {
+    None | Some(Value::Null) => { Vec::new() }
+    Some(Value::Object(fields)) => { get_field_values(fields)? }
+    Some(value) => { return Err(format!("The fields of the ticket must be a json object of field values. Got [{value}]")) }
+  };
+  CreateIssueParams::new(project_key, issue_type, get_text("summary")?, get_text("description")?, fields)
+}
+
+// text sent to jira comes in the body. The request is built directly from it, there is no
+// need to go through the base64 encoding of the text protocol.
+async fn get_request_kind_with_body(command: &str, params: &[String], body: Incoming) -> Result<Option<RequestKind>, String> {
+  // the url parameters are the key, or the key and the field or transition
+  let key = params.first().map(|x| x.as_str()).unwrap_or("");
+  let name = params.get(1).map(|x| x.as_str()).unwrap_or("");
+  let request_kind = match command {
+    "ADD_COMMENT" => {
+      RequestKind::Add_Comment(AddCommentParams::new(key, get_body_as_text(body).await?.as_str())?)
+    }
+    "EDIT_FIELD" => {
+      RequestKind::Edit_Field(EditFieldParams::new(key, name, get_body_as_text(body).await?.as_str())?)
+    }
+    "TRANSITION" => {
+      RequestKind::Transition(TransitionParams::new(key, name, get_body_as_fields(body).await?)?)
+    }
+    "CREATE_ISSUE" => {
+      RequestKind::Create_Issue(get_body_as_new_ticket(key, body).await?)
+    }
+    _ => { return Ok(None) }
+  };
+  Ok(Some(request_kind))
+}
+
+fn get_status_code(error_code: &ErrorCode) -> StatusCode {
+  match error_code {
+    ErrorCode::InvalidRequest
+    | ErrorCode::InvalidParameters => { StatusCode::BAD_REQUEST }
+    ErrorCode::LocalDatabase
+    | ErrorCode::EventsLost => { StatusCode::INTERNAL_SERVER_ERROR }
+    ErrorCode::RemoteServer => { StatusCode::BAD_GATEWAY }
+    ErrorCode::Offline => { StatusCode::SERVICE_UNAVAILABLE }
+  }
+}
+
+fn json_response(status: StatusCode, json: Value) -> Response<Full<Bytes>> {
+  let mut response = Response::new(Full::new(Bytes::from(format!("{json}\n"))));
+  *response.status_mut() = status;
+  response.headers_mut().insert(CONTENT_TYPE, "application/json".parse().unwrap());
+  response
+}
+
+fn error_response(status: StatusCode, error_code: &ErrorCode, message: &str) -> Response<Full<Bytes>> {
+  json_response(status, json!({"errors": [{"code": error_code.as_str(), "message": message}]}))
+}
+
+fn replies_to_response(replies: Vec<Reply>) -> Response<Full<Bytes>> {
+  // handlers first reply with local data, and then again with fresher data from the jira server
+  // if it differs. Http only gets one answer, the last one is the most up to date.
+  let mut result = None;
+  let mut errors = Vec::new();
+  let mut stale = None;
+  for reply in replies {
+    match reply.kind {
+      ReplyKind::Result(data) => { result = Some(data) }
+      ReplyKind::Error(code, message) => { errors.push((code, message)) }
+      ReplyKind::Stale(last_synchronisation) => { stale = Some(json!({"last_synchronisation": last_synchronisation})) }
+      _ => {}
+    }
+  }
+
+  let errors_json = errors
+    .iter()
+    .map(|(code, message)| json!({"code": code.as_str(), "message": message}))
+    .collect::<Vec<_>>();
+
+  match (result, errors.first()) {
+    (Some(ResultData::AttachmentContent { mime_type, content }), _) => {
+      let mime_type = mime_type.unwrap_or_else(|| String::from("application/octet-stream"));
+      let mut response = Response::new(Full::new(Bytes::from(content)));
+      match mime_type.parse() {
+        Ok(v) => { response.headers_mut().insert(CONTENT_TYPE, v); }
+        Err(e) => { eprintln!("Invalid mime type [{mime_type}] stored in the database for an attachment. Err: {e:?}") }
+      }
+      // anyone can attach a html or svg file to a ticket. Opened in the browser, its scripts
+      // would run with the origin of the api, and could read or change any ticket.
+      let headers = response.headers_mut();
+      headers.insert(CONTENT_DISPOSITION, HeaderValue::from_static("attachment"));
+      headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+      headers.insert(CONTENT_SECURITY_POLICY, HeaderValue::from_static("sandbox"));
+      response
+    }
+    (Some(data), _) => {
+      json_response(StatusCode::OK, json!({"result": result_data_to_json(&data), "errors": errors_json, "stale": stale}))
+    }
+    (None, Some((code, _))) => {
+      json_response(get_status_code(code), json!({"errors": errors_json}))
+    }
+    (None, None) => {
+      json_response(StatusCode::OK, json!({"result": null, "errors": errors_json}))
+    }
+  }
+}
+
+async fn get_replies(config: Config, request: Request, db_conn: Pool<Sqlite>) -> Vec<Reply> {
+  let (out_for_replies, mut replies_to_collect) = tokio::sync::mpsc::channel(32);
+
+  // not spawning the handler means it gets dropped, and therefore stops, if the http client
+  // goes away before the request is finished.
+  let handler = serve_request(config, request, out_for_replies, db_conn);
+  let collect_replies = async {
+    let mut replies = Vec::new();
+    while let Some(reply) = replies_to_collect.recv().await {
+      replies.push(reply);
+    }
+    replies
+  };
+
+  let (_, replies) = tokio::join!(handler, collect_replies);
+  replies
+}
+
+async fn serve_http_request(config: Config,
+                            http_request: hyper::Request<Incoming>,
+                            db_conn: Pool<Sqlite>) -> Result<Response<Full<Bytes>>, hyper::Error> {
+  static REQUEST_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+  if let Err(err_msg) = check_request_headers(http_request.method(), http_request.headers()) {
+    return Ok(error_response(StatusCode::FORBIDDEN, &ErrorCode::InvalidRequest, err_msg.as_str()));
+  }
+
+  let uri = http_request.uri();
+  let command = get_command(http_request.method(), uri.path(), uri.query());
+  let Some((command, params)) = command else {
+    let err_msg = format!("No such endpoint: {method} {path}", method = http_request.method(), path = uri.path());
+    return Ok(error_response(StatusCode::NOT_FOUND, &ErrorCode::InvalidRequest, err_msg.as_str()));
+  };
+
+  // parameters come from the url, percent decoded. They are joined with commas like in the text
+  // protocol, make sure they can't be mistaken for several parameters.
+  if let Some(param) = params.iter().find(|x| x.contains(',')) {
+    let err_msg = format!("Parameters can't contain commas. Got [{param}]");
+    return Ok(error_response(StatusCode::BAD_REQUEST, &ErrorCode::InvalidParameters, err_msg.as_str()));
+  }
+
+  let request_id = format!("http-{id}", id = REQUEST_COUNTER.fetch_add(1, Ordering::Relaxed));
+  let request_kind = get_request_kind_with_body(command, params.as_slice(), http_request.into_body()).await;
+  let request = match request_kind {
+    Ok(Some(request_kind)) => { Request::from_kind(request_id.as_str(), request_kind) }
+    Ok(None) if params.is_empty() => { Request::new(request_id.as_str(), command, None) }
+    Ok(None) => { Request::new(request_id.as_str(), command, Some(params.join(",").as_str())) }
+    Err(e) => { Err(e) }
+  };
+  let request = match request {
+    Ok(v) => { v }
+    Err(e) => {
+      return Ok(error_response(StatusCode::BAD_REQUEST, &ErrorCode::InvalidParameters, e.as_str()));
+    }
+  };
+
+  let replies = get_replies(config, request, db_conn).await;
+  Ok(replies_to_response(replies))
+}
+
+pub(crate) async fn serve_http_api(config: &Config, address: SocketAddr, db_conn: &Pool<Sqlite>) {
+  let listener = TcpListener::bind(address).await;
+  let listener = match listener {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("Error: failed to listen on {address}. Err: {e:?}");
+      return;
+    }
+  };
+
+  eprintln!("Ready to accept http requests on {address}");
+
+  let mut connections = JoinSet::new();
+  loop {
+    tokio::select! {
+      connection = listener.accept() => {
+        match connection {
+          Ok((stream, _addr)) => {
+            let config = config.clone();
+            let db_conn = db_conn.clone();
+            let service = service_fn(move |http_request| serve_http_request(config.clone(), http_request, db_conn.clone()));
+            connections.spawn(async move {
+              if let Err(e) = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await {
+                eprintln!("Error while serving http connection: {e:?}");
+              }
+            });
+          }
+          Err(e) => {
+            eprintln!("Failed to accept http connection: {e:?}");
+          }
+        }
+      },
+      _ = tokio::signal::ctrl_c() => {
+        eprintln!("Received interruption signal, shutting down");
+        break;
+      }
+    }
+
+    // remove handles of finished connections from set
+    while let Some(res) = connections.try_join_next() {
+      log_task_failure("http connection", res);
+    }
+  }
+
+  connections.abort_all();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|x| x.to_string()).collect()
+  }
+
+  #[test]
+  fn percent_decode_spaces_and_commas() {
+    assert_eq!(percent_decode("Story%20Points"), "Story Points");
+    assert_eq!(percent_decode("a%2Cb"), "a,b");
+    assert_eq!(percent_decode("%C3%A9t%C3%A9"), "été");
+  }
+
+  #[test]
+  fn percent_decode_keeps_invalid_sequences() {
+    assert_eq!(percent_decode("100%"), "100%");
+    assert_eq!(percent_decode("%zz"), "%zz");
+    assert_eq!(percent_decode("%2"), "%2");
+  }
+
+  #[test]
+  fn loopback_hosts() {
+    assert!(is_loopback_host("localhost"));
+    assert!(is_loopback_host("localhost:8765"));
+    assert!(is_loopback_host("127.0.0.1:8765"));
+    assert!(is_loopback_host("[::1]:8765"));
+    assert!(is_loopback_host("[::1]"));
+  }
+
+  #[test]
+  fn non_loopback_hosts() {
+    assert!(!is_loopback_host(""));
+    assert!(!is_loopback_host("example.com"));
+    assert!(!is_loopback_host("localhost.example.com:8765"));
+    assert!(!is_loopback_host("192.168.1.10:8765"));
+    assert!(!is_loopback_host("[2001:db8::1]:8765"));
+  }
+
+  fn get_headers(headers: &[(&'static str, &'static str)]) -> HeaderMap {
+    headers
+      .iter()
+      .map(|(name, value)| (name.parse().unwrap(), HeaderValue::from_static(value)))
+      .collect()
+  }
+
+  #[test]
+  fn loopback_origins() {
+    assert!(is_loopback_origin("http://localhost:8765"));
+    assert!(is_loopback_origin("http://127.0.0.1:8765"));
+    assert!(is_loopback_origin("https://[::1]:8765"));
+  }
+
+  #[test]
+  fn non_loopback_origins() {
+    assert!(!is_loopback_origin("null"));
+    assert!(!is_loopback_origin("localhost"));
+    assert!(!is_loopback_origin("https://example.com"));
+    assert!(!is_loopback_origin("http://localhost.example.com:8765"));
+  }
+
+  #[test]
+  fn requests_from_local_clients() {
+    assert_eq!(check_request_headers(&Method::GET, &get_headers(&[("host", "127.0.0.1:8765")])), Ok(()));
+    assert_eq!(check_request_headers(&Method::GET, &get_headers(&[("host", "localhost:8765"), ("origin", "http://localhost:3000")])), Ok(()));
+  }
+
+  #[test]
+  fn requests_from_other_hosts_are_rejected() {
+    assert!(check_request_headers(&Method::GET, &get_headers(&[])).is_err());
+    assert!(check_request_headers(&Method::GET, &get_headers(&[("host", "attacker.example.com")])).is_err());
+  }
+
+  #[test]
+  fn requests_from_other_origins_are_rejected() {
+    assert!(check_request_headers(&Method::GET, &get_headers(&[("host", "127.0.0.1:8765"), ("origin", "https://attacker.example.com")])).is_err());
+    assert!(check_request_headers(&Method::GET, &get_headers(&[("host", "127.0.0.1:8765"), ("origin", "null")])).is_err());
+    let comment_from_other_site = [("host", "127.0.0.1:8765"), ("origin", "https://attacker.example.com"), ("content-type", "application/json")];
+    assert!(check_request_headers(&Method::POST, &get_headers(&comment_from_other_site)).is_err());
+  }
+
+  #[test]
+  fn json_content_types() {
+    assert!(is_json_content_type("application/json"));
+    assert!(is_json_content_type("Application/JSON; charset=utf-8"));
+    assert!(!is_json_content_type(""));
+    assert!(!is_json_content_type("text/plain"));
+    assert!(!is_json_content_type("application/x-www-form-urlencoded"));
+  }
+
+  #[test]
+  fn writes_with_json_content_type_or_custom_header() {
+    let json_body = [("host", "127.0.0.1:8765"), ("content-type", "application/json")];
+    assert_eq!(check_request_headers(&Method::POST, &get_headers(&json_body)), Ok(()));
+    assert_eq!(check_request_headers(&Method::PUT, &get_headers(&json_body)), Ok(()));
+    let markdown_body = [("host", "127.0.0.1:8765"), ("content-type", "text/markdown"), ("x-requested-with", "local_jira_client")];
+    assert_eq!(check_request_headers(&Method::POST, &get_headers(&markdown_body)), Ok(()));
+  }
+
+  #[test]
+  fn writes_sendable_without_preflight_are_rejected() {
+    // the requests a web page can make the browser send to any site without asking first
+    for content_type in ["text/plain", "application/x-www-form-urlencoded", "multipart/form-data"] {
+      let headers = get_headers(&[("host", "127.0.0.1:8765"), ("content-type", content_type)]);
+      assert!(check_request_headers(&Method::POST, &headers).is_err());
+      assert!(check_request_headers(&Method::PUT, &headers).is_err());
+    }
+    assert!(check_request_headers(&Method::POST, &get_headers(&[("host", "127.0.0.1:8765")])).is_err());
+  }
+
+  #[test]
+  fn attachments_are_not_displayed_inline() {
+    let content = ResultData::AttachmentContent { mime_type: Some(String::from("image/svg+xml")), content: b"<svg/>".to_vec() };
+    let response = replies_to_response(vec![Reply::result("http-0", content)]);
+    let headers = response.headers();
+    assert_eq!(headers.get(CONTENT_TYPE).unwrap(), "image/svg+xml");
+    assert_eq!(headers.get(CONTENT_DISPOSITION).unwrap(), "attachment");
+    assert_eq!(headers.get(X_CONTENT_TYPE_OPTIONS).unwrap(), "nosniff");
+    assert_eq!(headers.get(CONTENT_SECURITY_POLICY).unwrap(), "sandbox");
+  }
+
+  #[test]
+  fn command_of_path() {
+    assert_eq!(get_command(&Method::GET, "/hello", None), Some(("HELLO", Vec::new())));
+    assert_eq!(get_command(&Method::GET, "/tickets/PROJ-1/fields/", None), Some(("FETCH_TICKET_KEY_VALUE_FIELDS", strings(&["PROJ-1"]))));
+    assert_eq!(get_command(&Method::POST, "/offline-mode/on", None), Some(("SET_OFFLINE_MODE", strings(&["ON"]))));
+  }
+
+  #[test]
+  fn command_with_query_parameters() {
+    assert_eq!(get_command(&Method::GET, "/tickets/PROJ-1", None), Some(("FETCH_TICKET", strings(&["PROJ-1", "HTML"]))));
+    assert_eq!(get_command(&Method::GET, "/tickets/PROJ-1", Some("format=MARKDOWN")), Some(("FETCH_TICKET", strings(&["PROJ-1", "MARKDOWN"]))));
+    assert_eq!(get_command(&Method::GET, "/logged-time", Some("to=2024-02-04&from=2024-01-29")), Some(("FETCH_LOGGED_TIME", strings(&["2024-01-29", "2024-02-04"]))));
+    assert_eq!(get_command(&Method::GET, "/logged-time", None), Some(("FETCH_LOGGED_TIME", strings(&["", ""]))));
+  }
+
+  #[test]
+  fn command_with_percent_encoded_parameters() {
+    assert_eq!(get_command(&Method::PUT, "/tickets/PROJ-1/fields/Story%20Points", None), Some(("EDIT_FIELD", strings(&["PROJ-1", "Story Points"]))));
+    assert_eq!(get_command(&Method::POST, "/tickets/PROJ-1/transitions/In%20Review", None), Some(("TRANSITION", strings(&["PROJ-1", "In Review"]))));
+    assert_eq!(get_command(&Method::GET, "/logged-time", Some("from=2024-01-29%2C2024-01-30")), Some(("FETCH_LOGGED_TIME", strings(&["2024-01-29,2024-01-30", ""]))));
+  }
+
+  #[test]
+  fn no_command_for_unknown_endpoints() {
+    assert_eq!(get_command(&Method::GET, "/unknown", None), None);
+    assert_eq!(get_command(&Method::DELETE, "/tickets/PROJ-1", None), None);
+    assert_eq!(get_command(&Method::GET, "/tickets/PROJ-1/comments", None), None);
+  }
+}
@@ -0,0 +1,253 @@
+use std::fmt::{Display, Formatter};
+use std::sync::OnceLock;
+use std::time::Duration;
+use reqwest::header::HeaderMap;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use tokio::sync::{Mutex, Semaphore};
+use tokio::time::Instant;
+use crate::get_config::Config;
+
+// All requests to the jira server go through a single client. Jira cloud throttles clients
+// sending too many requests (http 429), and synchronising a big project fires many requests at
+// once. The client bounds the number of requests in flight, spreads them over time, and retries
+// the ones rejected by the server after waiting as long as it asked to.
+
+const MAX_RETRIES: u32 = 5;
+const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(300); // don't let a misbehaving server stall us for hours
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_BODY_LENGTH_IN_ERRORS: usize = 500;
+
+#[derive(Debug)]
+pub(crate) enum HttpError {
+  Network(String),
+  Timeout,
+  RateLimited { retry_after: Option<Duration> }, // still throttled after all retries
+  Status { status: StatusCode, body: String },
+  InvalidJson(String),
+  UnexpectedJson(String), // valid json, but not what was asked for
+}
+
+impl Display for HttpError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      HttpError::Network(e) => { write!(f, "Error: failed to reach the jira server. Msg={e}") }
+      HttpError::Timeout => { write!(f, "Error: the jira server didn't answer in time") }
+      HttpError::RateLimited { retry_after: Some(retry_after) } => {
+        write!(f, "Error: the jira server is rate limiting requests. It asks to retry in {secs}s", secs = retry_after.as_secs())
+      }
+      HttpError::RateLimited { retry_after: None } => { write!(f, "Error: the jira server is rate limiting requests") }
+      HttpError::Status { status, body } => {
+        let body = body.chars().take(MAX_BODY_LENGTH_IN_ERRORS).collect::<String>();
+        write!(f, "Error: the jira server answered with status {status}. Body=[{body}]")
+      }
+      HttpError::InvalidJson(e) => { write!(f, "Error: Failed to parse response as json. Text is [{e}]") }
+      HttpError::UnexpectedJson(e) => { write!(f, "Error: unexpected reply from the jira server. {e}") }
+    }
+  }
+}
+
+// most of the code base reports errors as strings
+impl From<HttpError> for String {
+  fn from(value: HttpError) -> Self {
+    value.to_string()
+  }
+}
+
+impl From<reqwest::Error> for HttpError {
+  fn from(value: reqwest::Error) -> Self {
+    if value.is_timeout() {
+      HttpError::Timeout
+    } else {
+      HttpError::Network(value.to_string())
+    }
+  }
+}
+
+struct JiraHttpClient {
+  client: reqwest::Client,
+  requests_in_flight: Semaphore,
+  interval_between_requests: Duration,
+  next_request_at: Mutex<Instant>,
+}
+
+impl JiraHttpClient {
+  fn new(config: &Config) -> Self {
+    let client = reqwest::Client::builder()
+      .connect_timeout(CONNECT_TIMEOUT)
+      .timeout(config.request_timeout())
+      .build()
+      .unwrap_or_else(|e| {
+        eprintln!("Failed to configure the http client, using the default one. Err: {e}");
+        reqwest::Client::new()
+      });
+    let interval_between_requests = Duration::try_from_secs_f64(1.0 / config.max_requests_per_second())
+      .unwrap_or(MAX_BACKOFF)
+      .min(MAX_BACKOFF);
+
+    Self {
+      client,
+      requests_in_flight: Semaphore::new(config.max_concurrent_requests()),
+      interval_between_requests,
+      next_request_at: Mutex::new(Instant::now()),
+    }
+  }
+
+  async fn wait_for_request_slot(&self) {
+    let slot = {
+      let mut next_request_at = self.next_request_at.lock().await;
+      let slot = (*next_request_at).max(Instant::now());
+      *next_request_at = slot + self.interval_between_requests;
+      slot
+    };
+    tokio::time::sleep_until(slot).await;
+  }
+
+  // when the server throttles us, the other requests have to wait as well
+  async fn pause_requests_until(&self, instant: Instant) {
+    let mut next_request_at = self.next_request_at.lock().await;
+    if *next_request_at < instant {
+      *next_request_at = instant;
+    }
+  }
+}
+
+// the configuration is the same for the whole lifetime of the server, the first caller sets it up.
+fn get_jira_http_client(config: &Config) -> &'static JiraHttpClient {
+  static JIRA_HTTP_CLIENT: OnceLock<JiraHttpClient> = OnceLock::new();
+  JIRA_HTTP_CLIENT.get_or_init(|| JiraHttpClient::new(config))
+}
+
+// a request modifying data may have been applied even though a gateway answered with an error.
+// Only the ones the server refused to handle are worth sending again.
+fn is_worth_retrying(status: StatusCode, is_idempotent: bool) -> bool {
+  match status {
+    StatusCode::TOO_MANY_REQUESTS => { true }
+    StatusCode::BAD_GATEWAY
+    | StatusCode::SERVICE_UNAVAILABLE
+    | StatusCode::GATEWAY_TIMEOUT => { is_idempotent }
+    _ => { false }
+  }
+}
+
+// Only the delay in seconds is supported. Jira doesn't send http dates.
+fn get_retry_after(headers: &HeaderMap) -> Option<Duration> {
+  headers
+    .get(reqwest::header::RETRY_AFTER)
+    .and_then(|x| x.to_str().ok())
+    .and_then(|x| x.trim().parse::<u64>().ok())
+    .map(|x| Duration::from_secs(x).min(MAX_RETRY_AFTER))
+}
+
+async fn get_error_from_response(response: Response, retry_after: Option<Duration>) -> HttpError {
+  let status = response.status();
+  if status == StatusCode::TOO_MANY_REQUESTS {
+    return HttpError::RateLimited { retry_after };
+  }
+  let body = response.text().await.unwrap_or_default();
+  HttpError::Status { status, body }
+}
+
+// Returns the response when its status is a success. build_request is called again for each retry.
+pub(crate) async fn send_request_to_jira<F>(config: &Config, build_request: F) -> Result<Response, HttpError>
+  where F: Fn(&reqwest::Client) -> RequestBuilder {
+  send_request_to_jira_with_retries(config, build_request, true).await
+}
+
+// Same as send_request_to_jira, for requests which must not be applied twice, like adding a comment.
+pub(crate) async fn send_non_idempotent_request_to_jira<F>(config: &Config, build_request: F) -> Result<Response, HttpError>
+  where F: Fn(&reqwest::Client) -> RequestBuilder {
+  send_request_to_jira_with_retries(config, build_request, false).await
+}
+
+async fn send_request_to_jira_with_retries<F>(config: &Config, build_request: F, is_idempotent: bool) -> Result<Response, HttpError>
+  where F: Fn(&reqwest::Client) -> RequestBuilder {
+  let jira = get_jira_http_client(config);
+  let mut backoff = FIRST_BACKOFF;
+  let mut nr_retries = 0;
+
+  loop {
+    let request = build_request(&jira.client).build()?;
+    let url = request.url().clone();
+
+    let response = {
+      // the semaphore is never closed
+      let _permit = jira.requests_in_flight.acquire().await;
+      jira.wait_for_request_slot().await;
+      jira.client.execute(request).await?
+    };
+
+    let status = response.status();
+    if status.is_success() {
+      return Ok(response);
+    }
+
+    let retry_after = get_retry_after(response.headers());
+    if !is_worth_retrying(status, is_idempotent) || nr_retries >= MAX_RETRIES {
+      return Err(get_error_from_response(response, retry_after).await);
+    }
+
+    let delay = retry_after.unwrap_or(backoff);
+    eprintln!("Jira server answered {status} to {url}. Retrying in {secs}s", secs = delay.as_secs_f32());
+    jira.pause_requests_until(Instant::now() + delay).await;
+    tokio::time::sleep(delay).await;
+
+    backoff = (backoff * 2).min(MAX_BACKOFF);
+    nr_retries += 1;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use reqwest::header::{HeaderValue, RETRY_AFTER};
+
+  fn get_headers_with_retry_after(retry_after: &'static str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(RETRY_AFTER, HeaderValue::from_static(retry_after));
+    headers
+  }
+
+  #[test]
+  fn throttled_requests_are_retried() {
+    assert!(is_worth_retrying(StatusCode::TOO_MANY_REQUESTS, true));
+    assert!(is_worth_retrying(StatusCode::TOO_MANY_REQUESTS, false));
+  }
+
+  #[test]
+  fn gateway_errors_are_retried_for_idempotent_requests_only() {
+    for status in [StatusCode::BAD_GATEWAY, StatusCode::SERVICE_UNAVAILABLE, StatusCode::GATEWAY_TIMEOUT] {
+      assert!(is_worth_retrying(status, true));
+      assert!(!is_worth_retrying(status, false));
+    }
+  }
+
+  #[test]
+  fn other_errors_are_not_retried() {
+    for status in [StatusCode::BAD_REQUEST, StatusCode::UNAUTHORIZED, StatusCode::NOT_FOUND, StatusCode::INTERNAL_SERVER_ERROR] {
+      assert!(!is_worth_retrying(status, true));
+      assert!(!is_worth_retrying(status, false));
+    }
+  }
+
+  #[test]
+  fn retry_after_in_seconds() {
+    assert_eq!(get_retry_after(&get_headers_with_retry_after("30")), Some(Duration::from_secs(30)));
+    assert_eq!(get_retry_after(&get_headers_with_retry_after(" 0 ")), Some(Duration::from_secs(0)));
+  }
+
+  #[test]
+  fn missing_or_invalid_retry_after() {
+    assert_eq!(get_retry_after(&HeaderMap::new()), None);
+    assert_eq!(get_retry_after(&get_headers_with_retry_after("soon")), None);
+    assert_eq!(get_retry_after(&get_headers_with_retry_after("-5")), None);
+    assert_eq!(get_retry_after(&get_headers_with_retry_after("Wed, 21 Oct 2015 07:28:00 GMT")), None);
+  }
+
+  #[test]
+  fn retry_after_is_capped() {
+    assert_eq!(get_retry_after(&get_headers_with_retry_after("300")), Some(MAX_RETRY_AFTER));
+    assert_eq!(get_retry_after(&get_headers_with_retry_after("86400")), Some(MAX_RETRY_AFTER));
+  }
+}
@@ -0,0 +1,428 @@
+use base64::Engine;
+use serde_json::{json, Map, Value};
+use crate::capabilities::{get_parameter_names, is_optional_parameter};
+use crate::server::{KeyValueField, Reply, ReplyKind, Request, RequestKind, ResultData};
+use crate::srv_add_comment::AddCommentParams;
+use crate::srv_create_issue::CreateIssueParams;
+use crate::srv_edit_field::EditFieldParams;
+use crate::srv_transition::TransitionParams;
+
+fn check_for_unknown_parameters(command: &str, params: &Map<String, Value>) -> Result<(), String> {
+  let parameter_names = get_parameter_names(command);
+  let unknown_params = params
+    .keys()
+    .filter(|x| !parameter_names.contains(&x.as_str()))
+    .map(|x| x.as_str())
+    .collect::<Vec<_>>();
+  if !unknown_params.is_empty() {
+    return Err(format!("Invalid request. {command} doesn't take the following parameters: [{x}]", x = unknown_params.join(", ")));
+  }
+  Ok(())
+}
+
+fn get_string_parameter<'a>(command: &str, params: &'a Map<String, Value>, name: &str) -> Result<&'a str, String> {
+  match params.get(name) {
+    Some(Value::String(value)) => { Ok(value.as_str()) }
+    _ => { Err(format!("Invalid request. {command} needs a parameter named \"{name}\" of type string")) }
+  }
+}
+
+fn get_fields_parameter(command: &str, params: &Map<String, Value>) -> Result<Vec<(String, String)>, String> {
+  // like {"resolution": "Done", "customfield_10010": "42"}
+  let fields = match params.get("fields") {
+    None | Some(Value::Null) => { return Ok(Vec::new()) }
+    Some(Value::Object(fields)) => { fields }
+    Some(_) => { return Err(format!("Invalid request. Parameter \"fields\" of {command} must be a json object")) }
+  };
+  fields
+    .iter()
+    .map(|(field, value)| match value {
+      Value::String(value) => { Ok((field.clone(), value.clone())) }
+      _ => { Err(format!("Invalid request. Field \"{field}\" of {command} must have a value of type string")) }
+    })
+    .collect()
+}
+
+// commands carrying free text get their parameters as is, instead of going through the
+// comma separated (and base64 encoded) format of the text protocol
+fn get_request_kind_with_text(command: &str, params: &Map<String, Value>) -> Result<Option<RequestKind>, String> {
+  let request_kind = match command {
+    "ADD_COMMENT" => {
+      RequestKind::Add_Comment(AddCommentParams::new(
+        get_string_parameter(command, params, "key")?,
+        get_string_parameter(command, params, "markdown")?)?)
+    }
+    "EDIT_FIELD" => {
+      RequestKind::Edit_Field(EditFieldParams::new(
+        get_string_parameter(command, params, "key")?,
+        get_string_parameter(command, params, "field")?,
+        get_string_parameter(command, params, "value")?)?)
+    }
+    "TRANSITION" => {
+      RequestKind::Transition(TransitionParams::new(
+        get_string_parameter(command, params, "key")?,
+        get_string_parameter(command, params, "transition")?,
+        get_fields_parameter(command, params)?)?)
+    }
+    "CREATE_ISSUE" => {
+      RequestKind::Create_Issue(CreateIssueParams::new(
+        get_string_parameter(command, params, "project")?,
+        get_string_parameter(command, params, "issue_type")?,
+        get_string_parameter(command, params, "summary")?,
+        get_string_parameter(command, params, "description")?,
+        get_fields_parameter(command, params)?)?)
+    }
+    _ => { return Ok(None) }
+  };
+  Ok(Some(request_kind))
+}
+
+// other commands only take keys, ids, dates... which are joined with commas like in the
+// text protocol. Such values therefore can't contain commas.
+fn get_text_parameters(command: &str, params: &Map<String, Value>) -> Result<Option<String>, String> {
+  let parameter_names = get_parameter_names(command);
+  if parameter_names.is_empty() {
+    return Ok(None);
+  }
+
+  let mut values = Vec::new();
+  for name in parameter_names {
+    // a parameter is either a string, or a list of strings for commands like
+    // FETCH_TICKETS_KEY_VALUE_FIELDS which take a comma separated list in the text protocol.
+    let value = match params.get(*name) {
+      None if is_optional_parameter(command, name) => { continue }
+      Some(Value::Array(list)) if list.is_empty() && is_optional_parameter(command, name) => { continue }
+      Some(Value::String(value)) => { vec![value.as_str()] }
+      Some(Value::Array(list)) if !list.is_empty() && list.iter().all(|x| x.is_string()) => {
+        list.iter().filter_map(|x| x.as_str()).collect::<Vec<_>>()
+      }
+      _ => {
+        return Err(format!("Invalid request. {command} needs a parameter named \"{name}\" of type string or list of strings"));
+      }
+    };
+    if let Some(value) = value.iter().find(|x| x.contains(',')) {
+      return Err(format!("Invalid request. Parameter \"{name}\" can't contain commas. Got [{value}]"));
+    }
+    values.extend(value);
+  }
+
+  Ok(Some(values.join(",")))
+}
+
+pub(crate) fn request_from_json_line(line: &str) -> Result<Request, String> {
+  // a request looks like:
+  // {"id": "req-1", "command": "FETCH_TICKET", "params": {"key": "PROJ-123", "format": "HTML"}}
+  let json = serde_json::from_str::<Value>(line);
+  let json = match json {
+    Ok(v) => { v }
+    Err(e) => { return Err(format!("Invalid request. Not valid json. Err: {e}")) }
+  };
+
+  let Some(json) = json.as_object() else {
+    return Err(String::from("Invalid request. Request must be a json object"));
+  };
+
+  let Some(request_id) = json.get("id").and_then(|x| x.as_str()) else {
+    return Err(String::from("Invalid request. Request must contain an \"id\" of type string"));
+  };
+
+  let Some(command) = json.get("command").and_then(|x| x.as_str()) else {
+    return Err(String::from("Invalid request. Request must contain a \"command\" of type string"));
+  };
+
+  let no_params = Map::new();
+  let params = match json.get("params") {
+    None | Some(Value::Null) => { &no_params }
+    Some(Value::Object(params)) => { params }
+    Some(_) => { return Err(String::from("Invalid request. \"params\" must be a json object")) }
+  };
+
+  check_for_unknown_parameters(command, params)?;
+  if let Some(request_kind) = get_request_kind_with_text(command, params)? {
+    return Request::from_kind(request_id, request_kind);
+  }
+  let params = get_text_parameters(command, params)?;
+  Request::new(request_id, command, params.as_deref())
+}
+
+fn key_value_fields_to_json(fields: &[KeyValueField]) -> Value {
+  let fields = fields
+    .iter()
+    .map(|x| {
+      // values are stored as json in the database. Give them back as such instead of
+      // as a string so clients don't have to parse them a second time.
+      let value = serde_json::from_str::<Value>(x.value.as_str())
+        .unwrap_or_else(|_| Value::String(x.value.clone()));
+      json!({"key": x.key, "name": x.name, "value": value})
+    })
+    .collect::<Vec<_>>();
+  Value::Array(fields)
+}
+
+pub(crate) fn result_data_to_json(data: &ResultData) -> Value {
+  match data {
+    ResultData::Ticket(ticket) => { json!(ticket) }
+    ResultData::TicketList(keys) => { json!(keys) }
+    ResultData::DeletedTickets(keys) => { json!({"deleted": keys}) }
+    ResultData::TicketHistory(history) => {
+      let history = history
+        .iter()
+        .map(|x| json!({"author": x.author, "created": x.created, "field": x.field, "from": x.from, "to": x.to}))
+        .collect::<Vec<_>>();
+      Value::Array(history)
+    }
+    ResultData::KeyValueFields(fields) => { key_value_fields_to_json(fields) }
+    ResultData::TicketsKeyValueFields(tickets) => {
+      let tickets = tickets
+        .iter()
+        .map(|(key, fields)| json!({"key": key, "fields": key_value_fields_to_json(fields)}))
+        .collect::<Vec<_>>();
+      Value::Array(tickets)
+    }
+    ResultData::AttachmentList(attachments) => {
+      let attachments = attachments
+        .iter()
+        .map(|x| json!({"uuid": x.uuid, "filename": x.filename}))
+        .collect::<Vec<_>>();
+      Value::Array(attachments)
+    }
+    ResultData::AttachmentContent { content, .. } => {
+      json!(base64::engine::general_purpose::STANDARD.encode(content.as_slice()))
+    }
+    ResultData::LoggedTime(logged_time) => {
+      let logged_time = logged_time
+        .iter()
+        .map(|x| json!({"author": x.author, "key": x.key, "day": x.day, "seconds": x.seconds}))
+        .collect::<Vec<_>>();
+      Value::Array(logged_time)
+    }
+    ResultData::BoardList(boards) => {
+      let boards = boards
+        .iter()
+        .map(|x| json!({"id": x.id, "name": x.name, "type": x.board_type, "project": x.project_key}))
+        .collect::<Vec<_>>();
+      Value::Array(boards)
+    }
+    ResultData::SprintList(sprints) => {
+      let sprints = sprints
+        .iter()
+        .map(|x| json!({
+          "id": x.id,
+          "name": x.name,
+          "state": x.state,
+          "start_date": x.start_date,
+          "end_date": x.end_date,
+          "complete_date": x.complete_date,
+          "goal": x.goal,
+        }))
+        .collect::<Vec<_>>();
+      Value::Array(sprints)
+    }
+    ResultData::VersionList(versions) => {
+      let versions = versions
+        .iter()
+        .map(|x| json!({
+          "id": x.id,
+          "name": x.name,
+          "description": x.description,
+          "released": x.is_released,
+          "archived": x.is_archived,
+          "release_date": x.release_date,
+        }))
+        .collect::<Vec<_>>();
+      Value::Array(versions)
+    }
+    ResultData::ComponentList(components) => {
+      let components = components
+        .iter()
+        .map(|x| json!({"id": x.id, "name": x.name, "description": x.description, "lead": x.lead}))
+        .collect::<Vec<_>>();
+      Value::Array(components)
+    }
+    ResultData::TicketStatusList(tickets) => {
+      let tickets = tickets
+        .iter()
+        .map(|x| json!({
+          "key": x.key,
+          "status": x.status,
+          "status_category": x.status_category,
+          "priority": x.priority,
+          "resolution": x.resolution,
+        }))
+        .collect::<Vec<_>>();
+      Value::Array(tickets)
+    }
+    ResultData::PersonList(people) => {
+      let people = people
+        .iter()
+        .map(|x| json!({"account_id": x.account_id, "display_name": x.display_name}))
+        .collect::<Vec<_>>();
+      Value::Array(people)
+    }
+    ResultData::CommentId(id) => { json!({"id": id}) }
+    ResultData::CreatedTicketKey(key) => { json!({"key": key}) }
+    ResultData::TransitionList(transitions) => {
+      let transitions = transitions
+        .iter()
+        .map(|x| json!({
+          "id": x.id,
+          "name": x.name,
+          "to_status": x.to_status,
+          "required_fields": x.required_fields,
+        }))
+        .collect::<Vec<_>>();
+      Value::Array(transitions)
+    }
+    ResultData::TicketTree(nodes) => {
+      let nodes = nodes
+        .iter()
+        .map(|x| json!({
+          "key": x.key,
+          "parent": x.parent,
+          "depth": x.depth,
+          "summary": x.summary,
+          "status": x.status,
+        }))
+        .collect::<Vec<_>>();
+      Value::Array(nodes)
+    }
+    ResultData::Hello { server_name, server_version, protocol_version } => {
+      json!({"server_name": server_name, "server_version": server_version, "protocol_version": protocol_version})
+    }
+    ResultData::Capabilities(capabilities) => {
+      let commands = capabilities.commands
+        .iter()
+        .map(|(name, parameters)| json!({"name": name, "parameters": parameters}))
+        .collect::<Vec<_>>();
+      json!({
+        "protocol_version": capabilities.protocol_version,
+        "commands": commands,
+        "ticket_formats": capabilities.ticket_formats,
+        "features": capabilities.features,
+      })
+    }
+  }
+}
+
+pub(crate) fn reply_to_json_line(reply: &Reply) -> String {
+  let request_id = reply.request_id.as_str();
+  let json = match &reply.kind {
+    ReplyKind::Ack => {
+      json!({"id": request_id, "status": "ACK"})
+    }
+    ReplyKind::Result(data) => {
+      json!({"id": request_id, "status": "RESULT", "result": result_data_to_json(data)})
+    }
+    ReplyKind::Error(code, message) => {
+      json!({"id": request_id, "status": "ERROR", "error": {"code": code.as_str(), "message": message}})
+    }
+    ReplyKind::Finished => {
+      json!({"id": request_id, "status": "FINISHED"})
+    }
+    ReplyKind::Cancelled => {
+      json!({"id": request_id, "status": "CANCELLED"})
+    }
+    ReplyKind::Progress(progress) => {
+      let progress = json!({
+        "project": progress.project_key,
+        "pages_fetched": progress.pages_fetched,
+        "pages_total": progress.pages_total,
+        "tickets_detailed": progress.tickets_detailed,
+        "tickets_total": progress.tickets_total,
+      });
+      json!({"id": request_id, "status": "PROGRESS", "progress": progress})
+    }
+    ReplyKind::Event(changes) => {
+      let updated = changes.updated
+        .iter()
+        .map(|x| json!({"key": x.key, "fields": x.fields}))
+        .collect::<Vec<_>>();
+      let event = json!({
+        "created": changes.created,
+        "updated": updated,
+        "deleted": changes.deleted,
+      });
+      json!({"id": request_id, "status": "EVENT", "event": event})
+    }
+    ReplyKind::Stale(last_synchronisation) => {
+      json!({"id": request_id, "status": "STALE", "stale": {"last_synchronisation": last_synchronisation}})
+    }
+  };
+
+  format!("{json}\n")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn params(json: Value) -> Map<String, Value> {
+    json.as_object().unwrap().clone()
+  }
+
+  #[test]
+  fn text_parameters_follow_the_order_of_the_command() {
+    let res = get_text_parameters("FETCH_TICKET", &params(json!({"format": "HTML", "key": "PROJ-1"})));
+    assert_eq!(res, Ok(Some(String::from("PROJ-1,HTML"))));
+  }
+
+  #[test]
+  fn text_parameters_join_lists() {
+    let res = get_text_parameters("FETCH_TICKETS_KEY_VALUE_FIELDS", &params(json!({"keys": ["PROJ-1", "PROJ-2"]})));
+    assert_eq!(res, Ok(Some(String::from("PROJ-1,PROJ-2"))));
+  }
+
+  #[test]
+  fn text_parameters_of_command_without_parameters() {
+    assert_eq!(get_text_parameters("FETCH_TICKET_LIST", &Map::new()), Ok(None));
+  }
+
+  #[test]
+  fn text_parameters_reject_commas() {
+    assert!(get_text_parameters("FETCH_TICKET", &params(json!({"key": "PROJ-1,PROJ-2", "format": "HTML"}))).is_err());
+    assert!(get_text_parameters("FETCH_TICKETS_KEY_VALUE_FIELDS", &params(json!({"keys": ["PROJ-1,PROJ-2"]}))).is_err());
+  }
+
+  #[test]
+  fn text_parameters_reject_missing_or_mistyped_parameters() {
+    assert!(get_text_parameters("FETCH_TICKET", &params(json!({"key": "PROJ-1"}))).is_err());
+    assert!(get_text_parameters("FETCH_TICKET", &params(json!({"key": 1, "format": "HTML"}))).is_err());
+    assert!(get_text_parameters("FETCH_TICKETS_KEY_VALUE_FIELDS", &params(json!({"keys": []}))).is_err());
+  }
+
+  #[test]
+  fn request_from_json_line_with_text_parameters() {
+    let request = request_from_json_line(r#"{"id": "req-1", "command": "FETCH_TICKET", "params": {"key": "PROJ-1", "format": "HTML"}}"#).unwrap();
+    assert_eq!(request.request_id, "req-1");
+    assert!(request.request_kind == RequestKind::Fetch_Ticket(String::from("PROJ-1,HTML")));
+  }
+
+  #[test]
+  fn request_from_json_line_without_parameters() {
+    let request = request_from_json_line(r#"{"id": "req-2", "command": "FETCH_TICKET_LIST"}"#).unwrap();
+    assert!(request.request_kind == RequestKind::Fetch_Ticket_List);
+  }
+
+  #[test]
+  fn request_from_json_line_with_free_text() {
+    let request = request_from_json_line(r#"{"id": "req-3", "command": "ADD_COMMENT", "params": {"key": "PROJ-1", "markdown": "Fixed, *really*"}}"#).unwrap();
+    let expected = AddCommentParams::new("PROJ-1", "Fixed, *really*").unwrap();
+    assert!(request.request_kind == RequestKind::Add_Comment(expected));
+
+    let request = request_from_json_line(r#"{"id": "req-4", "command": "TRANSITION", "params": {"key": "PROJ-1", "transition": "Done", "fields": {"resolution": "Won't do, sorry"}}}"#).unwrap();
+    let expected = TransitionParams::new("PROJ-1", "Done", vec![(String::from("resolution"), String::from("Won't do, sorry"))]).unwrap();
+    assert!(request.request_kind == RequestKind::Transition(expected));
+  }
+
+  #[test]
+  fn request_from_json_line_rejects_invalid_requests() {
+    assert!(request_from_json_line("not json").is_err());
+    assert!(request_from_json_line(r#"["FETCH_TICKET_LIST"]"#).is_err());
+    assert!(request_from_json_line(r#"{"command": "FETCH_TICKET_LIST"}"#).is_err());
+    assert!(request_from_json_line(r#"{"id": "req 1", "command": "FETCH_TICKET_LIST"}"#).is_err());
+    assert!(request_from_json_line(r#"{"id": "req-1", "command": "FETCH_TICKET_LIST", "params": []}"#).is_err());
+    assert!(request_from_json_line(r#"{"id": "req-1", "command": "FETCH_TICKET_LIST", "params": {"key": "PROJ-1"}}"#).is_err());
+    assert!(request_from_json_line(r#"{"id": "req-1", "command": "ADD_COMMENT", "params": {"key": "PROJ-1", "markdown": 1}}"#).is_err());
+    assert!(request_from_json_line(r#"{"id": "req-1", "command": "TRANSITION", "params": {"key": "PROJ-1", "transition": "Done", "fields": {"resolution": 1}}}"#).is_err());
+  }
+}
@@ -26,32 +26,168 @@ use crate::manage_project_table::update_project_list_in_db;
 // https://docs.atlassian.com/software/jira/docs/api/REST/9.14.0/#api/2/project-getAllProjects
 
 mod atlassian_document_format;
+mod capabilities;
+mod create_issue;
 mod defaults;
+mod edit_issue_field;
 mod find_issues_that_need_updating;
 mod get_attachment_content;
 mod get_config;
 mod get_issue_details;
 mod get_json_from_url;
 mod get_project_tasks_from_server;
+mod http_api;
+mod jira_http_client;
+mod json_protocol;
+mod manage_boards_and_sprints;
+mod manage_create_metadata;
+mod manage_deleted_issues;
 mod manage_field_table;
 mod manage_interesting_projects;
 mod manage_issue_comments;
 mod manage_issue_field;
+mod manage_issue_hierarchy;
+mod manage_issue_history;
+mod manage_issue_transitions;
+mod manage_issue_watchers;
+mod manage_issue_worklogs;
 mod manage_issuelinktype_table;
 mod manage_issuetype_table;
 mod manage_project_table;
+mod manage_status_priority_resolution_tables;
+mod manage_synchronisation_table;
+mod manage_versions_and_components;
+mod offline_mode;
 mod server;
+mod sync_progress;
+mod ticket_events;
 mod utils;
 mod srv_fetch_ticket;
 mod srv_fetch_ticket_list;
+mod srv_fetch_ticket_history;
+mod srv_fetch_logged_time;
+mod srv_fetch_board_list;
+mod srv_fetch_board_sprints;
+mod srv_fetch_sprint_tickets;
+mod srv_fetch_project_versions;
+mod srv_fetch_version_tickets;
+mod srv_fetch_project_components;
+mod srv_fetch_component_tickets;
+mod srv_fetch_ticket_status_list;
+mod srv_fetch_watched_tickets;
+mod srv_fetch_ticket_watchers;
+mod srv_fetch_ticket_voters;
+mod srv_fetch_ticket_tree;
+mod srv_add_comment;
+mod srv_edit_field;
+mod srv_fetch_transitions;
+mod srv_transition;
+mod srv_create_issue;
 mod srv_fetch_ticket_key_value_list;
+mod srv_fetch_tickets_key_value_fields;
 mod srv_fetch_attachment_list_for_ticket;
 mod srv_fetch_attachment_content;
 mod srv_synchronise_ticket;
 mod srv_synchronise_updated;
 mod srv_synchronise_all;
+mod srv_subscribe;
+mod srv_offline_mode;
+mod srv_capabilities;
 mod atlassian_document_format_html_output;
 mod atlassian_document_utils;
+mod markdown_to_atlassian_document_format;
+
+// columns added to tables after they were first released. CREATE TABLE IF NOT EXISTS in the
+// schema doesn't add them to the tables of databases created by an older version.
+const ADDED_COLUMNS: &[(&str /* table */, &str /* column */, &str /* type */)] = &[
+    ("Issue", "status_id", "INTEGER"),
+    ("Issue", "priority_id", "INTEGER"),
+    ("Issue", "resolution_id", "INTEGER"),
+];
+
+#[derive(FromRow)]
+struct ColumnName {
+    name: String,
+}
+
+async fn add_missing_columns(db: &Pool<Sqlite>) -> Result<(), String> {
+    for (table, column, column_type) in ADDED_COLUMNS {
+        let columns = sqlx::query_as::<_, ColumnName>("SELECT name FROM pragma_table_info(?);")
+          .bind(table)
+          .fetch_all(db)
+          .await
+          .map_err(|e| format!("Failed to list the columns of table {table}. Err: {e}"))?;
+
+        // tables which don't exist yet are created with all their columns
+        if columns.is_empty() || columns.iter().any(|x| x.name == *column) {
+            continue;
+        }
+
+        eprintln!("Adding column {column} to table {table}");
+        sqlx::query(format!("ALTER TABLE {table} ADD COLUMN {column} {column_type};").as_str())
+          .execute(db)
+          .await
+          .map_err(|e| format!("Failed to add column {column} to table {table}. Err: {e}"))?;
+    }
+    Ok(())
+}
+
+#[derive(FromRow)]
+struct TableSql {
+    sql: String,
+}
+
+// display names used to be unique, which dropped the accounts sharing the display name of another
+// one, like the deleted accounts which are all "Former user". The table is rebuilt without the
+// constraint, following https://www.sqlite.org/lang_altertable.html#otheralter: foreign keys
+// referencing People must not be checked while the old table is dropped.
+async fn remove_unique_display_names(db: &Pool<Sqlite>) -> Result<(), String> {
+    let table = sqlx::query_as::<_, TableSql>("SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'People';")
+      .fetch_optional(db)
+      .await
+      .map_err(|e| format!("Failed to get the definition of table People. Err: {e}"))?;
+    let Some(table) = table else {
+        return Ok(());
+    };
+    if !table.sql.contains("displayName TEXT UNIQUE") {
+        return Ok(());
+    }
+
+    eprintln!("Removing the unique constraint on the display names of table People");
+    let mut conn = db
+      .acquire()
+      .await
+      .map_err(|e| format!("Failed to get a connection to rebuild table People. Err: {e}"))?;
+    sqlx::query("PRAGMA foreign_keys = OFF;")
+      .execute(&mut *conn)
+      .await
+      .map_err(|e| format!("Failed to disable foreign keys to rebuild table People. Err: {e}"))?;
+
+    let query_str =
+      "BEGIN;
+       CREATE TABLE People_new (
+          accountId TEXT UNIQUE PRIMARY KEY NOT NULL,
+          displayName TEXT NOT NULL
+       ) STRICT;
+       INSERT INTO People_new (accountId, displayName) SELECT accountId, displayName FROM People;
+       DROP TABLE People;
+       ALTER TABLE People_new RENAME TO People;
+       COMMIT;";
+    let res = sqlx::query(query_str)
+      .execute(&mut *conn)
+      .await
+      .map_err(|e| format!("Failed to rebuild table People. Err: {e}"));
+    if res.is_err() {
+        let _ = sqlx::query("ROLLBACK;").execute(&mut *conn).await;
+    }
+
+    // the connection goes back to the pool, foreign keys must be enforced again
+    sqlx::query("PRAGMA foreign_keys = ON;")
+      .execute(&mut *conn)
+      .await
+      .map_err(|e| format!("Failed to enable foreign keys after rebuilding table People. Err: {e}"))?;
+    res.map(|_| ())
+}
 
 async fn init_db(db_path: &std::path::PathBuf) -> Result<Pool<Sqlite>, String> {
     let path = db_path.to_str();
@@ -72,6 +208,8 @@ async fn init_db(db_path: &std::path::PathBuf) -> Result<Pool<Sqlite>, String> {
     }
 
     let db = SqlitePool::connect(path).await.unwrap();
+    add_missing_columns(&db).await?;
+    remove_unique_display_names(&db).await?;
     let create_schema = include_str!("create_schema.sql");
     let result = sqlx::query(create_schema)
       .execute(&db)
@@ -136,4 +274,7 @@ pub async fn main() {
     };
 
     server::server_request_loop(&config, &db).await;
+
+    // checkpoints the write-ahead log so that no other process finds it still in use.
+    db.close().await;
 }
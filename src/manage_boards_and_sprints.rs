@@ -0,0 +1,611 @@
+use std::collections::{HashMap, HashSet};
+use reqwest::StatusCode;
+use serde_json::Value;
+use sqlx::{FromRow, Pool, Sqlite};
+use crate::get_config::Config;
+use crate::get_json_from_url::get_all_pages_from_server;
+use crate::jira_http_client::HttpError;
+use crate::server::{Board, Sprint};
+
+// Boards and sprints come from the jira agile api. The boards are the ones of the interesting
+// projects, the sprints the ones of their scrum boards and of the simple boards of team-managed
+// projects. Kanban boards don't have sprints.
+
+#[derive(Debug, FromRow, Hash, Eq, PartialEq)]
+struct BoardRow {
+  id: i64,
+  name: String,
+  board_type: String,
+  project_key: String,
+}
+
+#[derive(Debug, FromRow, Hash, Eq, PartialEq)]
+struct SprintRow {
+  id: i64,
+  name: String,
+  state: String,
+  start_date: Option<String>,
+  end_date: Option<String>,
+  complete_date: Option<String>,
+  goal: Option<String>,
+}
+
+fn get_board_row_from_json(board: &Value, project_key: &str) -> Result<BoardRow, String> {
+  let id = board
+    .get("id")
+    .and_then(|x| x.as_i64());
+  let name = board
+    .get("name")
+    .and_then(|x| x.as_str());
+  let board_type = board
+    .get("type")
+    .and_then(|x| x.as_str());
+  let (Some(id), Some(name), Some(board_type)) = (id, name, board_type) else {
+    return Err(format!("Boards of project {project_key} contain an invalid board: {board}"));
+  };
+
+  Ok(BoardRow {
+    id,
+    name: name.to_string(),
+    board_type: board_type.to_string(),
+    project_key: project_key.to_string(),
+  })
+}
+
+fn get_sprint_row_from_json(sprint: &Value, board_id: i64) -> Result<SprintRow, String> {
+  let id = sprint
+    .get("id")
+    .and_then(|x| x.as_i64());
+  let name = sprint
+    .get("name")
+    .and_then(|x| x.as_str());
+  let state = sprint
+    .get("state")
+    .and_then(|x| x.as_str());
+  let (Some(id), Some(name), Some(state)) = (id, name, state) else {
+    return Err(format!("Sprints of board {board_id} contain an invalid sprint: {sprint}"));
+  };
+
+  let get_optional_string = |name: &str| {
+    sprint
+      .get(name)
+      .and_then(|x| x.as_str())
+      .map(|x| x.to_string())
+  };
+
+  Ok(SprintRow {
+    id,
+    name: name.to_string(),
+    state: state.to_string(),
+    start_date: get_optional_string("startDate"),
+    end_date: get_optional_string("endDate"),
+    complete_date: get_optional_string("completeDate"),
+    goal: get_optional_string("goal"),
+  })
+}
+
+async fn get_board_rows_from_server(config: &Config) -> Result<Vec<BoardRow>, String> {
+  // a board can show the tickets of several projects. It is kept once, with the first
+  // interesting project it was found in.
+  let mut res = Vec::<BoardRow>::new();
+  for project_key in config.interesting_projects() {
+    let query = format!("/rest/agile/1.0/board?projectKeyOrId={project_key}");
+    let boards = get_all_pages_from_server(config, query.as_str(), "values")
+      .await
+      .map_err(|e| format!("Error: failed to get the boards of project {project_key} from server.\n{e}"))?;
+    for board in &boards {
+      let board = get_board_row_from_json(board, project_key)?;
+      if res.iter().all(|x| x.id != board.id) {
+        res.push(board);
+      }
+    }
+  }
+  Ok(res)
+}
+
+fn may_have_sprints(board_type: &str) -> bool {
+  board_type == "scrum" || board_type == "simple"
+}
+
+// the boards of team-managed projects only have sprints when the sprints feature is enabled.
+// Jira refuses to list the sprints of the other ones, with a message in the user's language.
+fn is_board_without_sprints(error: &HttpError) -> bool {
+  matches!(error, HttpError::Status { status, .. } if *status == StatusCode::BAD_REQUEST)
+}
+
+async fn get_sprint_rows_from_server(config: &Config, board_id: i64) -> Result<Vec<SprintRow>, String> {
+  let query = format!("/rest/agile/1.0/board/{board_id}/sprint");
+  let sprints = match get_all_pages_from_server(config, query.as_str(), "values").await {
+    Ok(v) => { v }
+    Err(e) if is_board_without_sprints(&e) => { Vec::new() }
+    Err(e) => { return Err(format!("Error: failed to get the sprints of board {board_id} from server.\n{e}")) }
+  };
+  sprints
+    .iter()
+    .map(|x| get_sprint_row_from_json(x, board_id))
+    .collect()
+}
+
+async fn get_issue_ids_of_sprint_from_server(config: &Config, sprint_id: i64) -> Result<Vec<i64>, String> {
+  let query = format!("/rest/agile/1.0/sprint/{sprint_id}/issue?fields=key");
+  get_all_pages_from_server(config, query.as_str(), "issues")
+    .await
+    .map_err(|e| format!("Error: failed to get the issues of sprint {sprint_id} from server.\n{e}"))?
+    .iter()
+    .map(|issue| {
+      issue
+        .get("id")
+        .and_then(|x| x.as_str())
+        .and_then(|x| x.parse::<i64>().ok())
+        .ok_or_else(|| format!("Issues of sprint {sprint_id} contain an issue without id: {issue}"))
+    })
+    .collect()
+}
+
+async fn get_board_rows_from_db(db_conn: &Pool<Sqlite>) -> Result<Vec<BoardRow>, String> {
+  let query_str =
+    "SELECT id, name, type AS board_type, project_key
+     FROM Board
+     ORDER BY id ASC;";
+
+  sqlx::query_as::<_, BoardRow>(query_str)
+    .fetch_all(db_conn)
+    .await
+    .map_err(|e| format!("Error occurred while trying to get the boards from local database: {e}"))
+}
+
+async fn get_sprint_rows_from_db(board_id: Option<i64>, db_conn: &Pool<Sqlite>) -> Result<Vec<SprintRow>, String> {
+  // future sprints don't have a start date yet, they come last
+  let query_str =
+    "SELECT id, name, state, start_date, end_date, complete_date, goal
+     FROM Sprint
+     WHERE ?1 IS NULL OR id IN (SELECT sprint_id FROM BoardSprint WHERE board_id = ?1)
+     ORDER BY start_date IS NULL ASC, start_date ASC, id ASC;";
+
+  sqlx::query_as::<_, SprintRow>(query_str)
+    .bind(board_id)
+    .fetch_all(db_conn)
+    .await
+    .map_err(|e| format!("Error occurred while trying to get the sprints from local database: {e}"))
+}
+
+#[derive(FromRow)]
+struct IdInDb {
+  id: i64,
+}
+
+async fn get_ids_from_db(query_str: &str, id: i64, db_conn: &Pool<Sqlite>) -> Result<HashSet<i64>, String> {
+  sqlx::query_as::<_, IdInDb>(query_str)
+    .bind(id)
+    .fetch_all(db_conn)
+    .await
+    .map(|x| x.into_iter().map(|x| x.id).collect())
+    .map_err(|e| format!("Error occurred while trying to run [{query_str}] on the local database with id {id}: {e}"))
+}
+
+async fn update_boards_in_db(boards_in_remote: &[BoardRow], boards_in_db: &[BoardRow], db_conn: &Pool<Sqlite>) -> bool {
+  let boards_in_db_set = boards_in_db.iter().collect::<HashSet<_>>();
+  let ids_in_remote = boards_in_remote.iter().map(|x| x.id).collect::<HashSet<_>>();
+
+  let ids_to_remove = boards_in_db
+    .iter()
+    .filter(|x| !ids_in_remote.contains(&x.id))
+    .map(|x| x.id)
+    .collect::<Vec<_>>();
+  let boards_to_insert = boards_in_remote
+    .iter()
+    .filter(|x| !boards_in_db_set.contains(x))
+    .collect::<Vec<_>>();
+
+  if ids_to_remove.is_empty() && boards_to_insert.is_empty() {
+    eprintln!("Boards are up to date");
+    return true;
+  }
+
+  let mut tx = match db_conn.begin().await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("Error when starting a sql transaction to update the boards. Err: {e:?}");
+      return false;
+    }
+  };
+
+  // the sprints of removed boards are removed along with the ones deleted from jira,
+  // in remove_sprints_without_board_in_db
+  let queries = [
+    "DELETE FROM BoardSprint WHERE board_id = ?;",
+    "DELETE FROM Board WHERE id = ?;",
+  ];
+  for id in &ids_to_remove {
+    for query_str in queries {
+      let res = sqlx::query(query_str)
+        .bind(id)
+        .execute(&mut *tx)
+        .await;
+      if let Err(e) = res {
+        eprintln!("Error when removing board {id}. Err: {e:?}");
+        return false; // dropping the transaction rolls it back
+      }
+    }
+  }
+
+  let query_str =
+    "INSERT INTO Board (id, name, type, project_key)
+     VALUES (?, ?, ?, ?)
+     ON CONFLICT DO
+     UPDATE SET name = excluded.name,
+                type = excluded.type,
+                project_key = excluded.project_key;";
+  for board in &boards_to_insert {
+    let res = sqlx::query(query_str)
+      .bind(board.id)
+      .bind(&board.name)
+      .bind(&board.board_type)
+      .bind(&board.project_key)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when adding board {id}. Err: {e:?}", id = board.id);
+      return false;
+    }
+  }
+
+  match tx.commit().await {
+    Ok(_) => {
+      eprintln!("Updated boards: {removed} removed, {inserted} added or edited",
+                removed = ids_to_remove.len(), inserted = boards_to_insert.len());
+      true
+    }
+    Err(e) => {
+      eprintln!("Error when committing the boards. Err: {e:?}");
+      false
+    }
+  }
+}
+
+async fn update_sprints_of_board_in_db(board_id: i64,
+                                       sprints_in_remote: &[SprintRow],
+                                       sprints_in_db: &HashMap<i64, SprintRow>,
+                                       db_conn: &Pool<Sqlite>) -> bool {
+  let sprint_ids_in_db = match get_ids_from_db("SELECT sprint_id AS id FROM BoardSprint WHERE board_id = ?;", board_id, db_conn).await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("{e}");
+      return false;
+    }
+  };
+  let sprint_ids_in_remote = sprints_in_remote.iter().map(|x| x.id).collect::<HashSet<_>>();
+
+  let ids_to_remove = sprint_ids_in_db
+    .difference(&sprint_ids_in_remote)
+    .collect::<Vec<_>>();
+  let ids_to_add = sprint_ids_in_remote
+    .difference(&sprint_ids_in_db)
+    .collect::<Vec<_>>();
+  let sprints_to_insert = sprints_in_remote
+    .iter()
+    .filter(|x| sprints_in_db.get(&x.id) != Some(x))
+    .collect::<Vec<_>>();
+
+  if ids_to_remove.is_empty() && ids_to_add.is_empty() && sprints_to_insert.is_empty() {
+    eprintln!("Sprints of board {board_id} are up to date");
+    return true;
+  }
+
+  let mut tx = match db_conn.begin().await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("Error when starting a sql transaction to update the sprints of board {board_id}. Err: {e:?}");
+      return false;
+    }
+  };
+
+  let query_str =
+    "INSERT INTO Sprint (id, name, state, start_date, end_date, complete_date, goal)
+     VALUES (?, ?, ?, ?, ?, ?, ?)
+     ON CONFLICT DO
+     UPDATE SET name = excluded.name,
+                state = excluded.state,
+                start_date = excluded.start_date,
+                end_date = excluded.end_date,
+                complete_date = excluded.complete_date,
+                goal = excluded.goal;";
+  for sprint in &sprints_to_insert {
+    let res = sqlx::query(query_str)
+      .bind(sprint.id)
+      .bind(&sprint.name)
+      .bind(&sprint.state)
+      .bind(&sprint.start_date)
+      .bind(&sprint.end_date)
+      .bind(&sprint.complete_date)
+      .bind(&sprint.goal)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when adding sprint {id} of board {board_id}. Err: {e:?}", id = sprint.id);
+      return false; // dropping the transaction rolls it back
+    }
+  }
+
+  let query_str = "DELETE FROM BoardSprint WHERE board_id = ? AND sprint_id = ?;";
+  for id in &ids_to_remove {
+    let res = sqlx::query(query_str)
+      .bind(board_id)
+      .bind(id)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when removing sprint {id} from board {board_id}. Err: {e:?}");
+      return false;
+    }
+  }
+
+  let query_str = "INSERT INTO BoardSprint (board_id, sprint_id) VALUES (?, ?);";
+  for id in &ids_to_add {
+    let res = sqlx::query(query_str)
+      .bind(board_id)
+      .bind(id)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when adding sprint {id} to board {board_id}. Err: {e:?}");
+      return false;
+    }
+  }
+
+  match tx.commit().await {
+    Ok(_) => {
+      eprintln!("Updated sprints of board {board_id}: {removed} removed from the board, {added} added to it, {inserted} added or edited",
+                removed = ids_to_remove.len(), added = ids_to_add.len(), inserted = sprints_to_insert.len());
+      true
+    }
+    Err(e) => {
+      eprintln!("Error when committing the sprints of board {board_id}. Err: {e:?}");
+      false
+    }
+  }
+}
+
+async fn update_issues_of_sprint_in_db(sprint_id: i64, issue_ids_in_remote: &[i64], db_conn: &Pool<Sqlite>) -> bool {
+  let issue_ids_in_db = match get_ids_from_db("SELECT issue_id AS id FROM SprintIssue WHERE sprint_id = ?;", sprint_id, db_conn).await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("{e}");
+      return false;
+    }
+  };
+  let issue_ids_in_remote = issue_ids_in_remote.iter().copied().collect::<HashSet<_>>();
+
+  let ids_to_remove = issue_ids_in_db
+    .difference(&issue_ids_in_remote)
+    .collect::<Vec<_>>();
+  let ids_to_add = issue_ids_in_remote
+    .difference(&issue_ids_in_db)
+    .collect::<Vec<_>>();
+
+  if ids_to_remove.is_empty() && ids_to_add.is_empty() {
+    eprintln!("Tickets of sprint {sprint_id} are up to date");
+    return true;
+  }
+
+  let mut tx = match db_conn.begin().await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("Error when starting a sql transaction to update the tickets of sprint {sprint_id}. Err: {e:?}");
+      return false;
+    }
+  };
+
+  let query_str = "DELETE FROM SprintIssue WHERE sprint_id = ? AND issue_id = ?;";
+  for id in &ids_to_remove {
+    let res = sqlx::query(query_str)
+      .bind(sprint_id)
+      .bind(id)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when removing issue {id} from sprint {sprint_id}. Err: {e:?}");
+      return false; // dropping the transaction rolls it back
+    }
+  }
+
+  let query_str = "INSERT INTO SprintIssue (sprint_id, issue_id) VALUES (?, ?);";
+  for id in &ids_to_add {
+    let res = sqlx::query(query_str)
+      .bind(sprint_id)
+      .bind(id)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when adding issue {id} to sprint {sprint_id}. Err: {e:?}");
+      return false;
+    }
+  }
+
+  match tx.commit().await {
+    Ok(_) => {
+      eprintln!("Updated tickets of sprint {sprint_id}: {removed} removed, {added} added",
+                removed = ids_to_remove.len(), added = ids_to_add.len());
+      true
+    }
+    Err(e) => {
+      eprintln!("Error when committing the tickets of sprint {sprint_id}. Err: {e:?}");
+      false
+    }
+  }
+}
+
+// sprints deleted from jira, or whose boards are gone
+async fn remove_sprints_without_board_in_db(db_conn: &Pool<Sqlite>) -> bool {
+  let mut tx = match db_conn.begin().await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("Error when starting a sql transaction to remove the sprints without board. Err: {e:?}");
+      return false;
+    }
+  };
+
+  let queries = [
+    "DELETE FROM SprintIssue WHERE sprint_id NOT IN (SELECT sprint_id FROM BoardSprint);",
+    "DELETE FROM Sprint WHERE id NOT IN (SELECT sprint_id FROM BoardSprint);",
+  ];
+  for query_str in queries {
+    let res = sqlx::query(query_str)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when removing the sprints without board. Err: {e:?}");
+      return false;
+    }
+  }
+
+  match tx.commit().await {
+    Ok(_) => { true }
+    Err(e) => {
+      eprintln!("Error when committing the removal of the sprints without board. Err: {e:?}");
+      false
+    }
+  }
+}
+
+pub(crate) async fn update_boards_and_sprints_in_db(config: &Config, db_conn: &Pool<Sqlite>) {
+  let boards_in_remote = match get_board_rows_from_server(config).await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("{e}");
+      return;
+    }
+  };
+
+  let boards_in_db = match get_board_rows_from_db(db_conn).await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("{e}");
+      return;
+    }
+  };
+
+  if !update_boards_in_db(boards_in_remote.as_slice(), boards_in_db.as_slice(), db_conn).await {
+    return;
+  }
+
+  let sprints_in_db = match get_sprint_rows_from_db(None, db_conn).await {
+    Ok(v) => { v.into_iter().map(|x| (x.id, x)).collect::<HashMap<_, _>>() }
+    Err(e) => {
+      eprintln!("{e}");
+      return;
+    }
+  };
+
+  let mut sprints_needing_issues = HashSet::new();
+  let mut all_boards_updated = true;
+  for board in boards_in_remote.iter().filter(|x| may_have_sprints(x.board_type.as_str())) {
+    let sprints_in_remote = match get_sprint_rows_from_server(config, board.id).await {
+      Ok(v) => { v }
+      Err(e) => {
+        eprintln!("{e}");
+        all_boards_updated = false;
+        continue;
+      }
+    };
+
+    if !update_sprints_of_board_in_db(board.id, sprints_in_remote.as_slice(), &sprints_in_db, db_conn).await {
+      all_boards_updated = false;
+      continue;
+    }
+
+    // the tickets of a closed sprint don't change anymore. They are fetched one last time
+    // when the sprint gets closed.
+    sprints_needing_issues.extend(
+      sprints_in_remote
+        .iter()
+        .filter(|x| x.state != "closed" || sprints_in_db.get(&x.id).is_none_or(|old| old.state != "closed"))
+        .map(|x| x.id));
+  }
+
+  // a board failing to answer doesn't mean its sprints are gone
+  if all_boards_updated {
+    remove_sprints_without_board_in_db(db_conn).await;
+  }
+
+  for sprint_id in sprints_needing_issues {
+    match get_issue_ids_of_sprint_from_server(config, sprint_id).await {
+      Ok(issue_ids) => {
+        update_issues_of_sprint_in_db(sprint_id, issue_ids.as_slice(), db_conn).await;
+      }
+      Err(e) => {
+        eprintln!("{e}");
+      }
+    }
+  }
+}
+
+pub(crate) async fn get_boards_from_db(db_conn: &Pool<Sqlite>) -> Result<Vec<Board>, String> {
+  let rows = get_board_rows_from_db(db_conn).await?;
+  let res = rows
+    .into_iter()
+    .map(|x| Board { id: x.id, name: x.name, board_type: x.board_type, project_key: x.project_key })
+    .collect();
+  Ok(res)
+}
+
+pub(crate) async fn get_sprints_of_board_from_db(board_id: i64, db_conn: &Pool<Sqlite>) -> Result<Vec<Sprint>, String> {
+  let rows = get_sprint_rows_from_db(Some(board_id), db_conn).await?;
+  let res = rows
+    .into_iter()
+    .map(|x| Sprint {
+      id: x.id,
+      name: x.name,
+      state: x.state,
+      start_date: x.start_date,
+      end_date: x.end_date,
+      complete_date: x.complete_date,
+      goal: x.goal,
+    })
+    .collect();
+  Ok(res)
+}
+
+#[derive(FromRow)]
+struct KeyInDb {
+  key: String,
+}
+
+// only the tickets synchronised locally are listed
+pub(crate) async fn get_tickets_of_sprint_from_db(sprint_id: i64, db_conn: &Pool<Sqlite>) -> Result<Vec<String>, String> {
+  let query_str =
+    "SELECT Issue.key
+     FROM SprintIssue
+     JOIN Issue ON Issue.jira_id = SprintIssue.issue_id
+     WHERE SprintIssue.sprint_id = ?
+     ORDER BY Issue.jira_id ASC;";
+
+  sqlx::query_as::<_, KeyInDb>(query_str)
+    .bind(sprint_id)
+    .fetch_all(db_conn)
+    .await
+    .map(|x| x.into_iter().map(|x| x.key).collect())
+    .map_err(|e| format!("Error occurred while trying to get the tickets of sprint {sprint_id} from local database: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn boards_with_sprints() {
+    assert!(may_have_sprints("scrum"));
+    assert!(may_have_sprints("simple"));
+    assert!(!may_have_sprints("kanban"));
+  }
+
+  #[test]
+  fn boards_refusing_to_list_sprints() {
+    let refusal = HttpError::Status { status: StatusCode::BAD_REQUEST, body: String::from(r#"{"errorMessages":["The board does not support sprints"]}"#) };
+    assert!(is_board_without_sprints(&refusal));
+
+    let not_found = HttpError::Status { status: StatusCode::NOT_FOUND, body: String::new() };
+    assert!(!is_board_without_sprints(&not_found));
+    assert!(!is_board_without_sprints(&HttpError::Timeout));
+  }
+}
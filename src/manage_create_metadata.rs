@@ -0,0 +1,163 @@
+use std::collections::HashSet;
+use serde_json::Value;
+use sqlx::{FromRow, Pool, Sqlite};
+use crate::get_config::Config;
+use crate::get_json_from_url::get_all_pages_from_server;
+
+// Create metadata: the fields of the create screen of each issue type of the interesting projects,
+// and which of them must be filled. Creating a ticket is checked against it before contacting the
+// jira server.
+
+#[derive(Debug, FromRow, Hash, Eq, PartialEq)]
+pub(crate) struct CreateField {
+  pub(crate) field_id: String,  // like customfield_10016
+  pub(crate) is_required: bool, // required and without default value
+}
+
+#[derive(FromRow)]
+struct ProjectIssueType {
+  project_id: i64,
+  issue_type_id: i64,
+  issue_type_name: String,
+}
+
+// the issue types of the project, as listed when synchronising the projects
+async fn get_issue_types_of_project_from_db(project_key: &str, db_conn: &Pool<Sqlite>) -> Result<Vec<ProjectIssueType>, String> {
+  let query_str =
+    "SELECT Project.jira_id AS project_id, IssueType.jira_id AS issue_type_id, IssueType.name AS issue_type_name
+     FROM IssueTypePerProject
+     JOIN Project ON Project.jira_id = IssueTypePerProject.project_id
+     JOIN IssueType ON IssueType.jira_id = IssueTypePerProject.issue_type_id
+     WHERE Project.key = ?
+     ORDER BY IssueType.jira_id ASC;";
+
+  sqlx::query_as::<_, ProjectIssueType>(query_str)
+    .bind(project_key)
+    .fetch_all(db_conn)
+    .await
+    .map_err(|e| format!("Error occurred while trying to get the issue types of project {project_key} from local database: {e}"))
+}
+
+fn get_create_field_from_json(field: &Value) -> Option<CreateField> {
+  let field_id = field.get("fieldId").and_then(|x| x.as_str())?;
+  let is_required = field.get("required").and_then(|x| x.as_bool()).unwrap_or(false);
+  let has_default_value = field.get("hasDefaultValue").and_then(|x| x.as_bool()).unwrap_or(false);
+  Some(CreateField { field_id: field_id.to_string(), is_required: is_required && !has_default_value })
+}
+
+async fn get_create_fields_from_server(config: &Config, project_key: &str, issue_type_id: i64) -> Result<Vec<CreateField>, String> {
+  let query = format!("/rest/api/3/issue/createmeta/{project_key}/issuetypes/{issue_type_id}");
+  let fields = get_all_pages_from_server(config, query.as_str(), "fields")
+    .await
+    .map_err(|e| format!("Error: failed to get {query} from server.\n{e}"))?;
+  Ok(fields.iter().filter_map(get_create_field_from_json).collect())
+}
+
+pub(crate) async fn get_create_fields_from_db(project_id: i64, issue_type_id: i64, db_conn: &Pool<Sqlite>) -> Result<Vec<CreateField>, String> {
+  let query_str =
+    "SELECT field_id, is_required
+     FROM CreateMetadata
+     WHERE project_id = ? AND issue_type_id = ?
+     ORDER BY field_id ASC;";
+
+  sqlx::query_as::<_, CreateField>(query_str)
+    .bind(project_id)
+    .bind(issue_type_id)
+    .fetch_all(db_conn)
+    .await
+    .map_err(|e| format!("Error occurred while trying to get the create metadata of issue type {issue_type_id} of project {project_id} from local database: {e}"))
+}
+
+async fn update_create_fields_in_db(project_key: &str,
+                                    issue_type: &ProjectIssueType,
+                                    fields_in_remote: &[CreateField],
+                                    fields_in_db: &[CreateField],
+                                    db_conn: &Pool<Sqlite>) {
+  let fields_in_remote = fields_in_remote.iter().collect::<HashSet<_>>();
+  let fields_in_db = fields_in_db.iter().collect::<HashSet<_>>();
+  if fields_in_remote == fields_in_db {
+    eprintln!("The create metadata of issue type {name} of project {project_key} is up to date", name = issue_type.issue_type_name);
+    return;
+  }
+
+  let mut tx = match db_conn.begin().await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("Error when starting a sql transaction to update the create metadata of project {project_key}. Err: {e:?}");
+      return;
+    }
+  };
+
+  let res = sqlx::query("DELETE FROM CreateMetadata WHERE project_id = ? AND issue_type_id = ?;")
+    .bind(issue_type.project_id)
+    .bind(issue_type.issue_type_id)
+    .execute(&mut *tx)
+    .await;
+  if let Err(e) = res {
+    eprintln!("Error when removing the create metadata of issue type {name} of project {project_key}. Err: {e:?}", name = issue_type.issue_type_name);
+    return; // dropping the transaction rolls it back
+  }
+
+  let query_str = "INSERT INTO CreateMetadata (project_id, issue_type_id, field_id, is_required) VALUES (?, ?, ?, ?);";
+  for field in &fields_in_remote {
+    let res = sqlx::query(query_str)
+      .bind(issue_type.project_id)
+      .bind(issue_type.issue_type_id)
+      .bind(&field.field_id)
+      .bind(field.is_required)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when adding field {field_id} to the create metadata of project {project_key}. Err: {e:?}", field_id = field.field_id);
+      return;
+    }
+  }
+
+  match tx.commit().await {
+    Ok(_) => {
+      eprintln!("Updated the create metadata of issue type {name} of project {project_key}: {nr_fields} fields",
+                name = issue_type.issue_type_name, nr_fields = fields_in_remote.len());
+    }
+    Err(e) => {
+      eprintln!("Error when committing the create metadata of project {project_key}. Err: {e:?}");
+    }
+  }
+}
+
+async fn update_create_metadata_of_project_in_db(config: &Config, project_key: &str, db_conn: &Pool<Sqlite>) {
+  let issue_types = match get_issue_types_of_project_from_db(project_key, db_conn).await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("{e}");
+      return;
+    }
+  };
+
+  // issue types removed from the project
+  let query_str =
+    "DELETE FROM CreateMetadata
+     WHERE project_id IN (SELECT jira_id FROM Project WHERE key = ?)
+       AND issue_type_id NOT IN (SELECT issue_type_id FROM IssueTypePerProject WHERE project_id = CreateMetadata.project_id);";
+  if let Err(e) = sqlx::query(query_str).bind(project_key).execute(db_conn).await {
+    eprintln!("Error when removing the create metadata of former issue types of project {project_key}. Err: {e:?}");
+  }
+
+  for issue_type in &issue_types {
+    let fields_in_remote = get_create_fields_from_server(config, project_key, issue_type.issue_type_id).await;
+    let fields_in_db = get_create_fields_from_db(issue_type.project_id, issue_type.issue_type_id, db_conn).await;
+    match (fields_in_remote, fields_in_db) {
+      (Ok(fields_in_remote), Ok(fields_in_db)) => {
+        update_create_fields_in_db(project_key, issue_type, fields_in_remote.as_slice(), fields_in_db.as_slice(), db_conn).await;
+      }
+      (Err(e), _) | (_, Err(e)) => {
+        eprintln!("{e}");
+      }
+    }
+  }
+}
+
+pub(crate) async fn update_create_metadata_in_db(config: &Config, db_conn: &Pool<Sqlite>) {
+  for project_key in config.interesting_projects() {
+    update_create_metadata_of_project_in_db(config, project_key, db_conn).await;
+  }
+}
@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use serde_json::Value;
+use sqlx::{Pool, Sqlite};
+use crate::get_config::Config;
+use crate::get_json_from_url::get_json_from_url;
+use crate::manage_interesting_projects::Issue;
+use crate::sync_progress::get_number_of_pages;
+use crate::ticket_events::{publish_ticket_changes, TicketChanges};
+
+// Synchronisation only ever adds or updates tickets. Tickets deleted on the jira server, or moved
+// to another project (which changes their key), would otherwise stay in the local database forever.
+// Reconciliation compares the issue ids on the server with the ones in the Issue table.
+
+async fn get_one_page_of_issue_ids(config: &Config, project_key: &str, start: i64, max_result_per_query: i64) -> Result<Value, String> {
+  // id and key are always returned. Asking for the key field only keeps the reply small.
+  let query = format!("/rest/api/3/search?jql=project%3D%22{project_key}%22+ORDER+BY+created+ASC&fields=key&startAt={start}&maxResults={max_result_per_query}");
+  get_json_from_url(config, query.as_str()).await.map_err(String::from)
+}
+
+fn get_issue_ids_from_json(json: &Value, project_key: &str) -> Result<Vec<Issue>, String> {
+  let Some(issues) = json.get("issues").and_then(|x| x.as_array()) else {
+    return Err(format!("Search result for project {project_key} doesn't contain a list of issues"));
+  };
+
+  // a single unreadable issue must fail the whole reconciliation, otherwise it would be deleted
+  // from the local database.
+  issues
+    .iter()
+    .map(|x| {
+      let jira_id = x
+        .get("id")
+        .and_then(|x| x.as_str())
+        .and_then(|x| x.parse::<u32>().ok());
+      let key = x
+        .get("key")
+        .and_then(|x| x.as_str());
+      match (jira_id, key) {
+        (Some(jira_id), Some(key)) => {
+          Ok(Issue { jira_id, key: key.to_string(), project_key: project_key.to_string() })
+        }
+        _ => { Err(format!("Search result for project {project_key} contains an issue without id or key: {x}")) }
+      }
+    })
+    .collect()
+}
+
+async fn get_issue_ids_of_project_from_server(config: &Config, project_key: &str) -> Result<Vec<Issue>, String> {
+  let max_result_per_query = -1; // -1 is a special value telling jira "no limit"
+                                 // the returned json will tell us what is the configured limit
+  let first_json = get_one_page_of_issue_ids(config, project_key, 0, max_result_per_query).await?;
+
+  let max_result_per_query = first_json
+    .get("maxResults")
+    .and_then(|x| x.as_i64())
+    .unwrap_or(100);
+  let total = first_json
+    .get("total")
+    .and_then(|x| x.as_i64());
+
+  let mut res = get_issue_ids_from_json(&first_json, project_key)?;
+
+  let nr_pages = get_number_of_pages(total, max_result_per_query) as i64;
+  for i in 1..nr_pages {
+    let next_json = get_one_page_of_issue_ids(config, project_key, max_result_per_query * i, max_result_per_query).await?;
+    res.extend(get_issue_ids_from_json(&next_json, project_key)?);
+  }
+
+  // tickets deleted while paging shift the following pages, making us miss some tickets.
+  if let Some(total) = total {
+    if (res.len() as i64) < total {
+      return Err(format!("Got {nr} issue ids for project {project_key} while the jira server announced {total}", nr = res.len()));
+    }
+  }
+
+  Ok(res)
+}
+
+async fn get_issues_from_db(db_conn: &Pool<Sqlite>) -> Result<Vec<Issue>, String> {
+  let query_str =
+    "SELECT jira_id, key, project_key
+     FROM Issue;";
+
+  sqlx::query_as::<_, Issue>(query_str)
+    .fetch_all(db_conn)
+    .await
+    .map_err(|e| format!("Error occurred while trying to get issues from local database: {e}"))
+}
+
+async fn delete_issues_in_db(issue_ids: &[u32], db_conn: &Pool<Sqlite>) -> bool {
+  // rows referencing the issue must be deleted first to respect foreign key constraints.
+  let queries = [
+    "DELETE FROM IssueField WHERE issue_id = ?;",
+    "DELETE FROM IssueLink WHERE outward_issue_id = ?1 OR inward_issue_id = ?1;",
+    "DELETE FROM Comment WHERE issue_id = ?;",
+    "DELETE FROM IssueHistory WHERE issue_id = ?;",
+    "DELETE FROM Worklog WHERE issue_id = ?;",
+    "DELETE FROM IssueFixVersion WHERE issue_id = ?;",
+    "DELETE FROM IssueComponent WHERE issue_id = ?;",
+    "DELETE FROM Attachment WHERE issue_id = ?;",
+    "DELETE FROM watcher WHERE Issue = ?;",
+    "DELETE FROM voter WHERE Issue = ?;",
+    "DELETE FROM IssueHierarchy WHERE child_id = ?1 OR parent_id = ?1;",
+    "DELETE FROM IssueTransition WHERE issue_id = ?;",
+    "DELETE FROM Issue WHERE jira_id = ?;",
+  ];
+
+  let mut tx = match db_conn.begin().await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("Error when starting a sql transaction to delete issues. Err: {e:?}");
+      return false;
+    }
+  };
+
+  for issue_id in issue_ids {
+    for query_str in queries {
+      let res = sqlx::query(query_str)
+        .bind(issue_id)
+        .execute(&mut *tx)
+        .await;
+      if let Err(e) = res {
+        eprintln!("Error when deleting issue with id {issue_id} with query [{query_str}]. Err: {e:?}");
+        return false; // dropping the transaction rolls it back
+      }
+    }
+  }
+
+  match tx.commit().await {
+    Ok(_) => { true }
+    Err(e) => {
+      eprintln!("Error when committing the deletion of issues. Err: {e:?}");
+      false
+    }
+  }
+}
+
+async fn rekey_issues_in_db(issues: &[&Issue], db_conn: &Pool<Sqlite>) -> bool {
+  let query_str =
+    "UPDATE Issue
+     SET key = ?, project_key = ?
+     WHERE jira_id = ?;";
+
+  let mut tx = match db_conn.begin().await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("Error when starting a sql transaction to rename issues. Err: {e:?}");
+      return false;
+    }
+  };
+
+  for Issue { jira_id, key, project_key } in issues {
+    let res = sqlx::query(query_str)
+      .bind(key)
+      .bind(project_key)
+      .bind(jira_id)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when renaming issue with id {jira_id} to {key}. Err: {e:?}");
+      return false;
+    }
+  }
+
+  match tx.commit().await {
+    Ok(_) => { true }
+    Err(e) => {
+      eprintln!("Error when committing the renaming of issues. Err: {e:?}");
+      false
+    }
+  }
+}
+
+// Returns the keys which disappeared from the local database, either because the ticket was
+// deleted (or moved out of the interesting projects), or because it got a new key.
+pub(crate) async fn reconcile_interesting_projects_in_db(config: &Config, db_conn: &Pool<Sqlite>) -> Vec<String> {
+  let interesting_projects = config.interesting_projects();
+
+  // all projects must be known before deleting anything: a ticket missing from a project may
+  // have been moved to another interesting project.
+  let mut issues_on_server = HashMap::new();
+  for project_key in interesting_projects {
+    match get_issue_ids_of_project_from_server(config, project_key).await {
+      Ok(issues) => {
+        issues_on_server.extend(issues.into_iter().map(|x| (x.jira_id, x)));
+      }
+      Err(e) => {
+        eprintln!("Not reconciling local tickets with the jira server since the list of tickets of project {project_key} couldn't be retrieved. Err: {e}");
+        return Vec::new();
+      }
+    }
+  }
+
+  let issues_in_db = match get_issues_from_db(db_conn).await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("{e}");
+      return Vec::new();
+    }
+  };
+
+  let mut issues_to_delete = Vec::new();
+  let mut deleted_keys = Vec::new();
+  let mut issues_to_rekey = Vec::new();
+  let mut old_keys_of_rekeyed_issues = Vec::new();
+  for issue in &issues_in_db {
+    match issues_on_server.get(&issue.jira_id) {
+      Some(issue_on_server) if issue_on_server.key != issue.key => {
+        issues_to_rekey.push(issue_on_server);
+        old_keys_of_rekeyed_issues.push(issue.key.clone());
+      }
+      Some(_) => {}
+      // tickets of projects which are no longer interesting are left alone
+      None if interesting_projects.contains(&issue.project_key) => {
+        issues_to_delete.push(issue.jira_id);
+        deleted_keys.push(issue.key.clone());
+      }
+      None => {}
+    }
+  }
+
+  let mut changes = TicketChanges::default();
+
+  // deletions first, in case a vanished ticket's key got reused by a moved one.
+  if !issues_to_delete.is_empty() {
+    if !delete_issues_in_db(issues_to_delete.as_slice(), db_conn).await {
+      return Vec::new();
+    }
+    eprintln!("Removed tickets deleted on the jira server or moved out of the interesting projects: [{keys}]", keys = deleted_keys.join(","));
+    changes.deleted.append(&mut deleted_keys);
+  }
+
+  if !issues_to_rekey.is_empty() && rekey_issues_in_db(issues_to_rekey.as_slice(), db_conn).await {
+    let new_keys = issues_to_rekey
+      .iter()
+      .map(|x| x.key.clone())
+      .collect::<Vec<_>>();
+    eprintln!("Renamed tickets moved to another project: [{old}] are now [{new}]", old = old_keys_of_rekeyed_issues.join(","), new = new_keys.join(","));
+    changes.deleted.append(&mut old_keys_of_rekeyed_issues);
+    changes.created.extend(new_keys);
+  }
+
+  let res = changes.deleted.clone();
+  publish_ticket_changes(changes);
+  res
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn issue_ids_of_search_result() {
+    let json = json!({"issues": [{"id": "10001", "key": "PROJ-1"}, {"id": "10002", "key": "PROJ-2"}]});
+    let issues = get_issue_ids_from_json(&json, "PROJ").unwrap();
+    assert_eq!(issues, vec![
+      Issue { jira_id: 10001, key: "PROJ-1".to_string(), project_key: "PROJ".to_string() },
+      Issue { jira_id: 10002, key: "PROJ-2".to_string(), project_key: "PROJ".to_string() },
+    ]);
+  }
+
+  #[test]
+  fn issue_ids_of_empty_search_result() {
+    let json = json!({"issues": []});
+    assert_eq!(get_issue_ids_from_json(&json, "PROJ"), Ok(vec![]));
+  }
+
+  #[test]
+  fn issue_ids_of_search_result_without_issues() {
+    assert!(get_issue_ids_from_json(&json!({"errorMessages": ["oops"]}), "PROJ").is_err());
+  }
+
+  #[test]
+  fn issue_ids_of_search_result_with_unreadable_issue() {
+    let json = json!({"issues": [{"id": "10001", "key": "PROJ-1"}, {"id": "not a number", "key": "PROJ-2"}]});
+    assert!(get_issue_ids_from_json(&json, "PROJ").is_err());
+    let json = json!({"issues": [{"id": "10001"}]});
+    assert!(get_issue_ids_from_json(&json, "PROJ").is_err());
+  }
+}
@@ -13,6 +13,8 @@ use crate::get_issue_details::add_details_to_issue_in_db;
 use crate::get_project_tasks_from_server::get_project_tasks_from_server;
 use crate::manage_issue_field::fill_issues_fields_from_json;
 use crate::manage_project_table::Project;
+use crate::manage_synchronisation_table::{finish_synchronisation_of_project, get_current_timestamp};
+use crate::sync_progress::ProgressReporter;
 
 
 #[derive(FromRow, Hash, PartialEq, Eq, Debug)]
@@ -426,8 +428,9 @@ pub(crate) async fn update_issue_links_in_db(issues_ids: &[u32], issue_links: &V
   }
 }
 
-async fn initialise_given_project_in_db(config: Config, project_key: String, mut db_conn: Pool<Sqlite>) {
-  let json_tickets = get_project_tasks_from_server(project_key.as_str(), &config).await;
+async fn initialise_given_project_in_db(config: Config, project_key: String, mut db_conn: Pool<Sqlite>, mut progress: ProgressReporter) {
+  let synchronisation_start = get_current_timestamp();
+  let json_tickets = get_project_tasks_from_server(project_key.as_str(), &config, &mut progress).await;
   let mut db_handle = db_conn.clone();
 
   if let Ok(paginated_json_tickets) = json_tickets {
@@ -481,20 +484,27 @@ async fn initialise_given_project_in_db(config: Config, project_key: String, mut
       .map(|x| &x.key)
       .collect::<Vec<_>>();
 
-    for key in issues_keys {
-      add_details_to_issue_in_db(&config,
-                                 &key,
-                                 &mut db_conn).await
+    let nr_tickets = issues_keys.len();
+    progress.tickets(0, nr_tickets).await;
+    let mut failed_keys = Vec::new();
+    for (i, key) in issues_keys.into_iter().enumerate() {
+      if let Err(e) = add_details_to_issue_in_db(&config, key, &mut db_conn).await {
+        eprintln!("{e}");
+        failed_keys.push(key.as_str());
+      }
+      progress.tickets(i + 1, nr_tickets).await;
     }
+
+    finish_synchronisation_of_project(project_key.as_str(), synchronisation_start, failed_keys.as_slice(), &db_conn).await;
   }
 }
 
-pub(crate) async fn initialise_interesting_projects_in_db(config: &Config, db_conn: &mut Pool<Sqlite>) {
+pub(crate) async fn initialise_interesting_projects_in_db(config: &Config, db_conn: &mut Pool<Sqlite>, progress: &ProgressReporter) {
   let interesting_projects = config.interesting_projects();
 
   let mut tasks = interesting_projects
     .iter()
-    .map(|x| tokio::spawn(initialise_given_project_in_db(config.clone(), x.clone(), db_conn.clone())))
+    .map(|x| tokio::spawn(initialise_given_project_in_db(config.clone(), x.clone(), db_conn.clone(), progress.for_project(x))))
     .collect::<JoinSet<_>>();
 
   while let Some(res) = tasks.join_next().await {
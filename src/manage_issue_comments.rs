@@ -1,8 +1,10 @@
 use crate::get_config::Config;
-use crate::get_json_from_url::get_json_from_url;
+use crate::get_json_from_url::{get_json_from_url, post_json_to_url};
 use crate::manage_field_table::FieldMetadata;
 use crate::manage_interesting_projects::Issue;
+use crate::markdown_to_atlassian_document_format::markdown_to_adf;
 use sqlx::sqlite::SqliteQueryResult;
+use serde_json::json;
 use sqlx::types::JsonValue;
 use sqlx::{Error, FromRow, Pool, Sqlite};
 use std::collections::{HashMap, HashSet};
@@ -39,6 +41,96 @@ async fn get_comments_as_json_for_issue(
     Ok(json_data)
 }
 
+fn get_comment_from_json(x: &JsonValue, issue_id: u32) -> Option<commentFromJson> {
+  let Some(x) = x.as_object() else {
+    eprintln!("expected comment has the wrong format. Expected json object. Got {a}", a=x.to_string());
+    return None;
+  };
+
+  let Some(created) = x.get("created") else {
+    eprintln!("expected comment has the wrong format. Missing 'created' field");
+    return None;
+  };
+  let Some(created) = created.as_str() else {
+    eprintln!("created value has the wrong type. Should be a json string. is '{x}' instead", x = created.to_string());
+    return None;
+  };
+
+
+  let Some(modified) = x.get("updated") else {
+    eprintln!("expected comment has the wrong format. Missing 'updated' field");
+    return None;
+  };
+  let Some(modified) = modified.as_str() else {
+    eprintln!("updated value has the wrong type. Should be a json string. is '{x}' instead", x = modified.to_string());
+    return None;
+  };
+
+  let Some(content) = x.get("body") else {
+    eprintln!("expected comment has the wrong format. Missing 'updated' field");
+    return None;
+  };
+
+  let Some(author) = x.get("author") else {
+    eprintln!("expected comment has the wrong format. Missing 'author' field");
+    return None;
+  };
+  let Some(author) = author.as_object() else {
+    eprintln!("expected comment has the wrong format. 'author' should be a json object, but instead is {author}");
+    return None;
+  };
+  let Some(author_account_id) = author.get("accountId") else {
+    eprintln!("expected comment has the wrong format. 'author' should contain an accountId. Instead it is {author:?}");
+    return None;
+  };
+  let Some(author_account_id) = author_account_id.as_str() else {
+    eprintln!("Invalid comment format. 'author account id' should be a json string. Instead, it is {author_account_id}");
+    return None;
+  };
+  let Some(author_display_name) = author.get("displayName") else {
+    eprintln!("expected comment has the wrong format. 'author' should contain a displayName. Instead it is {author:?}");
+    return None;
+  };
+  let Some(author_display_name) = author_display_name.as_str() else {
+    eprintln!("Invalid comment format. 'author display name' should be a json string. Instead, it is {author_display_name}");
+    return None;
+  };
+
+  let author = Author {
+    accountId: author_account_id.to_string(),
+    displayName: author_display_name.to_string()
+  };
+
+  let Some(id) = x.get("id") else {
+    eprintln!("expected comment has the wrong format. Missing 'id' field");
+    return None;
+  };
+
+  let Some(id) = id.as_str() else {
+    eprintln!("expected comment has the wrong format. 'id' field is not a json string. It is {id}");
+    return None;
+  };
+  let id = match str::parse::<i64>(id) {
+    Ok(x) => {x}
+    Err(e) => {
+      eprintln!("expected comment has the wrong format. Can't get a i64 out of 'id'. id is {id}, err is {e}");
+      return None;
+    }
+  };
+  let created = created.to_string();
+  let modified = modified.to_string();
+  let created = remove_surrounding_quotes(created);
+  let modified = remove_surrounding_quotes(modified);
+  Some(commentFromJson {
+    author,
+    created,
+    modified,
+    content: content.to_string(),
+    issue_id,
+    id,
+  })
+}
+
 async fn get_comments_from_server_for_issue(
     config: &Config,
     issue_id: u32,
@@ -79,96 +171,9 @@ async fn get_comments_from_server_for_issue(
     };
 
     let comments = comments
-    .into_iter()
-    .filter_map(|x| {
-      let Some(x) = x.as_object() else {
-        eprintln!("expected comment has the wrong format. Expected json object. Got {a}", a=x.to_string());
-        return None;
-      };
-
-      let Some(created) = x.get("created") else {
-        eprintln!("expected comment has the wrong format. Missing 'created' field");
-        return None;
-      };
-      let Some(created) = created.as_str() else {
-        eprintln!("created value has the wrong type. Should be a json string. is '{x}' instead", x = created.to_string());
-        return None;
-      };
-
-
-      let Some(modified) = x.get("updated") else {
-        eprintln!("expected comment has the wrong format. Missing 'updated' field");
-        return None;
-      };
-      let Some(modified) = modified.as_str() else {
-        eprintln!("updated value has the wrong type. Should be a json string. is '{x}' instead", x = modified.to_string());
-        return None;
-      };
-
-      let Some(content) = x.get("body") else {
-        eprintln!("expected comment has the wrong format. Missing 'updated' field");
-        return None;
-      };
-
-      let Some(author) = x.get("author") else {
-        eprintln!("expected comment has the wrong format. Missing 'author' field");
-        return None;
-      };
-      let Some(author) = author.as_object() else {
-        eprintln!("expected comment has the wrong format. 'author' should be a json object, but instead is {author}");
-        return None;
-      };
-      let Some(author_account_id) = author.get("accountId") else {
-        eprintln!("expected comment has the wrong format. 'author' should contain an accountId. Instead it is {author:?}");
-        return None;
-      };
-      let Some(author_account_id) = author_account_id.as_str() else {
-        eprintln!("Invalid comment format. 'author account id' should be a json string. Instead, it is {author_account_id}");
-        return None;
-      };
-      let Some(author_display_name) = author.get("displayName") else {
-        eprintln!("expected comment has the wrong format. 'author' should contain a displayName. Instead it is {author:?}");
-        return None;
-      };
-      let Some(author_display_name) = author_display_name.as_str() else {
-        eprintln!("Invalid comment format. 'author display name' should be a json string. Instead, it is {author_display_name}");
-        return None;
-      };
-
-      let author = Author {
-        accountId: author_account_id.to_string(),
-        displayName: author_display_name.to_string()
-      };
-
-      let Some(id) = x.get("id") else {
-        eprintln!("expected comment has the wrong format. Missing 'id' field");
-        return None;
-      };
-
-      let Some(id) = id.as_str() else {
-        eprintln!("expected comment has the wrong format. 'id' field is not a json string. It is {id}");
-        return None;
-      };
-      let id = match str::parse::<i64>(id) {
-        Ok(x) => {x}
-        Err(e) => {
-          eprintln!("expected comment has the wrong format. Can't get a i64 out of 'id'. id is {id}, err is {e}");
-          return None;
-        }
-      };
-      let created = created.to_string();
-      let modified = modified.to_string();
-      let created = remove_surrounding_quotes(created);
-      let modified = remove_surrounding_quotes(modified);
-      Some(commentFromJson {
-        author,
-        created,
-        modified,
-        content: content.to_string(),
-        issue_id,
-        id,
-      })
-    }).collect::<Vec<_>>();
+      .iter()
+      .filter_map(|x| get_comment_from_json(x, issue_id))
+      .collect::<Vec<_>>();
 
     Some(comments)
 }
@@ -315,7 +320,7 @@ async fn update_comments_in_db(comments_in_remote_for_issue: Vec<commentFromJson
 
              let query_str = "INSERT INTO People (accountId, displayName) VALUES
                 (?, ?)
-            ON CONFLICT DO
+            ON CONFLICT(accountId) DO
             UPDATE SET displayName = excluded.displayName";
 
             // first, insert the authors since the comments references them as a foreign key
@@ -491,10 +496,10 @@ pub async fn add_comments_for_issue_into_db(
     config: &Config,
     issue_id: u32,
     db_conn: &mut Pool<Sqlite>,
-) {
+) -> Result<(), String> {
     let comments_in_remote_for_issue = get_comments_from_server_for_issue(&config, issue_id).await;
     let Some(comments_in_remote_for_issue) = comments_in_remote_for_issue else {
-      return;
+      return Err(format!("Error: failed to get the comments of issue {issue_id} from server"));
     };
 
     let comments_in_db_for_issue = get_comments_from_db_for_issue(issue_id, db_conn).await;
@@ -502,4 +507,79 @@ pub async fn add_comments_for_issue_into_db(
     update_comments_in_db(comments_in_remote_for_issue,
                           comments_in_db_for_issue.as_ref(),
                           issue_id, db_conn).await;
+    Ok(())
+}
+
+async fn insert_new_comment_into_db(comment: &commentFromJson, db_conn: &Pool<Sqlite>) -> Result<(), String> {
+  let mut tx = db_conn
+    .begin()
+    .await
+    .map_err(|e| format!("Error when starting a sql transaction to add comment {id}. Err: {e:?}", id = comment.id))?;
+
+  sqlx::query(
+    "INSERT INTO People (accountId, displayName) VALUES (?, ?)
+     ON CONFLICT(accountId) DO
+     UPDATE SET displayName = excluded.displayName;")
+    .bind(&comment.author.accountId)
+    .bind(&comment.author.displayName)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| format!("Error when adding the author of comment {id}. Err: {e:?}", id = comment.id))?; // dropping the transaction rolls it back
+
+  // a new comment comes after the existing ones
+  sqlx::query(
+    "INSERT INTO Comment (id, issue_id, position_in_array, content_data, author, creation_time, last_modification_time)
+     SELECT ?, ?, COALESCE(MAX(position_in_array) + 1, 0), ?, ?, ?, ?
+     FROM Comment
+     WHERE issue_id = ?;")
+    .bind(comment.id)
+    .bind(comment.issue_id)
+    .bind(&comment.content)
+    .bind(&comment.author.accountId)
+    .bind(&comment.created)
+    .bind(&comment.modified)
+    .bind(comment.issue_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| format!("Error when adding comment {id}. Err: {e:?}", id = comment.id))?;
+
+  tx.commit()
+    .await
+    .map_err(|e| format!("Error when committing comment {id}. Err: {e:?}", id = comment.id))
+}
+
+// Adds the comment on the jira server, and stores the comment it created right away instead of
+// waiting for the next synchronisation of the issue. Returns the id of the new comment.
+pub(crate) async fn add_comment_to_issue(config: &Config, issue_key: &str, markdown: &str, db_conn: &Pool<Sqlite>) -> Result<i64, String> {
+  let body = json!({"body": markdown_to_adf(markdown)});
+  let query = format!("/rest/api/3/issue/{issue_key}/comment");
+  let json_data = post_json_to_url(config, query.as_str(), &body)
+    .await
+    .map_err(|e| format!("Error: failed to add a comment to issue {issue_key} on the server. {e}"))?;
+
+  let issue_id = sqlx::query_as::<_, IssueId>("SELECT jira_id AS id FROM Issue WHERE key = ?;")
+    .bind(issue_key)
+    .fetch_optional(db_conn)
+    .await;
+  let issue_id = match issue_id {
+    Ok(issue_id) => { issue_id.map(|x| x.id as u32) }
+    Err(e) => {
+      eprintln!("Error occurred while trying to get the id of issue {issue_key} from local database: {e}");
+      None
+    }
+  };
+
+  let Some(comment) = get_comment_from_json(&json_data, issue_id.unwrap_or_default()) else {
+    return Err(format!("Comment added to issue {issue_key}, but the server replied with an unexpected json: {json_data}"));
+  };
+
+  // comments of issues which aren't synchronised aren't stored. For the others, the comment is on
+  // the server already, the next synchronisation of the issue will catch up if storing it fails.
+  if issue_id.is_some() {
+    if let Err(e) = insert_new_comment_into_db(&comment, db_conn).await {
+      eprintln!("{e}");
+    }
+  }
+
+  Ok(comment.id)
 }
@@ -2,8 +2,9 @@ use crate::manage_interesting_projects::{get_id, Issue};
 use serde_json::Value;
 use sqlx::{FromRow, Pool, Sqlite};
 use std::cmp::Ordering;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use crate::manage_issuetype_table::IssueType;
+use crate::ticket_events::{has_subscribers, publish_ticket_changes, TicketChanges, UpdatedTicket};
 
 #[derive(Hash, Eq, PartialEq, FromRow, Debug)]
 pub(crate) struct KeyValueProperty {
@@ -205,6 +206,74 @@ fn get_properties_in_remote_not_in_db<'a>(
     res
 }
 
+#[derive(FromRow)]
+struct IssueKey {
+    jira_id: u32,
+    key: String,
+}
+
+async fn get_ticket_changes(
+    properties_to_insert: &[&BrokenIssueProperties],
+    properties_in_db: &[(u32, HashSet<BrokenIssueProperties>)],
+    db_conn: &Pool<Sqlite>,
+) -> TicketChanges {
+    let mut changes = TicketChanges::default();
+    if !has_subscribers() {
+        return changes;
+    }
+
+    let mut changed_fields_per_issue = BTreeMap::<u32, Vec<String>>::new();
+    for property in properties_to_insert {
+        changed_fields_per_issue
+            .entry(property.issue_id)
+            .or_default()
+            .push(property.field_id.clone());
+    }
+    if changed_fields_per_issue.is_empty() {
+        return changes;
+    }
+
+    // the ids are passed as a json array to get all the keys with a single query
+    let issue_ids = serde_json::json!(changed_fields_per_issue.keys().collect::<Vec<_>>()).to_string();
+    let query_str = "SELECT jira_id, key
+     FROM Issue
+     WHERE jira_id IN (SELECT value FROM json_each(?));";
+    let keys = sqlx::query_as::<_, IssueKey>(query_str)
+        .bind(issue_ids)
+        .fetch_all(db_conn)
+        .await;
+    let keys = match keys {
+        Ok(v) => v
+            .into_iter()
+            .map(|x| (x.jira_id, x.key))
+            .collect::<HashMap<_, _>>(),
+        Err(e) => {
+            eprintln!("Error when fetching the keys of the changed issues: {e}");
+            return changes;
+        }
+    };
+
+    for (issue_id, mut fields) in changed_fields_per_issue {
+        let Some(key) = keys.get(&issue_id).cloned() else {
+            eprintln!("Error: no issue with id {issue_id} in the local database");
+            continue;
+        };
+
+        let is_new_issue = properties_in_db
+            .iter()
+            .any(|(id, properties)| *id == issue_id && properties.is_empty());
+        if is_new_issue {
+            changes.created.push(key);
+        } else {
+            fields.sort();
+            fields.dedup();
+            changes.updated.push(UpdatedTicket { key, fields });
+        }
+    }
+
+    changes
+}
+
 pub(crate) async fn fill_issues_fields(properties_in_remote: &Vec<IssueProperties>, db_conn: &mut Pool<Sqlite>) {
     let ids = properties_in_remote
       .iter()
@@ -227,14 +296,21 @@ pub(crate) async fn fill_issues_fields(properties_in_remote: &Vec<IssuePropertie
         flattened_properties_in_db.as_slice(),
     );
 
+    let changes = get_ticket_changes(
+        properties_to_insert.as_slice(),
+        flattened_properties_in_db.as_slice(),
+        db_conn,
+    ).await;
+
     // do not remove properties in db but not in remote here. This is because jira returns less
     // data when downloading {maxResults} issues at a time, than when accessing a single field.
     // deleting data here would mean removing the extra data we get when updating a single ticket.
     // To keep things properly synchronised, we can only remove fields when updating single tickets.
 
-    match properties_to_insert.is_empty() {
+    let is_insertion_successful = match properties_to_insert.is_empty() {
         true => {
-            eprintln!("No new field issue detected on remote")
+            eprintln!("No new field issue detected on remote");
+            true
         }
         false => {
             let query_str = "INSERT INTO IssueField (issue_id, field_id, field_value)
@@ -278,7 +354,12 @@ pub(crate) async fn fill_issues_fields(properties_in_remote: &Vec<IssuePropertie
             } else {
                 eprintln!("updated Issue fields in database: {row_affected} rows were inserted")
             }
+            !has_error
         }
+    };
+
+    if is_insertion_successful {
+        publish_ticket_changes(changes);
     }
 }
 
@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use serde_json::{Map, Value};
+use sqlx::{FromRow, Pool, Sqlite};
+use crate::server::TicketTreeNode;
+
+// Parent/child relationships between tickets: sub-tasks and their parent, and the children of
+// epics. Jira gives the parent of every ticket in its parent field, and the sub-tasks of a ticket
+// in its subtasks field. Children of epics are only known through their own parent field.
+
+// jira hierarchies are a few levels deep (epic, story, sub-task), this only guards against loops
+const MAX_TREE_DEPTH: i64 = 10;
+
+fn get_id_and_key_from_json(json: &Value) -> Option<(i64, &str)> {
+  let id = json
+    .get("id")
+    .and_then(|x| x.as_str())
+    .and_then(|x| x.parse::<i64>().ok());
+  let key = json
+    .get("key")
+    .and_then(|x| x.as_str());
+  id.zip(key)
+}
+
+pub(crate) async fn update_hierarchy_of_issue_in_db(issue_key: &str, issue_id: u32, json_data: &Value, db_conn: &Pool<Sqlite>) {
+  let fields = json_data.get("fields");
+  let parent_id = fields
+    .and_then(|x| x.get("parent"))
+    .and_then(get_id_and_key_from_json)
+    .map(|(id, _key)| id);
+  let subtask_ids = fields
+    .and_then(|x| x.get("subtasks"))
+    .and_then(|x| x.as_array())
+    .map(|x| x
+      .iter()
+      .filter_map(get_id_and_key_from_json)
+      .map(|(id, _key)| id)
+      .collect::<Vec<_>>())
+    .unwrap_or_default();
+
+  let mut tx = match db_conn.begin().await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("Error when starting a sql transaction to update the hierarchy of issue {issue_key}. Err: {e:?}");
+      return;
+    }
+  };
+
+  // the other children of the issue, like the children of an epic, are updated along with them
+  let res = match parent_id {
+    None => {
+      sqlx::query("DELETE FROM IssueHierarchy WHERE child_id = ?;")
+        .bind(issue_id)
+        .execute(&mut *tx)
+        .await
+    }
+    Some(parent_id) => {
+      sqlx::query(
+        "INSERT INTO IssueHierarchy (child_id, parent_id) VALUES (?, ?)
+         ON CONFLICT DO UPDATE SET parent_id = excluded.parent_id;")
+        .bind(issue_id)
+        .bind(parent_id)
+        .execute(&mut *tx)
+        .await
+    }
+  };
+  if let Err(e) = res {
+    eprintln!("Error when updating the parent of issue {issue_key}. Err: {e:?}");
+    return; // dropping the transaction rolls it back
+  }
+
+  for subtask_id in &subtask_ids {
+    let res = sqlx::query(
+      "INSERT INTO IssueHierarchy (child_id, parent_id) VALUES (?, ?)
+       ON CONFLICT DO UPDATE SET parent_id = excluded.parent_id;")
+      .bind(subtask_id)
+      .bind(issue_id)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when adding sub-task {subtask_id} of issue {issue_key}. Err: {e:?}");
+      return;
+    }
+  }
+
+  if let Err(e) = tx.commit().await {
+    eprintln!("Error when committing the hierarchy of issue {issue_key}. Err: {e:?}");
+  }
+}
+
+#[derive(FromRow)]
+struct TreeRow {
+  issue_id: i64,
+  parent_id: Option<i64>,
+  key: String,
+  summary: Option<String>,
+  status: Option<String>,
+}
+
+// the ticket itself comes first, followed by its descendants, depth first. Children are sorted by
+// issue id.
+pub(crate) async fn get_ticket_tree_from_db(issue_key: &str, db_conn: &Pool<Sqlite>) -> Result<Vec<TicketTreeNode>, String> {
+  let query_str =
+    "WITH RECURSIVE Tree(issue_id, parent_id, depth) AS (
+       SELECT jira_id, NULL, 0 FROM Issue WHERE key = ?
+       UNION
+       SELECT IssueHierarchy.child_id, IssueHierarchy.parent_id, Tree.depth + 1
+       FROM IssueHierarchy
+       JOIN Tree ON Tree.issue_id = IssueHierarchy.parent_id
+       WHERE Tree.depth < ?
+     )
+     SELECT Tree.issue_id AS issue_id,
+            Tree.parent_id AS parent_id,
+            Issue.key AS key,
+            json_extract(IssueField.field_value, '$') AS summary,
+            Status.name AS status
+     FROM Tree
+     JOIN Issue ON Issue.jira_id = Tree.issue_id
+     LEFT JOIN IssueField ON IssueField.issue_id = Tree.issue_id AND IssueField.field_id = 'summary'
+     LEFT JOIN Status ON Status.jira_id = Issue.status_id
+     ORDER BY Tree.issue_id ASC;";
+
+  let rows = sqlx::query_as::<_, TreeRow>(query_str)
+    .bind(issue_key)
+    .bind(MAX_TREE_DEPTH)
+    .fetch_all(db_conn)
+    .await
+    .map_err(|e| format!("Error occurred while trying to get the tree of issue {issue_key} from local database: {e}"))?;
+
+  let Some(root) = rows.iter().find(|x| x.parent_id.is_none()) else {
+    return Err(format!("Issue {issue_key} isn't in the local database"));
+  };
+
+  let mut children = HashMap::<i64, Vec<&TreeRow>>::new();
+  for row in &rows {
+    if let Some(parent_id) = row.parent_id {
+      children.entry(parent_id).or_default().push(row);
+    }
+  }
+
+  let keys = rows
+    .iter()
+    .map(|x| (x.issue_id, x.key.as_str()))
+    .collect::<HashMap<_, _>>();
+
+  let mut res = Vec::new();
+  let mut to_visit = vec![(root, 0)];
+  while let Some((row, depth)) = to_visit.pop() {
+    res.push(TicketTreeNode {
+      key: row.key.clone(),
+      parent: row.parent_id.and_then(|x| keys.get(&x)).map(|x| x.to_string()),
+      depth,
+      summary: row.summary.clone(),
+      status: row.status.clone(),
+    });
+    // a ticket reached twice would be a cycle, which jira doesn't allow
+    if res.len() > rows.len() {
+      return Err(format!("The hierarchy of issue {issue_key} contains a cycle"));
+    }
+    if let Some(row_children) = children.get(&row.issue_id) {
+      // rows are sorted by id, pushed in reverse to be visited in order
+      to_visit.extend(row_children.iter().rev().map(|x| (*x, depth + 1)));
+    }
+  }
+  Ok(res)
+}
+
+#[derive(FromRow, Debug, PartialEq, Eq)]
+pub(crate) struct Relative {
+  pub(crate) issue_id: i64,
+  pub(crate) key: String,
+  pub(crate) summary: Option<String>,
+}
+
+pub(crate) async fn get_parent_from_db(issue_key: &str, db_conn: &Pool<Sqlite>) -> Result<Option<Relative>, String> {
+  let query_str =
+    "SELECT Parent.jira_id AS issue_id, Parent.key AS key, json_extract(IssueField.field_value, '$') AS summary
+     FROM Issue
+     JOIN IssueHierarchy ON IssueHierarchy.child_id = Issue.jira_id
+     JOIN Issue AS Parent ON Parent.jira_id = IssueHierarchy.parent_id
+     LEFT JOIN IssueField ON IssueField.issue_id = Parent.jira_id AND IssueField.field_id = 'summary'
+     WHERE Issue.key = ?;";
+
+  sqlx::query_as::<_, Relative>(query_str)
+    .bind(issue_key)
+    .fetch_optional(db_conn)
+    .await
+    .map_err(|e| format!("Error occurred while trying to get the parent of issue {issue_key} from local database: {e}"))
+}
+
+pub(crate) async fn get_children_from_db(issue_key: &str, db_conn: &Pool<Sqlite>) -> Result<Vec<Relative>, String> {
+  let query_str =
+    "SELECT Child.jira_id AS issue_id, Child.key AS key, json_extract(IssueField.field_value, '$') AS summary
+     FROM Issue
+     JOIN IssueHierarchy ON IssueHierarchy.parent_id = Issue.jira_id
+     JOIN Issue AS Child ON Child.jira_id = IssueHierarchy.child_id
+     LEFT JOIN IssueField ON IssueField.issue_id = Child.jira_id AND IssueField.field_id = 'summary'
+     WHERE Issue.key = ?
+     ORDER BY Child.jira_id ASC;";
+
+  sqlx::query_as::<_, Relative>(query_str)
+    .bind(issue_key)
+    .fetch_all(db_conn)
+    .await
+    .map_err(|e| format!("Error occurred while trying to get the children of issue {issue_key} from local database: {e}"))
+}
+
+#[derive(Debug, PartialEq, Eq, Default)]
+pub(crate) struct Hierarchy {
+  pub(crate) parent: Option<Relative>,
+  pub(crate) children: Vec<Relative>, // sorted by issue id
+}
+
+pub(crate) async fn get_hierarchy_from_db(issue_key: &str, db_conn: &Pool<Sqlite>) -> Result<Hierarchy, String> {
+  let (parent, children) = tokio::join!(
+    get_parent_from_db(issue_key, db_conn),
+    get_children_from_db(issue_key, db_conn)
+  );
+  Ok(Hierarchy { parent: parent?, children: children? })
+}
+
+fn get_relative_from_json(json: &Value) -> Option<Relative> {
+  let (issue_id, key) = get_id_and_key_from_json(json)?;
+  let summary = json
+    .get("fields")
+    .and_then(|x| x.get("summary"))
+    .and_then(|x| x.as_str())
+    .map(|x| x.to_string());
+  Some(Relative { issue_id, key: key.to_string(), summary })
+}
+
+// the json of an issue only lists its sub-tasks, the children of epics are taken from children_in_db
+pub(crate) fn get_hierarchy_from_json(json_of_issue: &Map<String, Value>, children_in_db: Vec<Relative>) -> Hierarchy {
+  let fields = json_of_issue.get("fields");
+  let parent = fields
+    .and_then(|x| x.get("parent"))
+    .and_then(get_relative_from_json);
+  let subtasks = fields
+    .and_then(|x| x.get("subtasks"))
+    .and_then(|x| x.as_array())
+    .map(|x| x
+      .iter()
+      .filter_map(get_relative_from_json)
+      .collect::<Vec<_>>())
+    .unwrap_or_default();
+
+  let mut children = children_in_db
+    .into_iter()
+    .filter(|x| !subtasks.iter().any(|subtask| subtask.issue_id == x.issue_id))
+    .collect::<Vec<_>>();
+  children.extend(subtasks);
+  children.sort_by_key(|x| x.issue_id);
+  Hierarchy { parent, children }
+}
@@ -0,0 +1,407 @@
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use serde_json::Value;
+use sqlx::{FromRow, Pool, Sqlite};
+use crate::get_config::Config;
+use crate::get_json_from_url::get_json_from_url;
+use crate::server::HistoryItem;
+
+// The changelog of an issue is a list of histories. A history is a set of fields changed at once
+// by someone. Each changed field is stored as one row of the IssueHistory table.
+
+const MAX_HISTORIES_PER_PAGE: i64 = 100;
+
+#[derive(Debug, FromRow)]
+struct HistoryRow {
+  history_id: i64,
+  position_in_history: u32,
+  author_account_id: Option<String>, // none for changes made by jira itself, e.g. automations
+  author_display_name: Option<String>,
+  creation_time: String,
+  field: String,
+  from_value: Option<String>,
+  to_value: Option<String>,
+}
+
+// the display name of the author comes from the People table in the local database, and jira
+// doesn't always send it. It isn't part of the history itself, so it's left out of comparisons,
+// otherwise such rows would be replaced at each synchronisation.
+impl HistoryRow {
+  fn get_comparison_key(&self) -> (i64, u32, &str, Option<&str>, Option<&str>) {
+    (self.history_id, self.position_in_history, self.field.as_str(), self.from_value.as_deref(), self.to_value.as_deref())
+  }
+}
+
+impl PartialEq for HistoryRow {
+  fn eq(&self, other: &Self) -> bool {
+    self.get_comparison_key() == other.get_comparison_key()
+  }
+}
+
+impl Eq for HistoryRow {}
+
+impl Hash for HistoryRow {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.get_comparison_key().hash(state);
+  }
+}
+
+fn get_history_rows_from_json(history: &Value, issue_key: &str) -> Result<Vec<HistoryRow>, String> {
+  let history_id = history
+    .get("id")
+    .and_then(|x| x.as_str())
+    .and_then(|x| x.parse::<i64>().ok());
+  let creation_time = history
+    .get("created")
+    .and_then(|x| x.as_str());
+  let items = history
+    .get("items")
+    .and_then(|x| x.as_array());
+  let (Some(history_id), Some(creation_time), Some(items)) = (history_id, creation_time, items) else {
+    return Err(format!("Changelog of issue {issue_key} contains a history without id, creation time or items: {history}"));
+  };
+
+  let author = history.get("author");
+  let author_account_id = author
+    .and_then(|x| x.get("accountId"))
+    .and_then(|x| x.as_str())
+    .map(|x| x.to_string());
+  let author_display_name = author
+    .and_then(|x| x.get("displayName"))
+    .and_then(|x| x.as_str())
+    .map(|x| x.to_string());
+
+  // fromString and toString are the human readable values. They are missing for some fields,
+  // like the ones referencing people, in which case the ids are the best we have.
+  let get_value = |item: &Value, human_readable: &str, raw: &str| {
+    item
+      .get(human_readable)
+      .and_then(|x| x.as_str())
+      .or_else(|| item.get(raw).and_then(|x| x.as_str()))
+      .map(|x| x.to_string())
+  };
+
+  items
+    .iter()
+    .enumerate()
+    .map(|(position_in_history, item)| {
+      let Some(field) = item.get("field").and_then(|x| x.as_str()) else {
+        return Err(format!("Changelog of issue {issue_key} contains an item without field in history {history_id}: {item}"));
+      };
+      Ok(HistoryRow {
+        history_id,
+        position_in_history: position_in_history as u32,
+        author_account_id: author_account_id.clone(),
+        author_display_name: author_display_name.clone(),
+        creation_time: creation_time.to_string(),
+        field: field.to_string(),
+        from_value: get_value(item, "fromString", "from"),
+        to_value: get_value(item, "toString", "to"),
+      })
+    })
+    .collect()
+}
+
+async fn get_history_rows_from_server(config: &Config, issue_key: &str) -> Result<Vec<HistoryRow>, String> {
+  let mut res = Vec::new();
+  let mut start = 0;
+  loop {
+    let query = format!("/rest/api/3/issue/{issue_key}/changelog?startAt={start}&maxResults={MAX_HISTORIES_PER_PAGE}");
+    let json = match get_json_from_url(config, query.as_str()).await {
+      Ok(v) => { v }
+      Err(e) => { return Err(format!("Error: failed to get the changelog of issue {issue_key} from server.\n{e}")) }
+    };
+
+    let Some(histories) = json.get("values").and_then(|x| x.as_array()) else {
+      return Err(format!("Changelog of issue {issue_key} doesn't contain a list of histories"));
+    };
+    for history in histories {
+      res.extend(get_history_rows_from_json(history, issue_key)?);
+    }
+
+    start += histories.len() as i64;
+    let is_last = json
+      .get("isLast")
+      .and_then(|x| x.as_bool())
+      .unwrap_or(true);
+    let total = json
+      .get("total")
+      .and_then(|x| x.as_i64())
+      .unwrap_or(0);
+    if is_last || histories.is_empty() || start >= total {
+      // same order as in the local database
+      res.sort_by_key(|x| (x.history_id, x.position_in_history));
+      return Ok(res);
+    }
+  }
+}
+
+// the issue is fetched with expand=changelog, which embeds the first page of the changelog.
+// It holds the whole changelog of most tickets, so the changelog endpoint is only called for
+// the long ones.
+async fn get_history_rows(config: &Config, issue_key: &str, json_of_issue: &Value) -> Result<Vec<HistoryRow>, String> {
+  let changelog = json_of_issue.get("changelog");
+  let histories = changelog
+    .and_then(|x| x.get("histories"))
+    .and_then(|x| x.as_array());
+  let total = changelog
+    .and_then(|x| x.get("total"))
+    .and_then(|x| x.as_i64());
+  let histories = match (histories, total) {
+    (Some(histories), Some(total)) if histories.len() as i64 >= total => { histories }
+    _ => { return get_history_rows_from_server(config, issue_key).await }
+  };
+
+  let mut res = Vec::new();
+  for history in histories {
+    res.extend(get_history_rows_from_json(history, issue_key)?);
+  }
+  res.sort_by_key(|x| (x.history_id, x.position_in_history));
+  Ok(res)
+}
+
+async fn get_history_rows_from_db(issue_key: &str, db_conn: &Pool<Sqlite>) -> Result<Vec<HistoryRow>, String> {
+  let query_str =
+    "SELECT history_id, position_in_history, author AS author_account_id, People.displayName AS author_display_name,
+            creation_time, field, from_value, to_value
+     FROM IssueHistory
+     LEFT JOIN People ON People.accountId = IssueHistory.author
+     WHERE issue_id = (SELECT jira_id FROM Issue WHERE key = ?)
+     ORDER BY history_id ASC, position_in_history ASC;";
+
+  sqlx::query_as::<_, HistoryRow>(query_str)
+    .bind(issue_key)
+    .fetch_all(db_conn)
+    .await
+    .map_err(|e| format!("Error occurred while trying to get the history of issue {issue_key} from local database: {e}"))
+}
+
+async fn update_history_in_db(issue_key: &str,
+                              issue_id: u32,
+                              history_in_remote: &[HistoryRow],
+                              history_in_db: &[HistoryRow],
+                              db_conn: &Pool<Sqlite>) -> bool {
+  let history_in_remote_set = history_in_remote.iter().collect::<HashSet<_>>();
+  let history_in_db_set = history_in_db.iter().collect::<HashSet<_>>();
+
+  let rows_to_remove = history_in_db
+    .iter()
+    .filter(|x| !history_in_remote_set.contains(x))
+    .collect::<Vec<_>>();
+  let rows_to_insert = history_in_remote
+    .iter()
+    .filter(|x| !history_in_db_set.contains(x))
+    .collect::<Vec<_>>();
+
+  if rows_to_remove.is_empty() && rows_to_insert.is_empty() {
+    eprintln!("History of issue {issue_key} is up to date");
+    return true;
+  }
+
+  let mut tx = match db_conn.begin().await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("Error when starting a sql transaction to update the history of issue {issue_key}. Err: {e:?}");
+      return false;
+    }
+  };
+
+  // authors first, since the history references them as a foreign key. When jira doesn't send
+  // the display name, the account id stands for it, without overwriting a known display name.
+  let query_str =
+    "INSERT INTO People (accountId, displayName) VALUES (?1, COALESCE(?2, ?1))
+     ON CONFLICT(accountId) DO
+     UPDATE SET displayName = COALESCE(?2, People.displayName);";
+  let authors = rows_to_insert
+    .iter()
+    .filter_map(|x| x.author_account_id.as_ref().map(|id| (id, x.author_display_name.as_ref())))
+    .collect::<HashSet<_>>();
+  for (account_id, display_name) in authors {
+    let res = sqlx::query(query_str)
+      .bind(account_id)
+      .bind(display_name)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when adding author {account_id} of changes of issue {issue_key}. Err: {e:?}");
+      return false; // dropping the transaction rolls it back
+    }
+  }
+
+  let query_str = "DELETE FROM IssueHistory WHERE history_id = ? AND position_in_history = ?;";
+  for row in &rows_to_remove {
+    let res = sqlx::query(query_str)
+      .bind(row.history_id)
+      .bind(row.position_in_history)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when removing history {id} of issue {issue_key}. Err: {e:?}", id = row.history_id);
+      return false;
+    }
+  }
+
+  let query_str =
+    "INSERT INTO IssueHistory (history_id, position_in_history, issue_id, author, creation_time, field, from_value, to_value)
+     VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+     ON CONFLICT DO
+     UPDATE SET issue_id = excluded.issue_id,
+                author = excluded.author,
+                creation_time = excluded.creation_time,
+                field = excluded.field,
+                from_value = excluded.from_value,
+                to_value = excluded.to_value;";
+  for row in &rows_to_insert {
+    let res = sqlx::query(query_str)
+      .bind(row.history_id)
+      .bind(row.position_in_history)
+      .bind(issue_id)
+      .bind(&row.author_account_id)
+      .bind(&row.creation_time)
+      .bind(&row.field)
+      .bind(&row.from_value)
+      .bind(&row.to_value)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when adding history {id} of issue {issue_key}. Err: {e:?}", id = row.history_id);
+      return false;
+    }
+  }
+
+  match tx.commit().await {
+    Ok(_) => {
+      eprintln!("Updated history of issue {issue_key}: {removed} rows removed, {inserted} rows added",
+                removed = rows_to_remove.len(), inserted = rows_to_insert.len());
+      true
+    }
+    Err(e) => {
+      eprintln!("Error when committing the history of issue {issue_key}. Err: {e:?}");
+      false
+    }
+  }
+}
+
+// fails when the history couldn't be fetched from the jira server
+pub(crate) async fn add_history_for_issue_into_db(config: &Config,
+                                                  issue_key: &str,
+                                                  issue_id: u32,
+                                                  json_of_issue: &Value,
+                                                  db_conn: &Pool<Sqlite>) -> Result<(), String> {
+  let history_in_remote = get_history_rows(config, issue_key, json_of_issue).await?;
+
+  let history_in_db = match get_history_rows_from_db(issue_key, db_conn).await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("{e}");
+      return Ok(());
+    }
+  };
+
+  update_history_in_db(issue_key, issue_id, history_in_remote.as_slice(), history_in_db.as_slice(), db_conn).await;
+  Ok(())
+}
+
+fn to_history_items(rows: Vec<HistoryRow>) -> Vec<HistoryItem> {
+  rows
+    .into_iter()
+    .map(|x| HistoryItem {
+      author: x.author_display_name.or(x.author_account_id),
+      created: x.creation_time,
+      field: x.field,
+      from: x.from_value,
+      to: x.to_value,
+    })
+    .collect()
+}
+
+pub(crate) async fn get_history_of_issue_from_db(issue_key: &str, db_conn: &Pool<Sqlite>) -> Result<Vec<HistoryItem>, String> {
+  get_history_rows_from_db(issue_key, db_conn)
+    .await
+    .map(to_history_items)
+}
+
+pub(crate) async fn get_history_of_issue_from_server(config: &Config, issue_key: &str) -> Result<Vec<HistoryItem>, String> {
+  get_history_rows_from_server(config, issue_key)
+    .await
+    .map(to_history_items)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn history_rows_of_history() {
+    let history = json!({
+      "id": "10100",
+      "created": "2024-01-29T10:00:00.000+0100",
+      "author": {"accountId": "abc-123", "displayName": "Jane Doe"},
+      "items": [
+        {"field": "status", "from": "1", "fromString": "To Do", "to": "3", "toString": "In Progress"},
+        {"field": "labels", "fromString": null, "toString": "backend"}
+      ]
+    });
+    let rows = get_history_rows_from_json(&history, "PROJ-1").unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].history_id, 10100);
+    assert_eq!(rows[0].position_in_history, 0);
+    assert_eq!(rows[0].author_account_id.as_deref(), Some("abc-123"));
+    assert_eq!(rows[0].author_display_name.as_deref(), Some("Jane Doe"));
+    assert_eq!(rows[0].creation_time, "2024-01-29T10:00:00.000+0100");
+    assert_eq!(rows[0].field, "status");
+    assert_eq!(rows[0].from_value.as_deref(), Some("To Do"));
+    assert_eq!(rows[0].to_value.as_deref(), Some("In Progress"));
+    assert_eq!(rows[1].position_in_history, 1);
+    assert_eq!(rows[1].from_value, None);
+    assert_eq!(rows[1].to_value.as_deref(), Some("backend"));
+  }
+
+  #[test]
+  fn history_rows_without_human_readable_values() {
+    let history = json!({
+      "id": "10101",
+      "created": "2024-01-29T10:00:00.000+0100",
+      "items": [{"field": "assignee", "from": "abc-123", "to": "def-456"}]
+    });
+    let rows = get_history_rows_from_json(&history, "PROJ-1").unwrap();
+    assert_eq!(rows[0].author_account_id, None);
+    assert_eq!(rows[0].from_value.as_deref(), Some("abc-123"));
+    assert_eq!(rows[0].to_value.as_deref(), Some("def-456"));
+  }
+
+  #[test]
+  fn history_rows_without_author_display_name() {
+    let history = json!({
+      "id": "10102",
+      "created": "2024-01-29T10:00:00.000+0100",
+      "author": {"accountId": "abc-123"},
+      "items": [{"field": "summary", "fromString": "Old", "toString": "New"}]
+    });
+    let rows = get_history_rows_from_json(&history, "PROJ-1").unwrap();
+    assert_eq!(rows[0].author_account_id.as_deref(), Some("abc-123"));
+    assert_eq!(rows[0].author_display_name, None);
+
+    // the row stored in the database gets its display name from the People table
+    let mut stored_row = get_history_rows_from_json(&history, "PROJ-1").unwrap().remove(0);
+    stored_row.author_display_name = Some(String::from("abc-123"));
+    assert_eq!(rows[0], stored_row);
+    assert!([&stored_row].into_iter().collect::<HashSet<_>>().contains(&rows[0]));
+  }
+
+  #[test]
+  fn history_rows_with_different_values_differ() {
+    let history = json!({"id": "10103", "created": "2024-01-29T10:00:00.000+0100", "items": [{"field": "summary", "toString": "New"}]});
+    let changed_history = json!({"id": "10103", "created": "2024-01-29T10:00:00.000+0100", "items": [{"field": "summary", "toString": "Newer"}]});
+    assert_ne!(get_history_rows_from_json(&history, "PROJ-1"), get_history_rows_from_json(&changed_history, "PROJ-1"));
+  }
+
+  #[test]
+  fn invalid_histories() {
+    let without_id = json!({"created": "2024-01-29T10:00:00.000+0100", "items": []});
+    assert!(get_history_rows_from_json(&without_id, "PROJ-1").is_err());
+    let without_field = json!({"id": "10104", "created": "2024-01-29T10:00:00.000+0100", "items": [{"toString": "New"}]});
+    assert!(get_history_rows_from_json(&without_field, "PROJ-1").is_err());
+  }
+}
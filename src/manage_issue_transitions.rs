@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+use serde_json::{json, Map, Value};
+use sqlx::{FromRow, Pool, Sqlite};
+use crate::edit_issue_field::{get_issue_id_from_db, refresh_issue_fields_from_server, FieldValue};
+use crate::get_config::Config;
+use crate::get_json_from_url::{get_json_from_url, post_json_to_url};
+use crate::manage_field_table::get_fields_from_database;
+use crate::server::{KeyValueField, Transition};
+
+// Workflow transitions of the tickets. Which transitions are available depends on the status of
+// the ticket, on the workflow of its project and on the permissions of the user, so jira is asked
+// for each ticket. Some transitions show a screen with required fields, like the resolution when
+// closing a ticket, those have to be given along with the transition.
+
+#[derive(FromRow)]
+struct TransitionRow {
+  transition_id: i64,
+  name: String,
+  to_status_id: Option<i64>,
+  to_status: Option<String>,
+  required_fields: String, // json array of field ids
+}
+
+impl From<TransitionRow> for Transition {
+  fn from(row: TransitionRow) -> Self {
+    let required_fields = serde_json::from_str::<Vec<String>>(row.required_fields.as_str()).unwrap_or_default();
+    Transition { id: row.transition_id, name: row.name, to_status: row.to_status, required_fields }
+  }
+}
+
+// fields with a default value, like the resolution on some workflows, don't have to be given
+fn get_required_fields_from_json(fields: Option<&Map<String, Value>>) -> Vec<String> {
+  let Some(fields) = fields else {
+    return Vec::new();
+  };
+
+  let mut required_fields = fields
+    .iter()
+    .filter(|(_, field)| field.get("required").and_then(|x| x.as_bool()).unwrap_or(false))
+    .filter(|(_, field)| !field.get("hasDefaultValue").and_then(|x| x.as_bool()).unwrap_or(false))
+    .map(|(field_id, _)| field_id.clone())
+    .collect::<Vec<_>>();
+  required_fields.sort();
+  required_fields
+}
+
+fn get_transition_rows_from_json(json: &Value, issue_key: &str) -> Result<Vec<TransitionRow>, String> {
+  let Some(transitions) = json.get("transitions").and_then(|x| x.as_array()) else {
+    return Err(format!("Reply for the transitions of issue {issue_key} doesn't contain a list of transitions: {json}"));
+  };
+
+  transitions
+    .iter()
+    .map(|transition| {
+      let transition_id = transition
+        .get("id")
+        .and_then(|x| x.as_str())
+        .and_then(|x| x.parse::<i64>().ok());
+      let name = transition.get("name").and_then(|x| x.as_str());
+      let (Some(transition_id), Some(name)) = (transition_id, name) else {
+        return Err(format!("The transitions of issue {issue_key} contain a transition without id or name: {transition}"));
+      };
+      let to_status = transition.get("to");
+      let to_status_id = to_status
+        .and_then(|x| x.get("id"))
+        .and_then(|x| x.as_str())
+        .and_then(|x| x.parse::<i64>().ok());
+      let to_status_name = to_status
+        .and_then(|x| x.get("name"))
+        .and_then(|x| x.as_str())
+        .map(|x| x.to_string());
+      let required_fields = get_required_fields_from_json(transition.get("fields").and_then(|x| x.as_object()));
+      Ok(TransitionRow {
+        transition_id,
+        name: name.to_string(),
+        to_status_id,
+        to_status: to_status_name,
+        required_fields: json!(required_fields).to_string(),
+      })
+    })
+    .collect()
+}
+
+async fn get_transition_rows_from_server(config: &Config, issue_key: &str) -> Result<Vec<TransitionRow>, String> {
+  let query = format!("/rest/api/3/issue/{issue_key}/transitions?expand=transitions.fields");
+  let json = get_json_from_url(config, query.as_str())
+    .await
+    .map_err(|e| format!("Error: failed to get the transitions of issue {issue_key} from server.\n{e}"))?;
+  get_transition_rows_from_json(&json, issue_key)
+}
+
+async fn store_transitions_of_issue_in_db(issue_key: &str, issue_id: i64, transitions: &[TransitionRow], db_conn: &Pool<Sqlite>) {
+  let mut tx = match db_conn.begin().await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("Error when starting a sql transaction to update the transitions of issue {issue_key}. Err: {e:?}");
+      return;
+    }
+  };
+
+  let res = sqlx::query("DELETE FROM IssueTransition WHERE issue_id = ?;")
+    .bind(issue_id)
+    .execute(&mut *tx)
+    .await;
+  if let Err(e) = res {
+    eprintln!("Error when removing the transitions of issue {issue_key}. Err: {e:?}");
+    return; // dropping the transaction rolls it back
+  }
+
+  let query_str =
+    "INSERT INTO IssueTransition (issue_id, transition_id, position, name, to_status_id, required_fields)
+     VALUES (?, ?, ?, ?, ?, ?);";
+  for (position, transition) in transitions.iter().enumerate() {
+    let res = sqlx::query(query_str)
+      .bind(issue_id)
+      .bind(transition.transition_id)
+      .bind(position as i64)
+      .bind(&transition.name)
+      .bind(transition.to_status_id)
+      .bind(&transition.required_fields)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when adding transition {name} of issue {issue_key}. Err: {e:?}", name = transition.name);
+      return;
+    }
+  }
+
+  if let Err(e) = tx.commit().await {
+    eprintln!("Error when committing the transitions of issue {issue_key}. Err: {e:?}");
+  }
+}
+
+// fails when the transitions couldn't be fetched from the jira server
+pub(crate) async fn add_transitions_for_issue_into_db(config: &Config, issue_key: &str, issue_id: u32, db_conn: &Pool<Sqlite>) -> Result<(), String> {
+  let transitions = get_transition_rows_from_server(config, issue_key).await?;
+  store_transitions_of_issue_in_db(issue_key, issue_id as i64, transitions.as_slice(), db_conn).await;
+  Ok(())
+}
+
+// in the order given by jira
+pub(crate) async fn get_transitions_from_db(issue_key: &str, db_conn: &Pool<Sqlite>) -> Result<Vec<Transition>, String> {
+  let query_str =
+    "SELECT IssueTransition.transition_id AS transition_id,
+            IssueTransition.name AS name,
+            IssueTransition.to_status_id AS to_status_id,
+            Status.name AS to_status,
+            IssueTransition.required_fields AS required_fields
+     FROM IssueTransition
+     JOIN Issue ON Issue.jira_id = IssueTransition.issue_id
+     LEFT JOIN Status ON Status.jira_id = IssueTransition.to_status_id
+     WHERE Issue.key = ?
+     ORDER BY IssueTransition.position ASC;";
+
+  let rows = sqlx::query_as::<_, TransitionRow>(query_str)
+    .bind(issue_key)
+    .fetch_all(db_conn)
+    .await
+    .map_err(|e| format!("Error occurred while trying to get the transitions of issue {issue_key} from local database: {e}"))?;
+  Ok(rows.into_iter().map(Transition::from).collect())
+}
+
+fn normalise_name(name: &str) -> String {
+  name.trim().to_lowercase().replace('_', " ")
+}
+
+// transitions are given by id, by name, or by the name of the status they lead to. Like field
+// names, underscores stand for spaces.
+fn find_transition(transitions: Vec<Transition>, transition: &str) -> Option<Transition> {
+  let name = normalise_name(transition);
+  let position = transitions
+    .iter()
+    .position(|x| x.id.to_string() == transition)
+    .or_else(|| transitions.iter().position(|x| normalise_name(&x.name) == name))
+    .or_else(|| transitions.iter().position(|x| x.to_status.as_deref().is_some_and(|x| normalise_name(x) == name)))?;
+  transitions.into_iter().nth(position)
+}
+
+// the cached transitions may be outdated, e.g. when the ticket changed status on the server
+// since the last synchronisation. The server is asked when the transition isn't found locally.
+// Returns None when the transition isn't available for the ticket.
+pub(crate) async fn find_transition_of_issue(config: &Config, issue_key: &str, transition: &str, db_conn: &Pool<Sqlite>) -> Result<Option<Transition>, String> {
+  let transitions_in_db = get_transitions_from_db(issue_key, db_conn).await.unwrap_or_else(|e| {
+    eprintln!("{e}");
+    Vec::new()
+  });
+  if let Some(res) = find_transition(transitions_in_db, transition) {
+    return Ok(Some(res));
+  }
+
+  let transition_rows = get_transition_rows_from_server(config, issue_key).await?;
+  if let Some(issue_id) = get_issue_id_from_db(issue_key, db_conn).await {
+    store_transitions_of_issue_in_db(issue_key, issue_id, transition_rows.as_slice(), db_conn).await;
+  }
+  let transitions = transition_rows.into_iter().map(Transition::from).collect();
+  Ok(find_transition(transitions, transition))
+}
+
+// returns the fields changed by the transition, as stored once it is done: the status, the
+// resolution and the fields given along with the transition.
+pub(crate) async fn transition_issue(config: &Config,
+                                     issue_key: &str,
+                                     transition: &Transition,
+                                     field_values: &[FieldValue],
+                                     db_conn: &Pool<Sqlite>) -> Result<Vec<KeyValueField>, String> {
+  let mut body = json!({"transition": {"id": transition.id.to_string()}});
+  if !field_values.is_empty() {
+    let fields = field_values
+      .iter()
+      .map(|x| (x.field.jira_id.clone(), x.for_jira.clone()))
+      .collect::<Map<_, _>>();
+    body["fields"] = Value::Object(fields);
+  }
+
+  // not retried on gateway errors, the ticket may have moved anyway and the transition wouldn't
+  // be available anymore.
+  let query = format!("/rest/api/3/issue/{issue_key}/transitions");
+  post_json_to_url(config, query.as_str(), &body)
+    .await
+    .map_err(|e| format!("Error: failed to transition issue {issue_key} to {name} on the server. {e}", name = transition.name))?;
+
+  let mut field_ids = vec!["status", "resolution"];
+  for field_value in field_values {
+    if !field_ids.contains(&field_value.field.jira_id.as_str()) {
+      field_ids.push(field_value.field.jira_id.as_str());
+    }
+  }
+
+  // the transitions available from the new status differ
+  let issue_id = get_issue_id_from_db(issue_key, db_conn).await;
+  let (values, _) = tokio::join!(
+    refresh_issue_fields_from_server(config, issue_key, issue_id, field_ids.as_slice(), db_conn),
+    async {
+      if let Some(issue_id) = issue_id {
+        if let Err(e) = add_transitions_for_issue_into_db(config, issue_key, issue_id as u32, db_conn).await {
+          eprintln!("{e}");
+        }
+      }
+    }
+  );
+
+  // the transition is done, the next synchronisation will catch up with the fields
+  let values = match values {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("{e}");
+      return Ok(Vec::new());
+    }
+  };
+
+  let human_names = get_fields_from_database(db_conn)
+    .await
+    .into_iter()
+    .map(|x| (x.jira_id, x.human_name))
+    .collect::<HashMap<_, _>>();
+  let res = values
+    .into_iter()
+    .map(|(field_id, value)| KeyValueField {
+      name: human_names.get(&field_id).cloned().unwrap_or_else(|| field_id.clone()),
+      key: field_id,
+      value: value.unwrap_or_else(|| Value::Null.to_string()),
+    })
+    .collect();
+  Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn get_transitions() -> Vec<Transition> {
+    vec![
+      Transition { id: 11, name: "Start progress".to_string(), to_status: Some("In Progress".to_string()), required_fields: vec![] },
+      Transition { id: 21, name: "Close".to_string(), to_status: Some("Done".to_string()), required_fields: vec!["resolution".to_string()] },
+      Transition { id: 31, name: "Reopen".to_string(), to_status: None, required_fields: vec![] },
+    ]
+  }
+
+  #[test]
+  fn find_transition_by_id() {
+    assert_eq!(find_transition(get_transitions(), "21").map(|x| x.id), Some(21));
+  }
+
+  #[test]
+  fn find_transition_by_name() {
+    assert_eq!(find_transition(get_transitions(), "Start progress").map(|x| x.id), Some(11));
+    assert_eq!(find_transition(get_transitions(), "start_PROGRESS").map(|x| x.id), Some(11));
+    assert_eq!(find_transition(get_transitions(), " reopen ").map(|x| x.id), Some(31));
+  }
+
+  #[test]
+  fn find_transition_by_target_status() {
+    assert_eq!(find_transition(get_transitions(), "In_Progress").map(|x| x.id), Some(11));
+    assert_eq!(find_transition(get_transitions(), "done").map(|x| x.id), Some(21));
+  }
+
+  #[test]
+  fn find_unknown_transition() {
+    assert_eq!(find_transition(get_transitions(), "Review"), None);
+    assert_eq!(find_transition(get_transitions(), "41"), None);
+    assert_eq!(find_transition(Vec::new(), "Done"), None);
+  }
+
+  #[test]
+  fn required_fields_of_transition_screen() {
+    let fields = json!({
+      "resolution": {"required": true, "hasDefaultValue": false},
+      "assignee": {"required": true},
+      "fixVersions": {"required": true, "hasDefaultValue": true},
+      "comment": {"required": false},
+      "labels": {}
+    });
+    assert_eq!(get_required_fields_from_json(fields.as_object()), vec!["assignee".to_string(), "resolution".to_string()]);
+  }
+
+  #[test]
+  fn required_fields_without_transition_screen() {
+    assert_eq!(get_required_fields_from_json(None), Vec::<String>::new());
+    assert_eq!(get_required_fields_from_json(json!({}).as_object()), Vec::<String>::new());
+  }
+}
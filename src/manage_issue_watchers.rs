@@ -0,0 +1,309 @@
+use std::collections::HashSet;
+use serde_json::Value;
+use sqlx::{FromRow, Pool, Sqlite};
+use crate::get_config::Config;
+use crate::get_json_from_url::get_json_from_url;
+use crate::server::Person;
+
+// Watchers and voters of a ticket are both lists of people. They are kept in the watcher and voter
+// tables, which have the same layout.
+
+#[derive(Debug, FromRow, Hash, Eq, PartialEq)]
+struct PersonRow {
+  account_id: String,
+  display_name: String,
+}
+
+fn get_person_rows_from_json(json: &Value, list_name: &str, issue_key: &str) -> Result<Vec<PersonRow>, String> {
+  let Some(people) = json.get(list_name).and_then(|x| x.as_array()) else {
+    return Err(format!("Reply for the {list_name} of issue {issue_key} doesn't contain a list of {list_name}: {json}"));
+  };
+
+  people
+    .iter()
+    .map(|person| {
+      let account_id = person.get("accountId").and_then(|x| x.as_str());
+      let Some(account_id) = account_id else {
+        return Err(format!("The {list_name} of issue {issue_key} contain a person without account id: {person}"));
+      };
+      let display_name = person
+        .get("displayName")
+        .and_then(|x| x.as_str())
+        .unwrap_or(account_id);
+      Ok(PersonRow {
+        account_id: account_id.to_string(),
+        display_name: display_name.to_string(),
+      })
+    })
+    .collect()
+}
+
+async fn get_person_rows_from_server(config: &Config, issue_key: &str, query: &str, list_name: &str) -> Result<Vec<PersonRow>, String> {
+  let json = get_json_from_url(config, query)
+    .await
+    .map_err(|e| format!("Error: failed to get the {list_name} of issue {issue_key} from server.\n{e}"))?;
+  get_person_rows_from_json(&json, list_name, issue_key)
+}
+
+async fn get_person_rows_from_db(table: &str, issue_id: u32, db_conn: &Pool<Sqlite>) -> Result<Vec<PersonRow>, String> {
+  let query_str = format!(
+    "SELECT person AS account_id, People.displayName AS display_name
+     FROM {table}
+     JOIN People ON People.accountId = {table}.person
+     WHERE Issue = ?;");
+
+  sqlx::query_as::<_, PersonRow>(query_str.as_str())
+    .bind(issue_id)
+    .fetch_all(db_conn)
+    .await
+    .map_err(|e| format!("Error occurred while trying to get the rows of table {table} for issue with id {issue_id} from local database: {e}"))
+}
+
+async fn update_people_of_issue_in_db(table: &str,
+                                      list_name: &str,
+                                      issue_key: &str,
+                                      issue_id: u32,
+                                      people_in_remote: &[PersonRow],
+                                      people_in_db: &[PersonRow],
+                                      db_conn: &Pool<Sqlite>) -> bool {
+  let people_in_db_set = people_in_db.iter().collect::<HashSet<_>>();
+  let ids_in_remote = people_in_remote.iter().map(|x| x.account_id.as_str()).collect::<HashSet<_>>();
+
+  let ids_to_remove = people_in_db
+    .iter()
+    .map(|x| x.account_id.as_str())
+    .filter(|x| !ids_in_remote.contains(x))
+    .collect::<Vec<_>>();
+  // also contains the people whose display name changed
+  let people_to_insert = people_in_remote
+    .iter()
+    .filter(|x| !people_in_db_set.contains(x))
+    .collect::<Vec<_>>();
+
+  if ids_to_remove.is_empty() && people_to_insert.is_empty() {
+    eprintln!("The {list_name} of issue {issue_key} are up to date");
+    return true;
+  }
+
+  let mut tx = match db_conn.begin().await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("Error when starting a sql transaction to update the {list_name} of issue {issue_key}. Err: {e:?}");
+      return false;
+    }
+  };
+
+  let query_str =
+    "INSERT INTO People (accountId, displayName) VALUES (?, ?)
+     ON CONFLICT(accountId) DO
+     UPDATE SET displayName = excluded.displayName;";
+  for person in &people_to_insert {
+    let res = sqlx::query(query_str)
+      .bind(&person.account_id)
+      .bind(&person.display_name)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when adding {name}, one of the {list_name} of issue {issue_key}. Err: {e:?}", name = person.display_name);
+      return false; // dropping the transaction rolls it back
+    }
+  }
+
+  let query_str = format!("DELETE FROM {table} WHERE Issue = ? AND person = ?;");
+  for account_id in &ids_to_remove {
+    let res = sqlx::query(query_str.as_str())
+      .bind(issue_id)
+      .bind(account_id)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when removing {account_id} from the {list_name} of issue {issue_key}. Err: {e:?}");
+      return false;
+    }
+  }
+
+  let query_str = format!("INSERT INTO {table} (person, Issue) VALUES (?, ?) ON CONFLICT DO NOTHING;");
+  for person in &people_to_insert {
+    let res = sqlx::query(query_str.as_str())
+      .bind(&person.account_id)
+      .bind(issue_id)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when adding {name} to the {list_name} of issue {issue_key}. Err: {e:?}", name = person.display_name);
+      return false;
+    }
+  }
+
+  match tx.commit().await {
+    Ok(_) => {
+      eprintln!("Updated the {list_name} of issue {issue_key}: {removed} removed, {inserted} added or renamed",
+                removed = ids_to_remove.len(), inserted = people_to_insert.len());
+      true
+    }
+    Err(e) => {
+      eprintln!("Error when committing the {list_name} of issue {issue_key}. Err: {e:?}");
+      false
+    }
+  }
+}
+
+async fn add_people_for_issue_into_db(config: &Config,
+                                      table: &str,
+                                      list_name: &str,
+                                      query: &str,
+                                      count_in_remote: Option<i64>,
+                                      issue_key: &str,
+                                      issue_id: u32,
+                                      db_conn: &Pool<Sqlite>) -> Result<(), String> {
+  // no need to ask the jira server for the list when the issue says it's empty
+  let people_in_remote = match count_in_remote {
+    Some(0) => { Vec::new() }
+    _ => { get_person_rows_from_server(config, issue_key, query, list_name).await? }
+  };
+
+  let people_in_db = match get_person_rows_from_db(table, issue_id, db_conn).await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("{e}");
+      return Ok(());
+    }
+  };
+
+  update_people_of_issue_in_db(table, list_name, issue_key, issue_id, people_in_remote.as_slice(), people_in_db.as_slice(), db_conn).await;
+  Ok(())
+}
+
+// fails when the watchers couldn't be fetched from the jira server
+pub(crate) async fn add_watchers_and_voters_for_issue_into_db(config: &Config,
+                                                              issue_key: &str,
+                                                              issue_id: u32,
+                                                              json_of_issue: &Value,
+                                                              db_conn: &Pool<Sqlite>) -> Result<(), String> {
+  // the issue only gives the number of watchers and voters, like
+  // "watches": {"watchCount": 2, "isWatching": false}, "votes": {"votes": 0, "hasVoted": false}
+  let get_count = |field: &str, count: &str| {
+    json_of_issue
+      .get("fields")
+      .and_then(|x| x.get(field))
+      .and_then(|x| x.get(count))
+      .and_then(|x| x.as_i64())
+  };
+  let watchers_query = format!("/rest/api/3/issue/{issue_key}/watchers");
+  let voters_query = format!("/rest/api/3/issue/{issue_key}/votes");
+  let (watchers, voters) = tokio::join!(
+    add_people_for_issue_into_db(config, "watcher", "watchers", watchers_query.as_str(), get_count("watches", "watchCount"), issue_key, issue_id, db_conn),
+    add_people_for_issue_into_db(config, "voter", "voters", voters_query.as_str(), get_count("votes", "votes"), issue_key, issue_id, db_conn)
+  );
+  // fails when voting is disabled on the jira server, the voters are then left as they are
+  if let Err(e) = voters {
+    eprintln!("{e}");
+  }
+  watchers
+}
+
+// remembers who we are, to know which tickets we watch
+pub(crate) async fn update_current_user_in_db(config: &Config, db_conn: &Pool<Sqlite>) {
+  let json = match get_json_from_url(config, "/rest/api/3/myself").await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("Error: failed to get the current user from server.\n{e}");
+      return;
+    }
+  };
+  let account_id = json.get("accountId").and_then(|x| x.as_str());
+  let Some(account_id) = account_id else {
+    eprintln!("Current user doesn't have an account id: {json}");
+    return;
+  };
+  let display_name = json
+    .get("displayName")
+    .and_then(|x| x.as_str())
+    .unwrap_or(account_id);
+
+  let mut tx = match db_conn.begin().await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("Error when starting a sql transaction to update the current user. Err: {e:?}");
+      return;
+    }
+  };
+
+  let res = sqlx::query(
+    "INSERT INTO People (accountId, displayName) VALUES (?, ?)
+     ON CONFLICT(accountId) DO
+     UPDATE SET displayName = excluded.displayName;")
+    .bind(account_id)
+    .bind(display_name)
+    .execute(&mut *tx)
+    .await;
+  if let Err(e) = res {
+    eprintln!("Error when adding the current user {display_name}. Err: {e:?}");
+    return; // dropping the transaction rolls it back
+  }
+
+  let res = sqlx::query(
+    "INSERT INTO CurrentUser (id, account_id) VALUES (0, ?)
+     ON CONFLICT DO
+     UPDATE SET account_id = excluded.account_id;")
+    .bind(account_id)
+    .execute(&mut *tx)
+    .await;
+  if let Err(e) = res {
+    eprintln!("Error when setting the current user to {display_name}. Err: {e:?}");
+    return;
+  }
+
+  if let Err(e) = tx.commit().await {
+    eprintln!("Error when committing the current user. Err: {e:?}");
+  }
+}
+
+#[derive(FromRow)]
+struct KeyInDb {
+  key: String,
+}
+
+// empty until the current user was fetched from the jira server once
+pub(crate) async fn get_watched_tickets_from_db(db_conn: &Pool<Sqlite>) -> Result<Vec<String>, String> {
+  let query_str =
+    "SELECT Issue.key AS key
+     FROM watcher
+     JOIN CurrentUser ON CurrentUser.account_id = watcher.person
+     JOIN Issue ON Issue.jira_id = watcher.Issue
+     ORDER BY Issue.jira_id ASC;";
+
+  sqlx::query_as::<_, KeyInDb>(query_str)
+    .fetch_all(db_conn)
+    .await
+    .map(|rows| rows.into_iter().map(|x| x.key).collect())
+    .map_err(|e| format!("Error occurred while trying to get the watched tickets from local database: {e}"))
+}
+
+async fn get_people_of_ticket_from_db(table: &str, issue_key: &str, db_conn: &Pool<Sqlite>) -> Result<Vec<Person>, String> {
+  let query_str = format!(
+    "SELECT person AS account_id, People.displayName AS display_name
+     FROM {table}
+     JOIN People ON People.accountId = {table}.person
+     JOIN Issue ON Issue.jira_id = {table}.Issue
+     WHERE Issue.key = ?
+     ORDER BY People.displayName ASC;");
+
+  sqlx::query_as::<_, PersonRow>(query_str.as_str())
+    .bind(issue_key)
+    .fetch_all(db_conn)
+    .await
+    .map(|rows| rows
+      .into_iter()
+      .map(|x| Person { account_id: x.account_id, display_name: x.display_name })
+      .collect())
+    .map_err(|e| format!("Error occurred while trying to get the rows of table {table} for issue {issue_key} from local database: {e}"))
+}
+
+pub(crate) async fn get_watchers_of_ticket_from_db(issue_key: &str, db_conn: &Pool<Sqlite>) -> Result<Vec<Person>, String> {
+  get_people_of_ticket_from_db("watcher", issue_key, db_conn).await
+}
+
+pub(crate) async fn get_voters_of_ticket_from_db(issue_key: &str, db_conn: &Pool<Sqlite>) -> Result<Vec<Person>, String> {
+  get_people_of_ticket_from_db("voter", issue_key, db_conn).await
+}
@@ -0,0 +1,297 @@
+use std::collections::HashSet;
+use serde_json::Value;
+use sqlx::{FromRow, Pool, Sqlite};
+use crate::get_config::Config;
+use crate::get_json_from_url::get_json_from_url;
+use crate::server::LoggedTime;
+
+const MAX_WORKLOGS_PER_PAGE: i64 = 1000;
+
+#[derive(Debug, FromRow, Hash, Eq, PartialEq)]
+struct WorklogRow {
+  id: i64,
+  author_account_id: Option<String>,
+  author_display_name: Option<String>,
+  started: String, // as given by jira, like 2024-01-31T09:00:00.000+0100
+  time_spent_seconds: i64,
+  comment_data: Option<String>, // atlassian document format
+  creation_time: String,
+  last_modification_time: String,
+}
+
+fn get_worklog_row_from_json(worklog: &Value, issue_key: &str) -> Result<WorklogRow, String> {
+  let id = worklog
+    .get("id")
+    .and_then(|x| x.as_str())
+    .and_then(|x| x.parse::<i64>().ok());
+  let started = worklog
+    .get("started")
+    .and_then(|x| x.as_str());
+  let time_spent_seconds = worklog
+    .get("timeSpentSeconds")
+    .and_then(|x| x.as_i64());
+  let creation_time = worklog
+    .get("created")
+    .and_then(|x| x.as_str());
+  let last_modification_time = worklog
+    .get("updated")
+    .and_then(|x| x.as_str());
+  let (Some(id), Some(started), Some(time_spent_seconds), Some(creation_time), Some(last_modification_time)) =
+    (id, started, time_spent_seconds, creation_time, last_modification_time) else {
+    return Err(format!("Worklogs of issue {issue_key} contain an invalid worklog: {worklog}"));
+  };
+
+  let author = worklog.get("author");
+  let author_account_id = author
+    .and_then(|x| x.get("accountId"))
+    .and_then(|x| x.as_str())
+    .map(|x| x.to_string());
+  let author_display_name = author
+    .and_then(|x| x.get("displayName"))
+    .and_then(|x| x.as_str())
+    .map(|x| x.to_string());
+  let comment_data = worklog
+    .get("comment")
+    .map(|x| x.to_string());
+
+  Ok(WorklogRow {
+    id,
+    author_account_id,
+    author_display_name,
+    started: started.to_string(),
+    time_spent_seconds,
+    comment_data,
+    creation_time: creation_time.to_string(),
+    last_modification_time: last_modification_time.to_string(),
+  })
+}
+
+async fn get_worklog_rows_from_server(config: &Config, issue_key: &str) -> Result<Vec<WorklogRow>, String> {
+  let mut res = Vec::new();
+  let mut start = 0;
+  loop {
+    let query = format!("/rest/api/3/issue/{issue_key}/worklog?startAt={start}&maxResults={MAX_WORKLOGS_PER_PAGE}");
+    let json = match get_json_from_url(config, query.as_str()).await {
+      Ok(v) => { v }
+      Err(e) => { return Err(format!("Error: failed to get the worklogs of issue {issue_key} from server.\n{e}")) }
+    };
+
+    let Some(worklogs) = json.get("worklogs").and_then(|x| x.as_array()) else {
+      return Err(format!("Worklogs of issue {issue_key} don't contain a list of worklogs"));
+    };
+    for worklog in worklogs {
+      res.push(get_worklog_row_from_json(worklog, issue_key)?);
+    }
+
+    start += worklogs.len() as i64;
+    let total = json
+      .get("total")
+      .and_then(|x| x.as_i64())
+      .unwrap_or(0);
+    if worklogs.is_empty() || start >= total {
+      return Ok(res);
+    }
+  }
+}
+
+// the worklog field of the issue holds the first page of worklogs, which is enough for most
+// tickets. The worklog endpoint is only called when there are more.
+async fn get_worklog_rows(config: &Config, issue_key: &str, json_of_issue: &Value) -> Result<Vec<WorklogRow>, String> {
+  let worklog = json_of_issue
+    .get("fields")
+    .and_then(|x| x.get("worklog"));
+  let worklogs = worklog
+    .and_then(|x| x.get("worklogs"))
+    .and_then(|x| x.as_array());
+  let total = worklog
+    .and_then(|x| x.get("total"))
+    .and_then(|x| x.as_i64());
+  match (worklogs, total) {
+    (Some(worklogs), Some(total)) if worklogs.len() as i64 >= total => {
+      worklogs
+        .iter()
+        .map(|x| get_worklog_row_from_json(x, issue_key))
+        .collect()
+    }
+    _ => { get_worklog_rows_from_server(config, issue_key).await }
+  }
+}
+
+async fn get_worklog_rows_from_db(issue_id: u32, db_conn: &Pool<Sqlite>) -> Result<Vec<WorklogRow>, String> {
+  let query_str =
+    "SELECT id, author AS author_account_id, People.displayName AS author_display_name, started,
+            time_spent_seconds, comment_data, creation_time, last_modification_time
+     FROM Worklog
+     LEFT JOIN People ON People.accountId = Worklog.author
+     WHERE issue_id = ?;";
+
+  sqlx::query_as::<_, WorklogRow>(query_str)
+    .bind(issue_id)
+    .fetch_all(db_conn)
+    .await
+    .map_err(|e| format!("Error occurred while trying to get the worklogs of issue with id {issue_id} from local database: {e}"))
+}
+
+async fn update_worklogs_in_db(issue_key: &str,
+                               issue_id: u32,
+                               worklogs_in_remote: &[WorklogRow],
+                               worklogs_in_db: &[WorklogRow],
+                               db_conn: &Pool<Sqlite>) -> bool {
+  let worklogs_in_db_set = worklogs_in_db.iter().collect::<HashSet<_>>();
+  let ids_in_remote = worklogs_in_remote.iter().map(|x| x.id).collect::<HashSet<_>>();
+
+  // edited worklogs are updated in place, only the deleted ones are removed
+  let ids_to_remove = worklogs_in_db
+    .iter()
+    .filter(|x| !ids_in_remote.contains(&x.id))
+    .map(|x| x.id)
+    .collect::<Vec<_>>();
+  let worklogs_to_insert = worklogs_in_remote
+    .iter()
+    .filter(|x| !worklogs_in_db_set.contains(x))
+    .collect::<Vec<_>>();
+
+  if ids_to_remove.is_empty() && worklogs_to_insert.is_empty() {
+    eprintln!("Worklogs of issue {issue_key} are up to date");
+    return true;
+  }
+
+  let mut tx = match db_conn.begin().await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("Error when starting a sql transaction to update the worklogs of issue {issue_key}. Err: {e:?}");
+      return false;
+    }
+  };
+
+  // authors first, since the worklogs reference them as a foreign key
+  let query_str =
+    "INSERT INTO People (accountId, displayName) VALUES (?, ?)
+     ON CONFLICT(accountId) DO
+     UPDATE SET displayName = excluded.displayName;";
+  let authors = worklogs_to_insert
+    .iter()
+    .filter_map(|x| x.author_account_id.as_ref().map(|id| (id, x.author_display_name.as_ref().unwrap_or(id))))
+    .collect::<HashSet<_>>();
+  for (account_id, display_name) in authors {
+    let res = sqlx::query(query_str)
+      .bind(account_id)
+      .bind(display_name)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when adding author {display_name} of worklogs of issue {issue_key}. Err: {e:?}");
+      return false; // dropping the transaction rolls it back
+    }
+  }
+
+  let query_str = "DELETE FROM Worklog WHERE id = ?;";
+  for id in &ids_to_remove {
+    let res = sqlx::query(query_str)
+      .bind(id)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when removing worklog {id} of issue {issue_key}. Err: {e:?}");
+      return false;
+    }
+  }
+
+  let query_str =
+    "INSERT INTO Worklog (id, issue_id, author, started, time_spent_seconds, comment_data, creation_time, last_modification_time)
+     VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+     ON CONFLICT DO
+     UPDATE SET issue_id = excluded.issue_id,
+                author = excluded.author,
+                started = excluded.started,
+                time_spent_seconds = excluded.time_spent_seconds,
+                comment_data = excluded.comment_data,
+                creation_time = excluded.creation_time,
+                last_modification_time = excluded.last_modification_time;";
+  for worklog in &worklogs_to_insert {
+    let res = sqlx::query(query_str)
+      .bind(worklog.id)
+      .bind(issue_id)
+      .bind(&worklog.author_account_id)
+      .bind(&worklog.started)
+      .bind(worklog.time_spent_seconds)
+      .bind(&worklog.comment_data)
+      .bind(&worklog.creation_time)
+      .bind(&worklog.last_modification_time)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when adding worklog {id} of issue {issue_key}. Err: {e:?}", id = worklog.id);
+      return false;
+    }
+  }
+
+  match tx.commit().await {
+    Ok(_) => {
+      eprintln!("Updated worklogs of issue {issue_key}: {removed} removed, {inserted} added or edited",
+                removed = ids_to_remove.len(), inserted = worklogs_to_insert.len());
+      true
+    }
+    Err(e) => {
+      eprintln!("Error when committing the worklogs of issue {issue_key}. Err: {e:?}");
+      false
+    }
+  }
+}
+
+// fails when the worklogs couldn't be fetched from the jira server
+pub(crate) async fn add_worklogs_for_issue_into_db(config: &Config,
+                                                   issue_key: &str,
+                                                   issue_id: u32,
+                                                   json_of_issue: &Value,
+                                                   db_conn: &Pool<Sqlite>) -> Result<(), String> {
+  let worklogs_in_remote = get_worklog_rows(config, issue_key, json_of_issue).await?;
+
+  let worklogs_in_db = match get_worklog_rows_from_db(issue_id, db_conn).await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("{e}");
+      return Ok(());
+    }
+  };
+
+  update_worklogs_in_db(issue_key, issue_id, worklogs_in_remote.as_slice(), worklogs_in_db.as_slice(), db_conn).await;
+  Ok(())
+}
+
+#[derive(FromRow)]
+struct LoggedTimeRow {
+  author: String,
+  issue_key: String,
+  day: String,
+  seconds: i64,
+}
+
+// days are like 2024-01-31, both included. The day of a worklog is the one of its start, in the
+// timezone of the person who logged it.
+pub(crate) async fn get_logged_time_from_db(first_day: &str, last_day: &str, db_conn: &Pool<Sqlite>) -> Result<Vec<LoggedTime>, String> {
+  let query_str =
+    "SELECT COALESCE(People.displayName, Worklog.author, '') AS author,
+            Issue.key AS issue_key,
+            substr(Worklog.started, 1, 10) AS day,
+            SUM(Worklog.time_spent_seconds) AS seconds
+     FROM Worklog
+     JOIN Issue ON Issue.jira_id = Worklog.issue_id
+     LEFT JOIN People ON People.accountId = Worklog.author
+     WHERE substr(Worklog.started, 1, 10) BETWEEN ? AND ?
+     GROUP BY author, issue_key, day
+     ORDER BY author ASC, day ASC, issue_key ASC;";
+
+  let rows = sqlx::query_as::<_, LoggedTimeRow>(query_str)
+    .bind(first_day)
+    .bind(last_day)
+    .fetch_all(db_conn)
+    .await
+    .map_err(|e| format!("Error occurred while summing the time logged between {first_day} and {last_day} in the local database: {e}"))?;
+
+  let res = rows
+    .into_iter()
+    .map(|x| LoggedTime { author: x.author, key: x.issue_key, day: x.day, seconds: x.seconds })
+    .collect();
+  Ok(res)
+}
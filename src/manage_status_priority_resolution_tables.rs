@@ -0,0 +1,377 @@
+use std::collections::HashSet;
+use serde_json::Value;
+use sqlx::{FromRow, Pool, Sqlite};
+use crate::get_config::Config;
+use crate::get_json_from_url::get_json_from_url;
+use crate::server::TicketStatus;
+
+// Catalogues of the statuses, priorities and resolutions defined on the jira server. Each ticket
+// references its current ones by id in the Issue table, so filtering tickets on them doesn't
+// require parsing the json of their fields.
+
+#[derive(Debug, FromRow, Hash, Eq, PartialEq)]
+struct StatusRow {
+  jira_id: i64,
+  name: String,
+  description: Option<String>,
+  category_key: String,
+  category_name: String,
+}
+
+#[derive(Debug, FromRow, Hash, Eq, PartialEq)]
+struct PriorityRow {
+  jira_id: i64,
+  position: i64,
+  name: String,
+  description: Option<String>,
+}
+
+#[derive(Debug, FromRow, Hash, Eq, PartialEq)]
+struct ResolutionRow {
+  jira_id: i64,
+  name: String,
+  description: Option<String>,
+}
+
+fn get_id_from_json(json: &Value) -> Option<i64> {
+  json
+    .get("id")
+    .and_then(|x| x.as_str())
+    .and_then(|x| x.parse::<i64>().ok())
+}
+
+fn get_optional_string_from_json(json: &Value, name: &str) -> Option<String> {
+  json
+    .get(name)
+    .and_then(|x| x.as_str())
+    .map(|x| x.to_string())
+}
+
+async fn get_json_array_from_server(config: &Config, query: &str) -> Result<Vec<Value>, String> {
+  let json = get_json_from_url(config, query)
+    .await
+    .map_err(|e| format!("Error: failed to get {query} from server.\n{e}"))?;
+
+  match json {
+    Value::Array(values) => { Ok(values) }
+    _ => { Err(format!("Reply to {query} isn't a json array: {json}")) }
+  }
+}
+
+async fn get_status_rows_from_server(config: &Config) -> Result<Vec<StatusRow>, String> {
+  get_json_array_from_server(config, "/rest/api/2/status")
+    .await?
+    .iter()
+    .map(|status| {
+      let jira_id = get_id_from_json(status);
+      let name = status.get("name").and_then(|x| x.as_str());
+      let category = status.get("statusCategory");
+      let category_key = category.and_then(|x| x.get("key")).and_then(|x| x.as_str());
+      let category_name = category.and_then(|x| x.get("name")).and_then(|x| x.as_str());
+      let (Some(jira_id), Some(name), Some(category_key), Some(category_name)) = (jira_id, name, category_key, category_name) else {
+        return Err(format!("Statuses contain an invalid status: {status}"));
+      };
+      Ok(StatusRow {
+        jira_id,
+        name: name.to_string(),
+        description: get_optional_string_from_json(status, "description"),
+        category_key: category_key.to_string(),
+        category_name: category_name.to_string(),
+      })
+    })
+    .collect()
+}
+
+async fn get_priority_rows_from_server(config: &Config) -> Result<Vec<PriorityRow>, String> {
+  // jira lists the priorities from the highest to the lowest
+  get_json_array_from_server(config, "/rest/api/2/priority")
+    .await?
+    .iter()
+    .enumerate()
+    .map(|(position, priority)| {
+      let jira_id = get_id_from_json(priority);
+      let name = priority.get("name").and_then(|x| x.as_str());
+      let (Some(jira_id), Some(name)) = (jira_id, name) else {
+        return Err(format!("Priorities contain an invalid priority: {priority}"));
+      };
+      Ok(PriorityRow {
+        jira_id,
+        position: position as i64,
+        name: name.to_string(),
+        description: get_optional_string_from_json(priority, "description"),
+      })
+    })
+    .collect()
+}
+
+async fn get_resolution_rows_from_server(config: &Config) -> Result<Vec<ResolutionRow>, String> {
+  get_json_array_from_server(config, "/rest/api/2/resolution")
+    .await?
+    .iter()
+    .map(|resolution| {
+      let jira_id = get_id_from_json(resolution);
+      let name = resolution.get("name").and_then(|x| x.as_str());
+      let (Some(jira_id), Some(name)) = (jira_id, name) else {
+        return Err(format!("Resolutions contain an invalid resolution: {resolution}"));
+      };
+      Ok(ResolutionRow {
+        jira_id,
+        name: name.to_string(),
+        description: get_optional_string_from_json(resolution, "description"),
+      })
+    })
+    .collect()
+}
+
+async fn get_rows_from_db<T>(query_str: &str, db_conn: &Pool<Sqlite>) -> Result<Vec<T>, String>
+where
+  T: for<'r> FromRow<'r, sqlx::sqlite::SqliteRow> + Send + Unpin,
+{
+  sqlx::query_as::<_, T>(query_str)
+    .fetch_all(db_conn)
+    .await
+    .map_err(|e| format!("Error occurred while trying to run [{query_str}] on the local database: {e}"))
+}
+
+// ids of the rows in db but not in remote, and rows in remote which are new or changed
+fn get_changes<'a, T: Eq + std::hash::Hash>(rows_in_remote: &'a [T],
+                                            rows_in_db: &'a [T],
+                                            get_id: impl Fn(&T) -> i64) -> (Vec<i64>, Vec<&'a T>) {
+  let rows_in_db_set = rows_in_db.iter().collect::<HashSet<_>>();
+  let ids_in_remote = rows_in_remote.iter().map(&get_id).collect::<HashSet<_>>();
+
+  let ids_to_remove = rows_in_db
+    .iter()
+    .map(&get_id)
+    .filter(|x| !ids_in_remote.contains(x))
+    .collect::<Vec<_>>();
+  let rows_to_insert = rows_in_remote
+    .iter()
+    .filter(|x| !rows_in_db_set.contains(x))
+    .collect::<Vec<_>>();
+  (ids_to_remove, rows_to_insert)
+}
+
+async fn update_statuses_in_db(config: &Config, db_conn: &Pool<Sqlite>) -> Result<(), String> {
+  let rows_in_remote = get_status_rows_from_server(config).await?;
+  let rows_in_db = get_rows_from_db::<StatusRow>(
+    "SELECT jira_id, name, description, category_key, category_name FROM Status;", db_conn).await?;
+  let (ids_to_remove, rows_to_insert) = get_changes(rows_in_remote.as_slice(), rows_in_db.as_slice(), |x| x.jira_id);
+  if ids_to_remove.is_empty() && rows_to_insert.is_empty() {
+    eprintln!("Statuses are up to date");
+    return Ok(());
+  }
+
+  let mut tx = db_conn
+    .begin()
+    .await
+    .map_err(|e| format!("Error when starting a sql transaction to update the statuses. Err: {e:?}"))?;
+
+  for id in &ids_to_remove {
+    sqlx::query("DELETE FROM Status WHERE jira_id = ?;")
+      .bind(id)
+      .execute(&mut *tx)
+      .await
+      .map_err(|e| format!("Error when removing status {id}. Err: {e:?}"))?; // dropping the transaction rolls it back
+  }
+
+  let query_str =
+    "INSERT INTO Status (jira_id, name, description, category_key, category_name)
+     VALUES (?, ?, ?, ?, ?)
+     ON CONFLICT DO
+     UPDATE SET name = excluded.name,
+                description = excluded.description,
+                category_key = excluded.category_key,
+                category_name = excluded.category_name;";
+  for row in &rows_to_insert {
+    sqlx::query(query_str)
+      .bind(row.jira_id)
+      .bind(&row.name)
+      .bind(&row.description)
+      .bind(&row.category_key)
+      .bind(&row.category_name)
+      .execute(&mut *tx)
+      .await
+      .map_err(|e| format!("Error when adding status {name}. Err: {e:?}", name = row.name))?;
+  }
+
+  tx.commit()
+    .await
+    .map_err(|e| format!("Error when committing the statuses. Err: {e:?}"))?;
+  eprintln!("Updated statuses: {removed} removed, {inserted} added or edited",
+            removed = ids_to_remove.len(), inserted = rows_to_insert.len());
+  Ok(())
+}
+
+async fn update_priorities_in_db(config: &Config, db_conn: &Pool<Sqlite>) -> Result<(), String> {
+  let rows_in_remote = get_priority_rows_from_server(config).await?;
+  let rows_in_db = get_rows_from_db::<PriorityRow>(
+    "SELECT jira_id, position, name, description FROM Priority;", db_conn).await?;
+  let (ids_to_remove, rows_to_insert) = get_changes(rows_in_remote.as_slice(), rows_in_db.as_slice(), |x| x.jira_id);
+  if ids_to_remove.is_empty() && rows_to_insert.is_empty() {
+    eprintln!("Priorities are up to date");
+    return Ok(());
+  }
+
+  let mut tx = db_conn
+    .begin()
+    .await
+    .map_err(|e| format!("Error when starting a sql transaction to update the priorities. Err: {e:?}"))?;
+
+  for id in &ids_to_remove {
+    sqlx::query("DELETE FROM Priority WHERE jira_id = ?;")
+      .bind(id)
+      .execute(&mut *tx)
+      .await
+      .map_err(|e| format!("Error when removing priority {id}. Err: {e:?}"))?; // dropping the transaction rolls it back
+  }
+
+  let query_str =
+    "INSERT INTO Priority (jira_id, position, name, description)
+     VALUES (?, ?, ?, ?)
+     ON CONFLICT DO
+     UPDATE SET position = excluded.position,
+                name = excluded.name,
+                description = excluded.description;";
+  for row in &rows_to_insert {
+    sqlx::query(query_str)
+      .bind(row.jira_id)
+      .bind(row.position)
+      .bind(&row.name)
+      .bind(&row.description)
+      .execute(&mut *tx)
+      .await
+      .map_err(|e| format!("Error when adding priority {name}. Err: {e:?}", name = row.name))?;
+  }
+
+  tx.commit()
+    .await
+    .map_err(|e| format!("Error when committing the priorities. Err: {e:?}"))?;
+  eprintln!("Updated priorities: {removed} removed, {inserted} added or edited",
+            removed = ids_to_remove.len(), inserted = rows_to_insert.len());
+  Ok(())
+}
+
+async fn update_resolutions_in_db(config: &Config, db_conn: &Pool<Sqlite>) -> Result<(), String> {
+  let rows_in_remote = get_resolution_rows_from_server(config).await?;
+  let rows_in_db = get_rows_from_db::<ResolutionRow>(
+    "SELECT jira_id, name, description FROM Resolution;", db_conn).await?;
+  let (ids_to_remove, rows_to_insert) = get_changes(rows_in_remote.as_slice(), rows_in_db.as_slice(), |x| x.jira_id);
+  if ids_to_remove.is_empty() && rows_to_insert.is_empty() {
+    eprintln!("Resolutions are up to date");
+    return Ok(());
+  }
+
+  let mut tx = db_conn
+    .begin()
+    .await
+    .map_err(|e| format!("Error when starting a sql transaction to update the resolutions. Err: {e:?}"))?;
+
+  for id in &ids_to_remove {
+    sqlx::query("DELETE FROM Resolution WHERE jira_id = ?;")
+      .bind(id)
+      .execute(&mut *tx)
+      .await
+      .map_err(|e| format!("Error when removing resolution {id}. Err: {e:?}"))?; // dropping the transaction rolls it back
+  }
+
+  let query_str =
+    "INSERT INTO Resolution (jira_id, name, description)
+     VALUES (?, ?, ?)
+     ON CONFLICT DO
+     UPDATE SET name = excluded.name,
+                description = excluded.description;";
+  for row in &rows_to_insert {
+    sqlx::query(query_str)
+      .bind(row.jira_id)
+      .bind(&row.name)
+      .bind(&row.description)
+      .execute(&mut *tx)
+      .await
+      .map_err(|e| format!("Error when adding resolution {name}. Err: {e:?}", name = row.name))?;
+  }
+
+  tx.commit()
+    .await
+    .map_err(|e| format!("Error when committing the resolutions. Err: {e:?}"))?;
+  eprintln!("Updated resolutions: {removed} removed, {inserted} added or edited",
+            removed = ids_to_remove.len(), inserted = rows_to_insert.len());
+  Ok(())
+}
+
+pub(crate) async fn update_statuses_priorities_and_resolutions_in_db(config: &Config, db_conn: &Pool<Sqlite>) {
+  let (statuses, priorities, resolutions) = tokio::join!(
+    update_statuses_in_db(config, db_conn),
+    update_priorities_in_db(config, db_conn),
+    update_resolutions_in_db(config, db_conn)
+  );
+
+  for res in [statuses, priorities, resolutions] {
+    if let Err(e) = res {
+      eprintln!("{e}");
+    }
+  }
+}
+
+// Stores the ids of the current status, priority and resolution of the ticket in the Issue table
+pub(crate) async fn update_status_priority_and_resolution_of_issue_in_db(issue_key: &str, issue_id: u32, json_data: &Value, db_conn: &Pool<Sqlite>) {
+  let get_field_id = |name: &str| {
+    json_data
+      .get("fields")
+      .and_then(|x| x.get(name))
+      .and_then(get_id_from_json)
+  };
+
+  let query_str =
+    "UPDATE Issue
+     SET status_id = ?, priority_id = ?, resolution_id = ?
+     WHERE jira_id = ?;";
+  let res = sqlx::query(query_str)
+    .bind(get_field_id("status"))
+    .bind(get_field_id("priority"))
+    .bind(get_field_id("resolution"))
+    .bind(issue_id)
+    .execute(db_conn)
+    .await;
+
+  if let Err(e) = res {
+    eprintln!("Error when updating the status, priority and resolution of issue {issue_key}. Err: {e:?}");
+  }
+}
+
+#[derive(FromRow)]
+struct TicketStatusRow {
+  key: String,
+  status: Option<String>,
+  status_category: Option<String>,
+  priority: Option<String>,
+  resolution: Option<String>,
+}
+
+// tickets whose status, priority or resolution isn't in the catalogues yet have them set to none
+pub(crate) async fn get_ticket_statuses_from_db(db_conn: &Pool<Sqlite>) -> Result<Vec<TicketStatus>, String> {
+  let query_str =
+    "SELECT Issue.key AS key,
+            Status.name AS status,
+            Status.category_key AS status_category,
+            Priority.name AS priority,
+            Resolution.name AS resolution
+     FROM Issue
+     LEFT JOIN Status ON Status.jira_id = Issue.status_id
+     LEFT JOIN Priority ON Priority.jira_id = Issue.priority_id
+     LEFT JOIN Resolution ON Resolution.jira_id = Issue.resolution_id
+     ORDER BY Issue.jira_id ASC;";
+
+  let rows = get_rows_from_db::<TicketStatusRow>(query_str, db_conn).await?;
+  let res = rows
+    .into_iter()
+    .map(|x| TicketStatus {
+      key: x.key,
+      status: x.status,
+      status_category: x.status_category,
+      priority: x.priority,
+      resolution: x.resolution,
+    })
+    .collect();
+  Ok(res)
+}
@@ -0,0 +1,190 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use sqlx::{FromRow, Pool, Sqlite};
+
+// seconds since the unix epoch, as measured by the local clock.
+pub(crate) fn get_current_timestamp() -> i64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|x| x.as_secs() as i64)
+    .unwrap_or(0)
+}
+
+#[derive(FromRow)]
+struct LastSynchronisation {
+  last_synchronisation: i64,
+}
+
+pub(crate) async fn get_last_synchronisation_of_project(project_key: &str, db_conn: &Pool<Sqlite>) -> Option<i64> {
+  let query_str =
+    "SELECT last_synchronisation
+     FROM ProjectSynchronisation
+     WHERE project_key = ?;";
+
+  let row = sqlx::query_as::<_, LastSynchronisation>(query_str)
+    .bind(project_key)
+    .fetch_optional(db_conn)
+    .await;
+
+  match row {
+    Ok(row) => { row.map(|x| x.last_synchronisation) }
+    Err(e) => {
+      eprintln!("Error occurred while trying to get the last synchronisation time of project {project_key} from the local database. Err: {e:?}");
+      None
+    }
+  }
+}
+
+// timestamp must be taken before querying the jira server, so that tickets updated while
+// synchronising get picked up by the next synchronisation.
+pub(crate) async fn set_last_synchronisation_of_project(project_key: &str, timestamp: i64, db_conn: &Pool<Sqlite>) {
+  let query_str =
+    "INSERT INTO ProjectSynchronisation (project_key, last_synchronisation) VALUES (?, ?)
+     ON CONFLICT(project_key) DO UPDATE SET last_synchronisation = excluded.last_synchronisation;";
+
+  let res = sqlx::query(query_str)
+    .bind(project_key)
+    .bind(timestamp)
+    .execute(db_conn)
+    .await;
+
+  if let Err(e) = res {
+    eprintln!("Error occurred while trying to save the last synchronisation time of project {project_key} in the local database. Err: {e:?}");
+  }
+}
+
+// jira dates are like 2024-01-31T10:00:00.000+0100
+pub(crate) fn get_timestamp_of_jira_date(date: &str) -> Option<i64> {
+  let (date, time_and_offset) = date.split_once('T')?;
+  let mut date = date.split('-').map(|x| x.parse::<i64>().ok());
+  let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+  if date.next().is_some() || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+    return None;
+  }
+
+  let offset_start = time_and_offset.find(['+', '-', 'Z'])?;
+  let (time, offset) = time_and_offset.split_at(offset_start);
+  let mut time = time.split('.').next()?.split(':').map(|x| x.parse::<i64>().ok());
+  let (hours, minutes, seconds) = (time.next()??, time.next()??, time.next()??);
+  if time.next().is_some() {
+    return None;
+  }
+
+  let offset_in_seconds = match offset.split_at(1) {
+    ("Z", "") => { 0 }
+    (sign, offset) => {
+      let offset = offset.replace(':', "");
+      if offset.len() != 4 {
+        return None;
+      }
+      let offset_hours = offset[..2].parse::<i64>().ok()?;
+      let offset_minutes = offset[2..].parse::<i64>().ok()?;
+      let offset_in_seconds = offset_hours * 3600 + offset_minutes * 60;
+      if sign == "-" { -offset_in_seconds } else { offset_in_seconds }
+    }
+  };
+
+  // days since the epoch, see http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+  let year = if month <= 2 { year - 1 } else { year };
+  let era = year.div_euclid(400);
+  let year_of_era = year - era * 400;
+  let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+  let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+  let days = era * 146097 + day_of_era - 719468;
+
+  Some(days * 86400 + hours * 3600 + minutes * 60 + seconds - offset_in_seconds)
+}
+
+#[derive(FromRow)]
+struct LastUpdate {
+  field_value: String,
+}
+
+// the details of the tickets which failed to be fetched (server unreachable, rate limited,
+// offline mode...) must be fetched by the next synchronisation. The high-water mark then only
+// moves up to the oldest update of these tickets, and their last update is forgotten so that they
+// aren't found to be up to date.
+pub(crate) async fn finish_synchronisation_of_project(project_key: &str,
+                                                      synchronisation_start: i64,
+                                                      failed_keys: &[&str],
+                                                      db_conn: &Pool<Sqlite>) {
+  let query_str =
+    "SELECT IssueField.field_value AS field_value
+     FROM IssueField
+     JOIN Issue ON Issue.jira_id = IssueField.issue_id
+     WHERE Issue.key = ? AND IssueField.field_id = 'updated';";
+
+  let mut timestamp = Some(synchronisation_start);
+  for issue_key in failed_keys {
+    let last_update = sqlx::query_as::<_, LastUpdate>(query_str)
+      .bind(issue_key)
+      .fetch_optional(db_conn)
+      .await
+      .ok()
+      .flatten()
+      .and_then(|x| serde_json::from_str::<String>(x.field_value.as_str()).ok())
+      .and_then(|x| get_timestamp_of_jira_date(x.as_str()));
+    timestamp = match (timestamp, last_update) {
+      (Some(timestamp), Some(last_update)) => { Some(timestamp.min(last_update)) }
+      _ => { None }
+    };
+
+    let res = sqlx::query("DELETE FROM IssueField WHERE field_id = 'updated' AND issue_id IN (SELECT jira_id FROM Issue WHERE key = ?);")
+      .bind(issue_key)
+      .execute(db_conn)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error occurred while trying to forget the last update of issue {issue_key}. Err: {e:?}");
+    }
+  }
+
+  match timestamp {
+    Some(timestamp) => {
+      if !failed_keys.is_empty() {
+        eprintln!("Failed to fetch the details of {n} tickets of project {project_key}, they will be fetched again", n = failed_keys.len());
+      }
+      set_last_synchronisation_of_project(project_key, timestamp, db_conn).await;
+    }
+    None => {
+      eprintln!("Failed to fetch the details of {n} tickets of project {project_key}, not moving its last synchronisation time", n = failed_keys.len());
+    }
+  }
+}
+
+// Local data is only as fresh as the least recently synchronised project. None means at least
+// one of the projects was never synchronised.
+pub(crate) async fn get_oldest_synchronisation(project_keys: &[String], db_conn: &Pool<Sqlite>) -> Option<i64> {
+  let mut res = None;
+  for project_key in project_keys {
+    let last_synchronisation = get_last_synchronisation_of_project(project_key, db_conn).await?;
+    res = Some(res.map_or(last_synchronisation, |x: i64| x.min(last_synchronisation)));
+  }
+  res
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn timestamp_of_utc_dates() {
+    assert_eq!(get_timestamp_of_jira_date("1970-01-01T00:00:00.000Z"), Some(0));
+    assert_eq!(get_timestamp_of_jira_date("2024-02-29T12:00:00Z"), Some(1709208000));
+  }
+
+  #[test]
+  fn timestamp_of_dates_with_offsets() {
+    assert_eq!(get_timestamp_of_jira_date("2024-01-29T10:00:00.000+0100"), Some(1706518800));
+    assert_eq!(get_timestamp_of_jira_date("2024-01-29T10:00:00.000+01:00"), Some(1706518800));
+    assert_eq!(get_timestamp_of_jira_date("2023-12-31T23:30:15.123-0530"), Some(1704085215));
+  }
+
+  #[test]
+  fn timestamp_of_invalid_dates() {
+    assert_eq!(get_timestamp_of_jira_date(""), None);
+    assert_eq!(get_timestamp_of_jira_date("2024-01-29"), None);
+    assert_eq!(get_timestamp_of_jira_date("2024-01-29T10:00:00.000"), None);
+    assert_eq!(get_timestamp_of_jira_date("2024-13-29T10:00:00.000Z"), None);
+    assert_eq!(get_timestamp_of_jira_date("2024-01-29T10:00.000Z"), None);
+    assert_eq!(get_timestamp_of_jira_date("2024-01-29T10:00:00.000+01"), None);
+  }
+}
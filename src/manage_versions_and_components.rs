@@ -0,0 +1,595 @@
+use std::collections::HashSet;
+use serde_json::Value;
+use sqlx::{FromRow, Pool, Sqlite};
+use crate::get_config::Config;
+use crate::get_json_from_url::get_json_from_url;
+use crate::server::{ProjectComponent, ProjectVersion};
+
+// Versions and components are listed per project, and referenced by the fixVersions and
+// components fields of the tickets. Both are kept in their own tables, with one junction table
+// each to the tickets.
+
+#[derive(Debug, FromRow, Hash, Eq, PartialEq)]
+struct VersionRow {
+  id: i64,
+  project_key: String,
+  position_in_project: i64,
+  name: String,
+  description: Option<String>,
+  is_released: bool,
+  is_archived: bool,
+  release_date: Option<String>,
+}
+
+#[derive(Debug, FromRow, Hash, Eq, PartialEq)]
+struct ComponentRow {
+  id: i64,
+  project_key: String,
+  name: String,
+  description: Option<String>,
+  lead_account_id: Option<String>,
+  lead_display_name: Option<String>,
+}
+
+fn get_id_from_json(json: &Value) -> Option<i64> {
+  json
+    .get("id")
+    .and_then(|x| x.as_str())
+    .and_then(|x| x.parse::<i64>().ok())
+}
+
+fn get_optional_string_from_json(json: &Value, name: &str) -> Option<String> {
+  json
+    .get(name)
+    .and_then(|x| x.as_str())
+    .map(|x| x.to_string())
+}
+
+fn get_version_row_from_json(version: &Value, project_key: &str, position_in_project: i64) -> Result<VersionRow, String> {
+  let id = get_id_from_json(version);
+  let name = version
+    .get("name")
+    .and_then(|x| x.as_str());
+  let (Some(id), Some(name)) = (id, name) else {
+    return Err(format!("Versions of project {project_key} contain an invalid version: {version}"));
+  };
+
+  let get_flag = |name: &str| {
+    version
+      .get(name)
+      .and_then(|x| x.as_bool())
+      .unwrap_or(false)
+  };
+
+  Ok(VersionRow {
+    id,
+    project_key: project_key.to_string(),
+    position_in_project,
+    name: name.to_string(),
+    description: get_optional_string_from_json(version, "description"),
+    is_released: get_flag("released"),
+    is_archived: get_flag("archived"),
+    release_date: get_optional_string_from_json(version, "releaseDate"),
+  })
+}
+
+fn get_component_row_from_json(component: &Value, project_key: &str) -> Result<ComponentRow, String> {
+  let id = get_id_from_json(component);
+  let name = component
+    .get("name")
+    .and_then(|x| x.as_str());
+  let (Some(id), Some(name)) = (id, name) else {
+    return Err(format!("Components of project {project_key} contain an invalid component: {component}"));
+  };
+
+  let lead = component.get("lead");
+  Ok(ComponentRow {
+    id,
+    project_key: project_key.to_string(),
+    name: name.to_string(),
+    description: get_optional_string_from_json(component, "description"),
+    lead_account_id: lead.and_then(|x| get_optional_string_from_json(x, "accountId")),
+    lead_display_name: lead.and_then(|x| get_optional_string_from_json(x, "displayName")),
+  })
+}
+
+async fn get_json_array_from_server(config: &Config, query: &str) -> Result<Vec<Value>, String> {
+  let json = get_json_from_url(config, query)
+    .await
+    .map_err(|e| format!("Error: failed to get {query} from server.\n{e}"))?;
+
+  match json {
+    Value::Array(values) => { Ok(values) }
+    _ => { Err(format!("Reply to {query} isn't a json array: {json}")) }
+  }
+}
+
+async fn get_version_rows_from_server(config: &Config, project_key: &str) -> Result<Vec<VersionRow>, String> {
+  let query = format!("/rest/api/3/project/{project_key}/versions");
+  get_json_array_from_server(config, query.as_str())
+    .await?
+    .iter()
+    .enumerate()
+    .map(|(position, x)| get_version_row_from_json(x, project_key, position as i64))
+    .collect()
+}
+
+async fn get_component_rows_from_server(config: &Config, project_key: &str) -> Result<Vec<ComponentRow>, String> {
+  let query = format!("/rest/api/3/project/{project_key}/components");
+  get_json_array_from_server(config, query.as_str())
+    .await?
+    .iter()
+    .map(|x| get_component_row_from_json(x, project_key))
+    .collect()
+}
+
+async fn get_version_rows_from_db(project_key: &str, db_conn: &Pool<Sqlite>) -> Result<Vec<VersionRow>, String> {
+  let query_str =
+    "SELECT id, project_key, position_in_project, name, description, is_released, is_archived, release_date
+     FROM ProjectVersion
+     WHERE project_key = ?
+     ORDER BY position_in_project ASC, id ASC;";
+
+  sqlx::query_as::<_, VersionRow>(query_str)
+    .bind(project_key)
+    .fetch_all(db_conn)
+    .await
+    .map_err(|e| format!("Error occurred while trying to get the versions of project {project_key} from local database: {e}"))
+}
+
+async fn get_component_rows_from_db(project_key: &str, db_conn: &Pool<Sqlite>) -> Result<Vec<ComponentRow>, String> {
+  let query_str =
+    "SELECT id, project_key, name, description, lead AS lead_account_id, People.displayName AS lead_display_name
+     FROM ProjectComponent
+     LEFT JOIN People ON People.accountId = ProjectComponent.lead
+     WHERE project_key = ?
+     ORDER BY name ASC, id ASC;";
+
+  sqlx::query_as::<_, ComponentRow>(query_str)
+    .bind(project_key)
+    .fetch_all(db_conn)
+    .await
+    .map_err(|e| format!("Error occurred while trying to get the components of project {project_key} from local database: {e}"))
+}
+
+async fn update_versions_of_project_in_db(project_key: &str,
+                                          versions_in_remote: &[VersionRow],
+                                          versions_in_db: &[VersionRow],
+                                          db_conn: &Pool<Sqlite>) -> bool {
+  let versions_in_db_set = versions_in_db.iter().collect::<HashSet<_>>();
+  let ids_in_remote = versions_in_remote.iter().map(|x| x.id).collect::<HashSet<_>>();
+
+  let ids_to_remove = versions_in_db
+    .iter()
+    .filter(|x| !ids_in_remote.contains(&x.id))
+    .map(|x| x.id)
+    .collect::<Vec<_>>();
+  let versions_to_insert = versions_in_remote
+    .iter()
+    .filter(|x| !versions_in_db_set.contains(x))
+    .collect::<Vec<_>>();
+
+  if ids_to_remove.is_empty() && versions_to_insert.is_empty() {
+    eprintln!("Versions of project {project_key} are up to date");
+    return true;
+  }
+
+  let mut tx = match db_conn.begin().await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("Error when starting a sql transaction to update the versions of project {project_key}. Err: {e:?}");
+      return false;
+    }
+  };
+
+  let queries = [
+    "DELETE FROM IssueFixVersion WHERE version_id = ?;",
+    "DELETE FROM ProjectVersion WHERE id = ?;",
+  ];
+  for id in &ids_to_remove {
+    for query_str in queries {
+      let res = sqlx::query(query_str)
+        .bind(id)
+        .execute(&mut *tx)
+        .await;
+      if let Err(e) = res {
+        eprintln!("Error when removing version {id} of project {project_key}. Err: {e:?}");
+        return false; // dropping the transaction rolls it back
+      }
+    }
+  }
+
+  let query_str =
+    "INSERT INTO ProjectVersion (id, project_key, position_in_project, name, description, is_released, is_archived, release_date)
+     VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+     ON CONFLICT DO
+     UPDATE SET project_key = excluded.project_key,
+                position_in_project = excluded.position_in_project,
+                name = excluded.name,
+                description = excluded.description,
+                is_released = excluded.is_released,
+                is_archived = excluded.is_archived,
+                release_date = excluded.release_date;";
+  for version in &versions_to_insert {
+    let res = sqlx::query(query_str)
+      .bind(version.id)
+      .bind(&version.project_key)
+      .bind(version.position_in_project)
+      .bind(&version.name)
+      .bind(&version.description)
+      .bind(version.is_released)
+      .bind(version.is_archived)
+      .bind(&version.release_date)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when adding version {name} of project {project_key}. Err: {e:?}", name = version.name);
+      return false;
+    }
+  }
+
+  match tx.commit().await {
+    Ok(_) => {
+      eprintln!("Updated versions of project {project_key}: {removed} removed, {inserted} added or edited",
+                removed = ids_to_remove.len(), inserted = versions_to_insert.len());
+      true
+    }
+    Err(e) => {
+      eprintln!("Error when committing the versions of project {project_key}. Err: {e:?}");
+      false
+    }
+  }
+}
+
+async fn update_components_of_project_in_db(project_key: &str,
+                                            components_in_remote: &[ComponentRow],
+                                            components_in_db: &[ComponentRow],
+                                            db_conn: &Pool<Sqlite>) -> bool {
+  let components_in_db_set = components_in_db.iter().collect::<HashSet<_>>();
+  let ids_in_remote = components_in_remote.iter().map(|x| x.id).collect::<HashSet<_>>();
+
+  let ids_to_remove = components_in_db
+    .iter()
+    .filter(|x| !ids_in_remote.contains(&x.id))
+    .map(|x| x.id)
+    .collect::<Vec<_>>();
+  let components_to_insert = components_in_remote
+    .iter()
+    .filter(|x| !components_in_db_set.contains(x))
+    .collect::<Vec<_>>();
+
+  if ids_to_remove.is_empty() && components_to_insert.is_empty() {
+    eprintln!("Components of project {project_key} are up to date");
+    return true;
+  }
+
+  let mut tx = match db_conn.begin().await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("Error when starting a sql transaction to update the components of project {project_key}. Err: {e:?}");
+      return false;
+    }
+  };
+
+  // leads first, since the components reference them as a foreign key
+  let query_str =
+    "INSERT INTO People (accountId, displayName) VALUES (?, ?)
+     ON CONFLICT(accountId) DO
+     UPDATE SET displayName = excluded.displayName;";
+  let leads = components_to_insert
+    .iter()
+    .filter_map(|x| x.lead_account_id.as_ref().map(|id| (id, x.lead_display_name.as_ref().unwrap_or(id))))
+    .collect::<HashSet<_>>();
+  for (account_id, display_name) in leads {
+    let res = sqlx::query(query_str)
+      .bind(account_id)
+      .bind(display_name)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when adding lead {display_name} of components of project {project_key}. Err: {e:?}");
+      return false; // dropping the transaction rolls it back
+    }
+  }
+
+  let queries = [
+    "DELETE FROM IssueComponent WHERE component_id = ?;",
+    "DELETE FROM ProjectComponent WHERE id = ?;",
+  ];
+  for id in &ids_to_remove {
+    for query_str in queries {
+      let res = sqlx::query(query_str)
+        .bind(id)
+        .execute(&mut *tx)
+        .await;
+      if let Err(e) = res {
+        eprintln!("Error when removing component {id} of project {project_key}. Err: {e:?}");
+        return false;
+      }
+    }
+  }
+
+  let query_str =
+    "INSERT INTO ProjectComponent (id, project_key, name, description, lead)
+     VALUES (?, ?, ?, ?, ?)
+     ON CONFLICT DO
+     UPDATE SET project_key = excluded.project_key,
+                name = excluded.name,
+                description = excluded.description,
+                lead = excluded.lead;";
+  for component in &components_to_insert {
+    let res = sqlx::query(query_str)
+      .bind(component.id)
+      .bind(&component.project_key)
+      .bind(&component.name)
+      .bind(&component.description)
+      .bind(&component.lead_account_id)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when adding component {name} of project {project_key}. Err: {e:?}", name = component.name);
+      return false;
+    }
+  }
+
+  match tx.commit().await {
+    Ok(_) => {
+      eprintln!("Updated components of project {project_key}: {removed} removed, {inserted} added or edited",
+                removed = ids_to_remove.len(), inserted = components_to_insert.len());
+      true
+    }
+    Err(e) => {
+      eprintln!("Error when committing the components of project {project_key}. Err: {e:?}");
+      false
+    }
+  }
+}
+
+async fn update_versions_and_components_of_project_in_db(config: &Config, project_key: &str, db_conn: &Pool<Sqlite>) {
+  match (get_version_rows_from_server(config, project_key).await, get_version_rows_from_db(project_key, db_conn).await) {
+    (Ok(versions_in_remote), Ok(versions_in_db)) => {
+      update_versions_of_project_in_db(project_key, versions_in_remote.as_slice(), versions_in_db.as_slice(), db_conn).await;
+    }
+    (Err(e), _) | (_, Err(e)) => {
+      eprintln!("{e}");
+    }
+  }
+
+  match (get_component_rows_from_server(config, project_key).await, get_component_rows_from_db(project_key, db_conn).await) {
+    (Ok(components_in_remote), Ok(components_in_db)) => {
+      update_components_of_project_in_db(project_key, components_in_remote.as_slice(), components_in_db.as_slice(), db_conn).await;
+    }
+    (Err(e), _) | (_, Err(e)) => {
+      eprintln!("{e}");
+    }
+  }
+}
+
+pub(crate) async fn update_versions_and_components_in_db(config: &Config, db_conn: &Pool<Sqlite>) {
+  for project_key in config.interesting_projects() {
+    update_versions_and_components_of_project_in_db(config, project_key, db_conn).await;
+  }
+}
+
+#[derive(FromRow)]
+struct IdInDb {
+  id: i64,
+}
+
+async fn get_ids_from_db(query_str: &str, issue_id: u32, db_conn: &Pool<Sqlite>) -> Result<HashSet<i64>, String> {
+  sqlx::query_as::<_, IdInDb>(query_str)
+    .bind(issue_id)
+    .fetch_all(db_conn)
+    .await
+    .map(|x| x.into_iter().map(|x| x.id).collect())
+    .map_err(|e| format!("Error occurred while trying to run [{query_str}] on the local database for issue {issue_id}: {e}"))
+}
+
+// Links the ticket to its fix versions and components, as found in its fields. A version or
+// component created after the last synchronisation of the project is added with what the ticket
+// tells about it, the next synchronisation of the project completes it.
+pub(crate) async fn update_versions_and_components_of_issue_in_db(issue_key: &str, issue_id: u32, json_data: &Value, db_conn: &Pool<Sqlite>) {
+  let fields = json_data.get("fields");
+  let Some(project_key) = fields
+    .and_then(|x| x.get("project"))
+    .and_then(|x| x.get("key"))
+    .and_then(|x| x.as_str()) else {
+    eprintln!("Error: the json data for {issue_key} does not contain a project key");
+    return;
+  };
+  let get_list = |name: &str| {
+    fields
+      .and_then(|x| x.get(name))
+      .and_then(|x| x.as_array())
+      .cloned()
+      .unwrap_or_default()
+  };
+
+  let versions = get_list("fixVersions")
+    .iter()
+    .map(|x| get_version_row_from_json(x, project_key, i64::MAX))
+    .collect::<Result<Vec<_>, _>>();
+  let components = get_list("components")
+    .iter()
+    .map(|x| get_component_row_from_json(x, project_key))
+    .collect::<Result<Vec<_>, _>>();
+  let (versions, components) = match (versions, components) {
+    (Ok(versions), Ok(components)) => { (versions, components) }
+    (Err(e), _) | (_, Err(e)) => {
+      eprintln!("Error: invalid fix versions or components in the json data of {issue_key}. {e}");
+      return;
+    }
+  };
+
+  let version_ids_in_db = get_ids_from_db("SELECT version_id AS id FROM IssueFixVersion WHERE issue_id = ?;", issue_id, db_conn).await;
+  let component_ids_in_db = get_ids_from_db("SELECT component_id AS id FROM IssueComponent WHERE issue_id = ?;", issue_id, db_conn).await;
+  let (version_ids_in_db, component_ids_in_db) = match (version_ids_in_db, component_ids_in_db) {
+    (Ok(versions), Ok(components)) => { (versions, components) }
+    (Err(e), _) | (_, Err(e)) => {
+      eprintln!("{e}");
+      return;
+    }
+  };
+
+  let version_ids_in_remote = versions.iter().map(|x| x.id).collect::<HashSet<_>>();
+  let component_ids_in_remote = components.iter().map(|x| x.id).collect::<HashSet<_>>();
+  if version_ids_in_remote == version_ids_in_db && component_ids_in_remote == component_ids_in_db {
+    eprintln!("Fix versions and components of issue {issue_key} are up to date");
+    return;
+  }
+
+  let mut tx = match db_conn.begin().await {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("Error when starting a sql transaction to update the fix versions and components of issue {issue_key}. Err: {e:?}");
+      return;
+    }
+  };
+
+  let queries = [
+    "DELETE FROM IssueFixVersion WHERE issue_id = ?;",
+    "DELETE FROM IssueComponent WHERE issue_id = ?;",
+  ];
+  for query_str in queries {
+    let res = sqlx::query(query_str)
+      .bind(issue_id)
+      .execute(&mut *tx)
+      .await;
+    if let Err(e) = res {
+      eprintln!("Error when removing the fix versions and components of issue {issue_key}. Err: {e:?}");
+      return; // dropping the transaction rolls it back
+    }
+  }
+
+  for version in &versions {
+    let res = sqlx::query(
+      "INSERT INTO ProjectVersion (id, project_key, position_in_project, name, description, is_released, is_archived, release_date)
+       VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+       ON CONFLICT DO NOTHING;")
+      .bind(version.id)
+      .bind(&version.project_key)
+      .bind(version.position_in_project)
+      .bind(&version.name)
+      .bind(&version.description)
+      .bind(version.is_released)
+      .bind(version.is_archived)
+      .bind(&version.release_date)
+      .execute(&mut *tx)
+      .await;
+    let res = match res {
+      Ok(_) => {
+        sqlx::query("INSERT INTO IssueFixVersion (issue_id, version_id) VALUES (?, ?);")
+          .bind(issue_id)
+          .bind(version.id)
+          .execute(&mut *tx)
+          .await
+      }
+      Err(e) => { Err(e) }
+    };
+    if let Err(e) = res {
+      eprintln!("Error when adding fix version {name} to issue {issue_key}. Err: {e:?}", name = version.name);
+      return;
+    }
+  }
+
+  for component in &components {
+    let res = sqlx::query(
+      "INSERT INTO ProjectComponent (id, project_key, name, description, lead)
+       VALUES (?, ?, ?, ?, NULL)
+       ON CONFLICT DO NOTHING;")
+      .bind(component.id)
+      .bind(&component.project_key)
+      .bind(&component.name)
+      .bind(&component.description)
+      .execute(&mut *tx)
+      .await;
+    let res = match res {
+      Ok(_) => {
+        sqlx::query("INSERT INTO IssueComponent (issue_id, component_id) VALUES (?, ?);")
+          .bind(issue_id)
+          .bind(component.id)
+          .execute(&mut *tx)
+          .await
+      }
+      Err(e) => { Err(e) }
+    };
+    if let Err(e) = res {
+      eprintln!("Error when adding component {name} to issue {issue_key}. Err: {e:?}", name = component.name);
+      return;
+    }
+  }
+
+  match tx.commit().await {
+    Ok(_) => {
+      eprintln!("Updated issue {issue_key}: {nr_versions} fix versions and {nr_components} components",
+                nr_versions = versions.len(), nr_components = components.len());
+    }
+    Err(e) => {
+      eprintln!("Error when committing the fix versions and components of issue {issue_key}. Err: {e:?}");
+    }
+  }
+}
+
+pub(crate) async fn get_versions_of_project_from_db(project_key: &str, db_conn: &Pool<Sqlite>) -> Result<Vec<ProjectVersion>, String> {
+  let rows = get_version_rows_from_db(project_key, db_conn).await?;
+  let res = rows
+    .into_iter()
+    .map(|x| ProjectVersion {
+      id: x.id,
+      name: x.name,
+      description: x.description,
+      is_released: x.is_released,
+      is_archived: x.is_archived,
+      release_date: x.release_date,
+    })
+    .collect();
+  Ok(res)
+}
+
+pub(crate) async fn get_components_of_project_from_db(project_key: &str, db_conn: &Pool<Sqlite>) -> Result<Vec<ProjectComponent>, String> {
+  let rows = get_component_rows_from_db(project_key, db_conn).await?;
+  let res = rows
+    .into_iter()
+    .map(|x| ProjectComponent {
+      id: x.id,
+      name: x.name,
+      description: x.description,
+      lead: x.lead_display_name.or(x.lead_account_id),
+    })
+    .collect();
+  Ok(res)
+}
+
+#[derive(FromRow)]
+struct KeyInDb {
+  key: String,
+}
+
+async fn get_keys_from_db(query_str: &str, id: i64, db_conn: &Pool<Sqlite>) -> Result<Vec<String>, String> {
+  sqlx::query_as::<_, KeyInDb>(query_str)
+    .bind(id)
+    .fetch_all(db_conn)
+    .await
+    .map(|x| x.into_iter().map(|x| x.key).collect())
+    .map_err(|e| format!("Error occurred while trying to run [{query_str}] on the local database with id {id}: {e}"))
+}
+
+pub(crate) async fn get_tickets_of_version_from_db(version_id: i64, db_conn: &Pool<Sqlite>) -> Result<Vec<String>, String> {
+  let query_str =
+    "SELECT Issue.key
+     FROM IssueFixVersion
+     JOIN Issue ON Issue.jira_id = IssueFixVersion.issue_id
+     WHERE IssueFixVersion.version_id = ?
+     ORDER BY Issue.jira_id ASC;";
+  get_keys_from_db(query_str, version_id, db_conn).await
+}
+
+pub(crate) async fn get_tickets_of_component_from_db(component_id: i64, db_conn: &Pool<Sqlite>) -> Result<Vec<String>, String> {
+  let query_str =
+    "SELECT Issue.key
+     FROM IssueComponent
+     JOIN Issue ON Issue.jira_id = IssueComponent.issue_id
+     WHERE IssueComponent.component_id = ?
+     ORDER BY Issue.jira_id ASC;";
+  get_keys_from_db(query_str, component_id, db_conn).await
+}
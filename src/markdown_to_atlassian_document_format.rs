@@ -0,0 +1,1086 @@
+use serde_json::{json, Value};
+
+// Converts markdown written by a user, e.g. for a new comment, to the atlassian document format
+// expected by jira. See atlassian_document_format.rs for the reverse direction.
+//
+// Follows CommonMark, with the github extensions for tables, task lists and strikethrough, in a
+// simplified way: there are no reference links nor html, and emphasis is closed by the next
+// delimiter run of the same length instead of using the whole delimiter algorithm. Like in the
+// jira editor, a single newline inside a paragraph is a line break. Task lists are also recognised
+// when written with ☐ and ☑, the way atlassian_document_format.rs renders them.
+
+const MAX_HEADING_LEVEL: usize = 6;
+
+// jira wants task lists and task items to have an id, unique in the document
+#[derive(Default)]
+struct LocalIds {
+  last: u32,
+}
+
+impl LocalIds {
+  fn next(&mut self) -> String {
+    self.last += 1;
+    self.last.to_string()
+  }
+}
+
+fn get_indentation(line: &str) -> usize {
+  line.chars().take_while(|x| *x == ' ').count()
+}
+
+// removes at most n leading spaces
+fn strip_indentation(line: &str, n: usize) -> &str {
+  &line[get_indentation(line).min(n)..]
+}
+
+fn is_blank(line: &str) -> bool {
+  line.trim().is_empty()
+}
+
+//
+// inline content: text with marks, and line breaks
+//
+
+enum Inline {
+  Text { text: String, marks: Vec<Value> },
+  HardBreak,
+}
+
+fn flush_text(text: &mut String, nodes: &mut Vec<Inline>) {
+  if !text.is_empty() {
+    nodes.push(Inline::Text { text: std::mem::take(text), marks: vec![] });
+  }
+}
+
+// marks are listed from the innermost to the outermost one, like atlassian_document_format.rs
+// applies them
+fn add_mark(nodes: Vec<Inline>, mark: &Value) -> Vec<Inline> {
+  let mark_type = mark.get("type");
+  nodes
+    .into_iter()
+    .map(|node| match node {
+      Inline::Text { text, mut marks } => {
+        let has_same_mark = marks.iter().any(|x| x.get("type") == mark_type);
+        // the code mark can only be combined with links
+        let has_code_mark = marks.iter().any(|x| x.get("type").and_then(|x| x.as_str()) == Some("code"));
+        if !has_same_mark && (!has_code_mark || mark_type.and_then(|x| x.as_str()) == Some("link")) {
+          marks.push(mark.clone());
+        }
+        Inline::Text { text, marks }
+      }
+      Inline::HardBreak => Inline::HardBreak,
+    })
+    .collect()
+}
+
+fn get_run_length(chars: &[char], start: usize) -> usize {
+  chars[start..].iter().take_while(|x| **x == chars[start]).count()
+}
+
+fn unescape(chars: &[char]) -> String {
+  let mut res = String::new();
+  let mut i = 0;
+  while i < chars.len() {
+    if chars[i] == '\\' && chars.get(i + 1).is_some_and(|x| x.is_ascii_punctuation()) {
+      i += 1;
+    }
+    res.push(chars[i]);
+    i += 1;
+  }
+  res
+}
+
+// index of the backticks closing a code span opened by `length` backticks
+fn find_code_span_end(chars: &[char], start: usize, length: usize) -> Option<usize> {
+  let mut i = start;
+  while i < chars.len() {
+    if chars[i] != '`' {
+      i += 1;
+      continue;
+    }
+    let run = get_run_length(chars, i);
+    if run == length {
+      return Some(i);
+    }
+    i += run;
+  }
+  None
+}
+
+fn code_span_to_text(chars: &[char]) -> String {
+  let code = chars
+    .iter()
+    .map(|x| if *x == '\n' { ' ' } else { *x })
+    .collect::<String>();
+  // a single space on both sides allows the code to start or end with a backtick
+  match code.strip_prefix(' ').and_then(|x| x.strip_suffix(' ')) {
+    Some(stripped) if !stripped.trim().is_empty() => stripped.to_string(),
+    _ => code,
+  }
+}
+
+struct Link {
+  label_end: usize,
+  href: String,
+  title: Option<String>,
+  end: usize,
+}
+
+fn skip_spaces(chars: &[char], start: usize) -> usize {
+  start + chars[start.min(chars.len())..].iter().take_while(|x| x.is_whitespace()).count()
+}
+
+// parses [label](destination "title"), chars[open] being the opening bracket
+fn parse_link(chars: &[char], open: usize) -> Option<Link> {
+  let mut depth = 0;
+  let mut i = open;
+  let label_end = loop {
+    match chars.get(i)? {
+      '\\' => i += 1,
+      '[' => depth += 1,
+      ']' => {
+        depth -= 1;
+        if depth == 0 {
+          break i;
+        }
+      }
+      _ => {}
+    }
+    i += 1;
+  };
+
+  if chars.get(label_end + 1) != Some(&'(') {
+    return None;
+  }
+  let mut i = skip_spaces(chars, label_end + 2);
+  let href = if chars.get(i) == Some(&'<') {
+    let end = (i + 1..chars.len()).find(|x| chars[*x] == '>' || chars[*x] == '\n')?;
+    if chars[end] != '>' {
+      return None;
+    }
+    let href = unescape(&chars[i + 1..end]);
+    i = end + 1;
+    href
+  } else {
+    let start = i;
+    let mut parentheses = 0;
+    while let Some(c) = chars.get(i) {
+      match c {
+        '\\' => i += 1,
+        '(' => parentheses += 1,
+        ')' if parentheses == 0 => break,
+        ')' => parentheses -= 1,
+        c if c.is_whitespace() => break,
+        _ => {}
+      }
+      i += 1;
+    }
+    unescape(&chars[start..i.min(chars.len())])
+  };
+
+  let mut i = skip_spaces(chars, i);
+  let title = match chars.get(i) {
+    Some(quote) if *quote == '"' || *quote == '\'' => {
+      let end = (i + 1..chars.len()).find(|x| chars[*x] == *quote && chars[*x - 1] != '\\')?;
+      let title = unescape(&chars[i + 1..end]);
+      i = skip_spaces(chars, end + 1);
+      Some(title)
+    }
+    _ => None,
+  };
+
+  if chars.get(i) != Some(&')') {
+    return None;
+  }
+  Some(Link { label_end, href, title, end: i + 1 })
+}
+
+// parses <https://example.com> and <someone@example.com>, chars[open] being the opening <.
+// Returns the link and the index following the closing >
+fn parse_autolink(chars: &[char], open: usize) -> Option<(String, usize)> {
+  let close = (open + 1..chars.len())
+    .take_while(|x| !chars[*x].is_whitespace() && chars[*x] != '<')
+    .find(|x| chars[*x] == '>')?;
+  let content = chars[open + 1..close].iter().collect::<String>();
+
+  let is_scheme = |scheme: &str| {
+    scheme.len() >= 2
+      && scheme.starts_with(|x: char| x.is_ascii_alphabetic())
+      && scheme.chars().all(|x| x.is_ascii_alphanumeric() || x == '+' || x == '.' || x == '-')
+  };
+  match content.split_once(':') {
+    Some((scheme, _)) if is_scheme(scheme) => Some((content, close + 1)),
+    None if content.contains('@') => Some((content, close + 1)),
+    _ => None,
+  }
+}
+
+fn get_emphasis_marks(delimiter: char, length: usize) -> Vec<Value> {
+  match (delimiter, length) {
+    ('*' | '_', 1) => vec![json!({"type": "em"})],
+    ('*' | '_', 2) => vec![json!({"type": "strong"})],
+    ('*' | '_', 3) => vec![json!({"type": "em"}), json!({"type": "strong"})],
+    ('~', 1 | 2) => vec![json!({"type": "strike"})],
+    _ => vec![],
+  }
+}
+
+// index of the delimiter run closing an emphasis opened by `length` delimiters
+fn find_closing_delimiter(chars: &[char], start: usize, delimiter: char, length: usize) -> Option<usize> {
+  let mut i = start;
+  while i < chars.len() {
+    match chars[i] {
+      '\\' => i += 2,
+      '`' => {
+        let run = get_run_length(chars, i);
+        i = find_code_span_end(chars, i + run, run).unwrap_or(i) + run;
+      }
+      c if c == delimiter => {
+        let run = get_run_length(chars, i);
+        let next = chars.get(i + run);
+        // underscores inside words aren't delimiters, e.g. snake_case
+        let closes = run == length
+          && !chars[i - 1].is_whitespace()
+          && (delimiter != '_' || !next.is_some_and(|x| x.is_alphanumeric()));
+        if closes {
+          return Some(i);
+        }
+        i += run;
+      }
+      _ => i += 1,
+    }
+  }
+  None
+}
+
+fn parse_inline_nodes(chars: &[char]) -> Vec<Inline> {
+  let mut res = Vec::new();
+  let mut text = String::new();
+  let mut i = 0;
+  while i < chars.len() {
+    let c = chars[i];
+    match c {
+      '\\' if chars.get(i + 1).is_some_and(|x| x.is_ascii_punctuation()) => {
+        text.push(chars[i + 1]);
+        i += 2;
+      }
+      '\n' => {
+        flush_text(&mut text, &mut res);
+        res.push(Inline::HardBreak);
+        i += 1;
+      }
+      '`' => {
+        let run = get_run_length(chars, i);
+        match find_code_span_end(chars, i + run, run) {
+          Some(end) => {
+            flush_text(&mut text, &mut res);
+            let code = code_span_to_text(&chars[i + run..end]);
+            if !code.is_empty() {
+              res.push(Inline::Text { text: code, marks: vec![json!({"type": "code"})] });
+            }
+            i = end + run;
+          }
+          None => {
+            text.extend(&chars[i..i + run]);
+            i += run;
+          }
+        }
+      }
+      '[' | '!' => {
+        // images can't be embedded from an url, they become links to the image
+        let open = if c == '!' && chars.get(i + 1) == Some(&'[') { i + 1 } else { i };
+        let link = if chars[open] == '[' { parse_link(chars, open) } else { None };
+        match link {
+          Some(link) => {
+            flush_text(&mut text, &mut res);
+            let mut label = parse_inline_nodes(&chars[open + 1..link.label_end]);
+            if label.is_empty() {
+              label.push(Inline::Text { text: link.href.clone(), marks: vec![] });
+            }
+            let mark = match link.title {
+              None => json!({"type": "link", "attrs": {"href": link.href}}),
+              Some(title) => json!({"type": "link", "attrs": {"href": link.href, "title": title}}),
+            };
+            res.extend(add_mark(label, &mark));
+            i = link.end;
+          }
+          None => {
+            text.push(c);
+            i += 1;
+          }
+        }
+      }
+      '<' => match parse_autolink(chars, i) {
+        Some((link, end)) => {
+          flush_text(&mut text, &mut res);
+          let href = if link.contains(':') { link.clone() } else { format!("mailto:{link}") };
+          let label = vec![Inline::Text { text: link, marks: vec![] }];
+          res.extend(add_mark(label, &json!({"type": "link", "attrs": {"href": href}})));
+          i = end;
+        }
+        None => {
+          text.push(c);
+          i += 1;
+        }
+      },
+      '*' | '_' | '~' => {
+        let run = get_run_length(chars, i);
+        let marks = get_emphasis_marks(c, run);
+        let previous = i.checked_sub(1).map(|x| chars[x]);
+        let can_open = !marks.is_empty()
+          && chars.get(i + run).is_some_and(|x| !x.is_whitespace())
+          && (c != '_' || !previous.is_some_and(|x| x.is_alphanumeric()));
+        let end = if can_open { find_closing_delimiter(chars, i + run, c, run) } else { None };
+        match end {
+          Some(end) => {
+            flush_text(&mut text, &mut res);
+            let mut nodes = parse_inline_nodes(&chars[i + run..end]);
+            for mark in &marks {
+              nodes = add_mark(nodes, mark);
+            }
+            res.extend(nodes);
+            i = end + run;
+          }
+          None => {
+            text.extend(&chars[i..i + run]);
+            i += run;
+          }
+        }
+      }
+      _ => {
+        text.push(c);
+        i += 1;
+      }
+    }
+  }
+  flush_text(&mut text, &mut res);
+  res
+}
+
+fn parse_inline(text: &str) -> Vec<Value> {
+  let chars = text.chars().collect::<Vec<_>>();
+
+  // neighbouring texts with the same marks are a single text node
+  let mut nodes: Vec<Inline> = Vec::new();
+  for node in parse_inline_nodes(&chars) {
+    if let (Some(Inline::Text { text, marks }), Inline::Text { text: next_text, marks: next_marks }) = (nodes.last_mut(), &node) {
+      if marks == next_marks {
+        text.push_str(next_text);
+        continue;
+      }
+    }
+    nodes.push(node);
+  }
+
+  nodes
+    .into_iter()
+    .map(|node| match node {
+      Inline::Text { text, marks } if marks.is_empty() => json!({"type": "text", "text": text}),
+      Inline::Text { text, marks } => json!({"type": "text", "text": text, "marks": marks}),
+      Inline::HardBreak => json!({"type": "hardBreak"}),
+    })
+    .collect()
+}
+
+// leading spaces aren't part of the text, and trailing spaces or backslash only mark line breaks
+fn join_paragraph_lines(lines: &[&str]) -> String {
+  lines
+    .iter()
+    .map(|x| {
+      let x = x.trim();
+      x.strip_suffix('\\').unwrap_or(x).trim_end()
+    })
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+//
+// blocks
+//
+
+fn get_atx_heading(line: &str) -> Option<(usize, &str)> {
+  if get_indentation(line) > 3 {
+    return None;
+  }
+  let line = line.trim_start();
+  let level = line.chars().take_while(|x| *x == '#').count();
+  if level == 0 || level > MAX_HEADING_LEVEL {
+    return None;
+  }
+  let text = &line[level..];
+  if !text.is_empty() && !text.starts_with(' ') {
+    return None;
+  }
+  // an optional closing sequence of # isn't part of the heading
+  let text = text.trim();
+  let without_closing_sequence = text.trim_end_matches('#');
+  if without_closing_sequence.is_empty() || without_closing_sequence.ends_with(' ') {
+    return Some((level, without_closing_sequence.trim_end()));
+  }
+  Some((level, text))
+}
+
+fn get_setext_heading_level(line: &str) -> Option<usize> {
+  if get_indentation(line) > 3 {
+    return None;
+  }
+  let line = line.trim();
+  if line.is_empty() {
+    None
+  } else if line.chars().all(|x| x == '=') {
+    Some(1)
+  } else if line.chars().all(|x| x == '-') {
+    Some(2)
+  } else {
+    None
+  }
+}
+
+fn is_thematic_break(line: &str) -> bool {
+  if get_indentation(line) > 3 {
+    return false;
+  }
+  let chars = line.chars().filter(|x| !x.is_whitespace()).collect::<Vec<_>>();
+  chars.len() >= 3
+    && matches!(chars[0], '-' | '*' | '_')
+    && chars.iter().all(|x| *x == chars[0])
+}
+
+struct CodeFence<'a> {
+  fence_char: char,
+  length: usize,
+  indentation: usize,
+  language: &'a str,
+}
+
+fn get_code_fence(line: &str) -> Option<CodeFence<'_>> {
+  let indentation = get_indentation(line);
+  if indentation > 3 {
+    return None;
+  }
+  let line = &line[indentation..];
+  let fence_char = line.chars().next().filter(|x| *x == '`' || *x == '~')?;
+  let length = line.chars().take_while(|x| *x == fence_char).count();
+  if length < 3 {
+    return None;
+  }
+  let info = line[length..].trim();
+  if fence_char == '`' && info.contains('`') {
+    return None;
+  }
+  // the language is the first word of the info string
+  let language = info.split_whitespace().next().unwrap_or_default();
+  Some(CodeFence { fence_char, length, indentation, language })
+}
+
+fn is_closing_code_fence(line: &str, fence: &CodeFence) -> bool {
+  let trimmed = line.trim();
+  get_indentation(line) <= 3
+    && trimmed.len() >= fence.length
+    && trimmed.chars().all(|x| x == fence.fence_char)
+}
+
+fn get_blockquote_content(line: &str) -> Option<&str> {
+  if get_indentation(line) > 3 {
+    return None;
+  }
+  let line = line.trim_start().strip_prefix('>')?;
+  Some(line.strip_prefix(' ').unwrap_or(line))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ListKind {
+  Bullet(char),
+  Ordered(char), // the delimiter following the number, . or )
+}
+
+struct ListMarker<'a> {
+  kind: ListKind,
+  start: u64,
+  content_indentation: usize,
+  content: &'a str,
+}
+
+fn get_list_marker(line: &str) -> Option<ListMarker<'_>> {
+  let indentation = get_indentation(line);
+  if indentation > 3 {
+    return None;
+  }
+  let line = &line[indentation..];
+  let digits = line.chars().take_while(|x| x.is_ascii_digit()).count();
+  let (kind, start, marker_length) = if digits == 0 {
+    let bullet = line.chars().next().filter(|x| matches!(x, '-' | '*' | '+'))?;
+    (ListKind::Bullet(bullet), 1, 1)
+  } else {
+    let delimiter = line[digits..].chars().next().filter(|x| *x == '.' || *x == ')')?;
+    let start = line[..digits].parse::<u64>().ok().filter(|_| digits <= 9)?;
+    (ListKind::Ordered(delimiter), start, digits + 1)
+  };
+
+  let after_marker = &line[marker_length..];
+  if is_blank(after_marker) {
+    let content_indentation = indentation + marker_length + 1;
+    return Some(ListMarker { kind, start, content_indentation, content: "" });
+  }
+  let spaces = get_indentation(after_marker);
+  if spaces == 0 {
+    return None;
+  }
+  // with more than 4 spaces, the content of the item is an indented code block
+  let spaces = if spaces > 4 { 1 } else { spaces };
+  let content_indentation = indentation + marker_length + spaces;
+  Some(ListMarker { kind, start, content_indentation, content: &after_marker[spaces..] })
+}
+
+// a task at the beginning of a list item, like "[x] done"
+fn get_task_item(text: &str) -> Option<(&'static str, &str)> {
+  [("[ ] ", "TODO"), ("[x] ", "DONE"), ("[X] ", "DONE")]
+    .into_iter()
+    .find_map(|(prefix, state)| text.strip_prefix(prefix).map(|x| (state, x)))
+}
+
+// a task the way atlassian_document_format.rs renders them, like "☑ done"
+fn get_rendered_task(line: &str) -> Option<(&'static str, &str)> {
+  if get_indentation(line) > 3 {
+    return None;
+  }
+  let line = line.trim_start();
+  [("☐ ", "TODO"), ("☑ ", "DONE")]
+    .into_iter()
+    .find_map(|(prefix, state)| line.strip_prefix(prefix).map(|x| (state, x)))
+}
+
+fn split_table_row(line: &str) -> Vec<String> {
+  let line = line.trim();
+  let line = line.strip_prefix('|').unwrap_or(line);
+  let line = match line.strip_suffix('|') {
+    Some(x) if !x.ends_with('\\') => x,
+    _ => line,
+  };
+
+  let mut cells = vec![String::new()];
+  let mut chars = line.chars();
+  while let Some(c) = chars.next() {
+    match (c, cells.last_mut()) {
+      ('|', _) => cells.push(String::new()),
+      ('\\', Some(cell)) => match chars.next() {
+        Some('|') => cell.push('|'),
+        Some(x) => {
+          cell.push('\\');
+          cell.push(x);
+        }
+        None => cell.push('\\'),
+      },
+      (c, Some(cell)) => cell.push(c),
+      (_, None) => {}
+    }
+  }
+  cells.into_iter().map(|x| x.trim().to_string()).collect()
+}
+
+fn is_table_delimiter_row(line: &str) -> bool {
+  line.contains('|')
+    && split_table_row(line).iter().all(|cell| {
+      let dashes = cell.strip_prefix(':').unwrap_or(cell);
+      let dashes = dashes.strip_suffix(':').unwrap_or(dashes);
+      !dashes.is_empty() && dashes.chars().all(|x| x == '-')
+    })
+}
+
+fn is_table_start(lines: &[&str]) -> bool {
+  match lines {
+    [header, delimiter, ..] => {
+      header.contains('|')
+        && is_table_delimiter_row(delimiter)
+        && split_table_row(header).len() == split_table_row(delimiter).len()
+    }
+    _ => false,
+  }
+}
+
+// whether the line starts a block, which ends the paragraph before it
+fn interrupts_paragraph(line: &str) -> bool {
+  get_atx_heading(line).is_some()
+    || get_code_fence(line).is_some()
+    || is_thematic_break(line)
+    || get_blockquote_content(line).is_some()
+    || get_rendered_task(line).is_some()
+    // empty items and ordered lists not starting at 1 can't interrupt a paragraph
+    || get_list_marker(line).is_some_and(|x| {
+      !x.content.is_empty() && (matches!(x.kind, ListKind::Bullet(_)) || x.start == 1)
+    })
+}
+
+fn paragraph(lines: &[&str]) -> Value {
+  json!({"type": "paragraph", "content": parse_inline(&join_paragraph_lines(lines))})
+}
+
+fn heading(level: usize, lines: &[&str]) -> Value {
+  json!({"type": "heading", "attrs": {"level": level}, "content": parse_inline(&join_paragraph_lines(lines))})
+}
+
+fn code_block(language: &str, code: &str) -> Value {
+  let content = if code.is_empty() { vec![] } else { vec![json!({"type": "text", "text": code})] };
+  if language.is_empty() {
+    json!({"type": "codeBlock", "content": content})
+  } else {
+    json!({"type": "codeBlock", "attrs": {"language": language}, "content": content})
+  }
+}
+
+fn task_list(items: Vec<(&str, Vec<&str>)>, local_ids: &mut LocalIds) -> Value {
+  let local_id = local_ids.next();
+  let content = items
+    .into_iter()
+    .map(|(state, lines)| json!({
+      "type": "taskItem",
+      "attrs": {"localId": local_ids.next(), "state": state},
+      "content": parse_inline(&join_paragraph_lines(&lines)),
+    }))
+    .collect::<Vec<_>>();
+  json!({"type": "taskList", "attrs": {"localId": local_id}, "content": content})
+}
+
+// each parse_ function below gets the lines starting with its block, and returns the block along
+// with the number of lines it spans
+
+fn parse_paragraph(lines: &[&str]) -> (Value, usize) {
+  let mut length = 1;
+  while length < lines.len() {
+    let line = lines[length];
+    if let Some(level) = get_setext_heading_level(line) {
+      return (heading(level, &lines[..length]), length + 1);
+    }
+    if is_blank(line) || interrupts_paragraph(line) {
+      break;
+    }
+    length += 1;
+  }
+  (paragraph(&lines[..length]), length)
+}
+
+fn parse_fenced_code_block(fence: &CodeFence, lines: &[&str]) -> (Value, usize) {
+  // a code block which isn't closed runs until the end of the document
+  let end = lines[1..]
+    .iter()
+    .position(|x| is_closing_code_fence(x, fence))
+    .map(|x| x + 1);
+  let code = lines[1..end.unwrap_or(lines.len())]
+    .iter()
+    .map(|x| strip_indentation(x, fence.indentation))
+    .collect::<Vec<_>>()
+    .join("\n");
+  (code_block(fence.language, &code), end.map_or(lines.len(), |x| x + 1))
+}
+
+fn parse_indented_code_block(lines: &[&str]) -> (Value, usize) {
+  let length = lines
+    .iter()
+    .position(|x| !is_blank(x) && get_indentation(x) < 4)
+    .unwrap_or(lines.len());
+  // blank lines after the code aren't part of it
+  let length = lines[..length]
+    .iter()
+    .rposition(|x| !is_blank(x))
+    .map_or(length, |x| x + 1);
+  let code = lines[..length]
+    .iter()
+    .map(|x| strip_indentation(x, 4))
+    .collect::<Vec<_>>()
+    .join("\n");
+  (code_block("", &code), length)
+}
+
+fn parse_blockquote(lines: &[&str], local_ids: &mut LocalIds) -> (Value, usize) {
+  let mut content = Vec::new();
+  for line in lines {
+    match get_blockquote_content(line) {
+      Some(x) => content.push(x),
+      // lazy continuation of a paragraph of the quote
+      None if !is_blank(line) && content.last().is_some_and(|x| !is_blank(x)) && !interrupts_paragraph(line) => {
+        content.push(line.trim_start());
+      }
+      None => break,
+    }
+  }
+  let length = content.len();
+  (json!({"type": "blockquote", "content": parse_blocks(&content, local_ids)}), length)
+}
+
+fn parse_rendered_task_list(lines: &[&str], local_ids: &mut LocalIds) -> (Value, usize) {
+  let mut items: Vec<(&str, Vec<&str>)> = Vec::new();
+  let mut length = 0;
+  for line in lines {
+    if let Some((state, text)) = get_rendered_task(line) {
+      items.push((state, vec![text]));
+    } else if is_blank(line) || interrupts_paragraph(line) {
+      break;
+    } else if let Some((_, item_lines)) = items.last_mut() {
+      item_lines.push(line);
+    }
+    length += 1;
+  }
+  (task_list(items, local_ids), length)
+}
+
+fn parse_list(first_marker: ListMarker, lines: &[&str], local_ids: &mut LocalIds) -> (Value, usize) {
+  let kind = first_marker.kind;
+  let start = first_marker.start;
+
+  let mut items: Vec<Vec<&str>> = Vec::new();
+  let mut length = 0;
+  let mut marker = Some(first_marker);
+  while let Some(current) = marker.take() {
+    let mut item_lines = vec![current.content];
+    length += 1;
+    while length < lines.len() {
+      let line = lines[length];
+      if is_blank(line) {
+        // blank lines are part of the item only when it goes on after them
+        let blank_lines = lines[length..].iter().take_while(|x| is_blank(x)).count();
+        let next = lines.get(length + blank_lines);
+        if next.is_none_or(|x| get_indentation(x) < current.content_indentation) {
+          break;
+        }
+        item_lines.extend(std::iter::repeat_n("", blank_lines));
+        length += blank_lines;
+      } else if get_indentation(line) >= current.content_indentation {
+        item_lines.push(&line[current.content_indentation..]);
+        length += 1;
+      } else if item_lines.last().is_some_and(|x| !is_blank(x)) && !interrupts_paragraph(line) && get_list_marker(line).is_none() {
+        // lazy continuation of a paragraph of the item
+        item_lines.push(line.trim_start());
+        length += 1;
+      } else {
+        break;
+      }
+    }
+    items.push(item_lines);
+
+    // the list goes on with the next item of the same kind, possibly after blank lines
+    let next = lines[length..]
+      .iter()
+      .position(|x| !is_blank(x))
+      .map(|x| length + x);
+    if let Some(next) = next {
+      if !is_thematic_break(lines[next]) {
+        marker = get_list_marker(lines[next]).filter(|x| x.kind == kind);
+        if marker.is_some() {
+          length = next;
+        }
+      }
+    }
+  }
+
+  let is_task_list = matches!(kind, ListKind::Bullet(_))
+    && items.iter().all(|x| get_task_item(x[0]).is_some());
+  if is_task_list {
+    let tasks = items
+      .into_iter()
+      .filter_map(|mut x| {
+        let (state, text) = get_task_item(x[0])?;
+        x[0] = text;
+        Some((state, x))
+      })
+      .collect();
+    return (task_list(tasks, local_ids), length);
+  }
+
+  let list_items = items
+    .into_iter()
+    .map(|x| {
+      let mut content = parse_blocks(&x, local_ids);
+      if content.is_empty() {
+        content.push(json!({"type": "paragraph", "content": []}));
+      }
+      json!({"type": "listItem", "content": content})
+    })
+    .collect::<Vec<_>>();
+  let list = match kind {
+    ListKind::Bullet(_) => json!({"type": "bulletList", "content": list_items}),
+    ListKind::Ordered(_) => json!({"type": "orderedList", "attrs": {"order": start}, "content": list_items}),
+  };
+  (list, length)
+}
+
+fn parse_table(lines: &[&str]) -> (Value, usize) {
+  let header = split_table_row(lines[0]);
+  let nr_columns = header.len();
+
+  // rows with too few cells are completed with empty ones, extra cells are ignored
+  let to_row = |cells: Vec<String>, cell_type: &str| {
+    let cells = cells
+      .into_iter()
+      .chain(std::iter::repeat(String::new()))
+      .take(nr_columns)
+      .map(|x| json!({"type": cell_type, "content": [{"type": "paragraph", "content": parse_inline(&x)}]}))
+      .collect::<Vec<_>>();
+    json!({"type": "tableRow", "content": cells})
+  };
+
+  let mut rows = vec![to_row(header, "tableHeader")];
+  let mut length = 2; // header and delimiter rows
+  while length < lines.len() && !is_blank(lines[length]) && !interrupts_paragraph(lines[length]) {
+    rows.push(to_row(split_table_row(lines[length]), "tableCell"));
+    length += 1;
+  }
+  (json!({"type": "table", "content": rows}), length)
+}
+
+fn parse_blocks(lines: &[&str], local_ids: &mut LocalIds) -> Vec<Value> {
+  let mut res = Vec::new();
+  let mut i = 0;
+  while i < lines.len() {
+    let line = lines[i];
+    if is_blank(line) {
+      i += 1;
+      continue;
+    }
+
+    let (block, length) = if let Some(fence) = get_code_fence(line) {
+      parse_fenced_code_block(&fence, &lines[i..])
+    } else if let Some((level, text)) = get_atx_heading(line) {
+      (heading(level, &[text]), 1)
+    } else if is_thematic_break(line) {
+      (json!({"type": "rule"}), 1)
+    } else if get_blockquote_content(line).is_some() {
+      parse_blockquote(&lines[i..], local_ids)
+    } else if get_rendered_task(line).is_some() {
+      parse_rendered_task_list(&lines[i..], local_ids)
+    } else if let Some(marker) = get_list_marker(line) {
+      parse_list(marker, &lines[i..], local_ids)
+    } else if get_indentation(line) >= 4 {
+      parse_indented_code_block(&lines[i..])
+    } else if is_table_start(&lines[i..]) {
+      parse_table(&lines[i..])
+    } else {
+      parse_paragraph(&lines[i..])
+    };
+    res.push(block);
+    i += length;
+  }
+  res
+}
+
+pub(crate) fn markdown_to_adf(markdown: &str) -> Value {
+  let markdown = markdown.replace("\r\n", "\n");
+  let lines = markdown.lines().collect::<Vec<_>>();
+  let content = parse_blocks(&lines, &mut LocalIds::default());
+
+  json!({"type": "doc", "version": 1, "content": content})
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::atlassian_document_format::root_elt_doc_to_string;
+
+  fn doc(content: Value) -> Value {
+    json!({"type": "doc", "version": 1, "content": content})
+  }
+
+  fn text(text: &str) -> Value {
+    json!({"type": "text", "text": text})
+  }
+
+  fn marked_text(text: &str, marks: Value) -> Value {
+    json!({"type": "text", "text": text, "marks": marks})
+  }
+
+  fn paragraph_of(content: Value) -> Value {
+    json!({"type": "paragraph", "content": content})
+  }
+
+  fn list_item_of(text_content: &str) -> Value {
+    json!({"type": "listItem", "content": [paragraph_of(json!([text(text_content)]))]})
+  }
+
+  // ADF -> markdown -> ADF. Strong and emphasis aren't covered since atlassian_document_format.rs
+  // renders them as *strong* and /emphasis/. Rules, tables, panels, medias and mentions aren't
+  // either, their rendering doesn't keep their structure.
+  fn assert_round_trip(adf: Value) {
+    let markdown = root_elt_doc_to_string(&adf);
+    assert_eq!(markdown_to_adf(&markdown), adf, "markdown was:\n{markdown}");
+  }
+
+  #[test]
+  fn round_trip_paragraphs_and_line_breaks() {
+    assert_round_trip(doc(json!([
+      paragraph_of(json!([text("first line"), {"type": "hardBreak"}, text("second line")])),
+      paragraph_of(json!([text("another paragraph")])),
+    ])));
+  }
+
+  #[test]
+  fn round_trip_headings() {
+    let headings = (1..=6)
+      .map(|level| json!({"type": "heading", "attrs": {"level": level}, "content": [text(&format!("heading {level}"))]}))
+      .chain([paragraph_of(json!([text("text")]))])
+      .collect::<Vec<_>>();
+    assert_round_trip(doc(Value::Array(headings)));
+  }
+
+  #[test]
+  fn round_trip_marks() {
+    assert_round_trip(doc(json!([
+      paragraph_of(json!([
+        text("run "),
+        marked_text("cargo build", json!([{"type": "code"}])),
+        text(", see "),
+        marked_text("the docs", json!([{"type": "link", "attrs": {"href": "https://example.com/docs"}}])),
+        text(" or "),
+        marked_text("this", json!([{"type": "code"}, {"type": "link", "attrs": {"href": "https://example.com"}}])),
+        text(" but "),
+        marked_text("not that", json!([{"type": "strike"}])),
+      ])),
+    ])));
+  }
+
+  #[test]
+  fn round_trip_code_blocks() {
+    assert_round_trip(doc(json!([
+      {"type": "codeBlock", "attrs": {"language": "rust"}, "content": [text("fn main() {\n    println!(\"hi\");\n}")]},
+      {"type": "codeBlock", "content": [text("no language")]},
+    ])));
+  }
+
+  #[test]
+  fn round_trip_blockquote() {
+    assert_round_trip(doc(json!([
+      {"type": "blockquote", "content": [
+        paragraph_of(json!([text("quoted")])),
+        paragraph_of(json!([text("twice")])),
+      ]},
+      paragraph_of(json!([text("after")])),
+    ])));
+  }
+
+  #[test]
+  fn round_trip_bullet_lists() {
+    assert_round_trip(doc(json!([
+      {"type": "bulletList", "content": [
+        {"type": "listItem", "content": [
+          paragraph_of(json!([text("first")])),
+          {"type": "bulletList", "content": [list_item_of("nested")]},
+        ]},
+        list_item_of("second"),
+      ]},
+      paragraph_of(json!([text("after")])),
+    ])));
+  }
+
+  #[test]
+  fn round_trip_ordered_lists() {
+    assert_round_trip(doc(json!([
+      paragraph_of(json!([text("steps:")])),
+      {"type": "orderedList", "attrs": {"order": 1}, "content": [list_item_of("first"), list_item_of("second")]},
+    ])));
+    // like in CommonMark, only lists starting at 1 can follow a paragraph without a blank line
+    assert_round_trip(doc(json!([
+      {"type": "orderedList", "attrs": {"order": 3}, "content": [list_item_of("third"), list_item_of("fourth")]},
+    ])));
+  }
+
+  #[test]
+  fn round_trip_task_list() {
+    assert_round_trip(doc(json!([
+      {"type": "taskList", "attrs": {"localId": "1"}, "content": [
+        {"type": "taskItem", "attrs": {"localId": "2", "state": "TODO"}, "content": [text("to do")]},
+        {"type": "taskItem", "attrs": {"localId": "3", "state": "DONE"}, "content": [text("done")]},
+      ]},
+      paragraph_of(json!([text("after")])),
+    ])));
+  }
+
+  #[test]
+  fn emphasis() {
+    let adf = markdown_to_adf("*em* **strong** ***both*** _em_ __strong__ ~~strike~~ snake_case_name 2 * 3");
+    assert_eq!(adf, doc(json!([paragraph_of(json!([
+      marked_text("em", json!([{"type": "em"}])),
+      text(" "),
+      marked_text("strong", json!([{"type": "strong"}])),
+      text(" "),
+      marked_text("both", json!([{"type": "em"}, {"type": "strong"}])),
+      text(" "),
+      marked_text("em", json!([{"type": "em"}])),
+      text(" "),
+      marked_text("strong", json!([{"type": "strong"}])),
+      text(" "),
+      marked_text("strike", json!([{"type": "strike"}])),
+      text(" snake_case_name 2 * 3"),
+    ]))])));
+  }
+
+  #[test]
+  fn nested_marks_and_escapes() {
+    let adf = markdown_to_adf("**see [the `docs`](https://example.com \"title\")** \\*not em\\* <https://example.com/a>");
+    let link = json!({"type": "link", "attrs": {"href": "https://example.com", "title": "title"}});
+    assert_eq!(adf, doc(json!([paragraph_of(json!([
+      marked_text("see ", json!([{"type": "strong"}])),
+      marked_text("the ", json!([link, {"type": "strong"}])),
+      marked_text("docs", json!([{"type": "code"}, link])),
+      text(" *not em* "),
+      marked_text("https://example.com/a", json!([{"type": "link", "attrs": {"href": "https://example.com/a"}}])),
+    ]))])));
+  }
+
+  #[test]
+  fn github_task_list_and_rule() {
+    let adf = markdown_to_adf("- [ ] write\n- [x] test\n\n---\n\n* * *");
+    assert_eq!(adf, doc(json!([
+      {"type": "taskList", "attrs": {"localId": "1"}, "content": [
+        {"type": "taskItem", "attrs": {"localId": "2", "state": "TODO"}, "content": [text("write")]},
+        {"type": "taskItem", "attrs": {"localId": "3", "state": "DONE"}, "content": [text("test")]},
+      ]},
+      {"type": "rule"},
+      {"type": "rule"},
+    ])));
+  }
+
+  #[test]
+  fn table() {
+    let adf = markdown_to_adf("| key | summary |\n|-----|:-------:|\n| PROJ-1 | `a \\| b` |\n| PROJ-2 |");
+    let cell = |cell_type: &str, content: Value| json!({"type": cell_type, "content": [paragraph_of(content)]});
+    assert_eq!(adf, doc(json!([{"type": "table", "content": [
+      {"type": "tableRow", "content": [cell("tableHeader", json!([text("key")])), cell("tableHeader", json!([text("summary")]))]},
+      {"type": "tableRow", "content": [cell("tableCell", json!([text("PROJ-1")])), cell("tableCell", json!([marked_text("a | b", json!([{"type": "code"}]))]))]},
+      {"type": "tableRow", "content": [cell("tableCell", json!([text("PROJ-2")])), cell("tableCell", json!([]))]},
+    ]}])));
+  }
+
+  #[test]
+  fn lists_with_lazy_lines_and_loose_items() {
+    let adf = markdown_to_adf("1) first\ncontinued\n\n2) second\n\n   more\n\nafter");
+    assert_eq!(adf, doc(json!([
+      {"type": "orderedList", "attrs": {"order": 1}, "content": [
+        {"type": "listItem", "content": [paragraph_of(json!([text("first"), {"type": "hardBreak"}, text("continued")]))]},
+        {"type": "listItem", "content": [paragraph_of(json!([text("second")])), paragraph_of(json!([text("more")]))]},
+      ]},
+      paragraph_of(json!([text("after")])),
+    ])));
+  }
+
+  #[test]
+  fn code_blocks() {
+    let adf = markdown_to_adf("~~~ python extra\nprint(1)\n~~~\n\n    indented\n\n    code\n\ntext\n```\nnot closed");
+    assert_eq!(adf, doc(json!([
+      {"type": "codeBlock", "attrs": {"language": "python"}, "content": [text("print(1)")]},
+      {"type": "codeBlock", "content": [text("indented\n\ncode")]},
+      paragraph_of(json!([text("text")])),
+      {"type": "codeBlock", "content": [text("not closed")]},
+    ])));
+  }
+
+  #[test]
+  fn headings_and_quotes() {
+    let adf = markdown_to_adf("## Title ##\nSub title\n---------\n> quoted\nlazy\n> # heading in quote");
+    assert_eq!(adf, doc(json!([
+      {"type": "heading", "attrs": {"level": 2}, "content": [text("Title")]},
+      {"type": "heading", "attrs": {"level": 2}, "content": [text("Sub title")]},
+      {"type": "blockquote", "content": [
+        paragraph_of(json!([text("quoted"), {"type": "hardBreak"}, text("lazy")])),
+        {"type": "heading", "attrs": {"level": 1}, "content": [text("heading in quote")]},
+      ]},
+    ])));
+  }
+}
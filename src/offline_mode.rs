@@ -0,0 +1,30 @@
+use std::sync::OnceLock;
+use tokio::sync::watch;
+
+// In offline mode, requests are answered from the local database only and the background
+// synchronisation pauses. This avoids errors and long timeouts when the jira server isn't
+// reachable (trains, flights, ...). The mode is shared by all connections of the server.
+fn get_offline_mode() -> &'static watch::Sender<bool> {
+  static OFFLINE_MODE: OnceLock<watch::Sender<bool>> = OnceLock::new();
+  OFFLINE_MODE.get_or_init(|| {
+    let (sender, _) = watch::channel(false);
+    sender
+  })
+}
+
+pub(crate) fn is_offline() -> bool {
+  *get_offline_mode().borrow()
+}
+
+pub(crate) fn set_offline(offline: bool) {
+  let was_offline = get_offline_mode().send_replace(offline);
+  if was_offline != offline {
+    eprintln!("Switched offline mode {state}", state = if offline { "on" } else { "off" });
+  }
+}
+
+pub(crate) async fn wait_until_online() {
+  let mut receiver = get_offline_mode().subscribe();
+  // the sender lives in a static, it is never dropped.
+  let _ = receiver.wait_for(|offline| !offline).await;
+}
@@ -1,49 +1,132 @@
 use std::{io, sync, thread};
+use std::collections::HashMap;
 use std::fmt::format;
-use std::io::{ErrorKind, Read, read_to_string};
+use std::io::{ErrorKind, IsTerminal, Read, read_to_string, Write};
 use std::ptr::{addr_of_mut, read};
 use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use base64::Engine;
 use sqlx::{Pool, Sqlite};
-use tokio::task::JoinSet;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::task::{AbortHandle, JoinSet};
 use tokio::time::sleep;
 use crate::find_issues_that_need_updating::update_interesting_projects_in_db;
 use crate::get_config::Config;
+use crate::sync_progress::{ProgressReporter, SyncProgress};
+use crate::ticket_events::TicketChanges;
+use crate::http_api::serve_http_api;
+use crate::json_protocol::{reply_to_json_line, request_from_json_line};
+use crate::manage_boards_and_sprints::update_boards_and_sprints_in_db;
+use crate::manage_create_metadata::update_create_metadata_in_db;
+use crate::manage_deleted_issues::reconcile_interesting_projects_in_db;
 use crate::manage_field_table::update_fields_in_db;
 use crate::manage_interesting_projects::initialise_interesting_projects_in_db;
+use crate::manage_issue_watchers::update_current_user_in_db;
 use crate::manage_issuelinktype_table::update_issue_link_types_in_db;
 use crate::manage_issuetype_table::update_issue_types_in_db;
 use crate::manage_project_table::update_project_list_in_db;
+use crate::manage_status_priority_resolution_tables::update_statuses_priorities_and_resolutions_in_db;
+use crate::manage_versions_and_components::update_versions_and_components_in_db;
+use crate::offline_mode::{is_offline, set_offline, wait_until_online};
 use crate::server::RequestKind::Push_error_message;
 use crate::srv_fetch_attachment_content::serve_fetch_attachment_content;
 use crate::srv_fetch_attachment_list_for_ticket::serve_fetch_ticket_attachment_list;
 use crate::srv_fetch_ticket::serve_fetch_ticket_request;
 use crate::srv_fetch_ticket_key_value_list::serve_fetch_ticket_key_value_fields;
 use crate::srv_fetch_ticket_list::serve_fetch_ticket_list_request;
+use crate::srv_fetch_tickets_key_value_fields::serve_fetch_tickets_key_value_fields;
+use crate::srv_capabilities::{serve_capabilities, serve_hello};
+use crate::srv_offline_mode::{serve_modification_while_offline, serve_set_offline_mode, serve_synchronise_while_offline};
+use crate::srv_fetch_ticket_history::serve_fetch_ticket_history;
+use crate::srv_fetch_logged_time::serve_fetch_logged_time;
+use crate::srv_fetch_board_list::serve_fetch_board_list;
+use crate::srv_fetch_board_sprints::serve_fetch_board_sprints;
+use crate::srv_fetch_sprint_tickets::serve_fetch_sprint_tickets;
+use crate::srv_fetch_project_versions::serve_fetch_project_versions;
+use crate::srv_fetch_version_tickets::serve_fetch_version_tickets;
+use crate::srv_fetch_project_components::serve_fetch_project_components;
+use crate::srv_fetch_component_tickets::serve_fetch_component_tickets;
+use crate::srv_fetch_ticket_status_list::serve_fetch_ticket_status_list;
+use crate::srv_fetch_watched_tickets::serve_fetch_watched_tickets;
+use crate::srv_fetch_ticket_watchers::serve_fetch_ticket_watchers;
+use crate::srv_fetch_ticket_voters::serve_fetch_ticket_voters;
+use crate::srv_fetch_ticket_tree::serve_fetch_ticket_tree;
+use crate::srv_add_comment::{serve_add_comment, AddCommentParams};
+use crate::srv_edit_field::{serve_edit_field, EditFieldParams};
+use crate::srv_fetch_transitions::serve_fetch_transitions;
+use crate::srv_transition::{serve_transition, TransitionParams};
+use crate::srv_create_issue::{serve_create_issue, CreateIssueParams};
+use crate::srv_subscribe::serve_subscribe;
 use crate::srv_synchronise_all::serve_synchronise_all;
 use crate::srv_synchronise_ticket::serve_synchronise_ticket;
 use crate::srv_synchronise_updated::serve_synchronise_updated_tickets;
 
 
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub(crate) enum Protocol {
+  Text,
+  JsonLines,
+}
+
+impl Protocol {
+  fn try_new(protocol: &str) -> Result<Self, String> {
+    match protocol {
+      "TEXT" => Ok(Protocol::Text),
+      "JSON_LINES" => Ok(Protocol::JsonLines),
+      _ => Err(format!("Unknown protocol. Supported: TEXT and JSON_LINES. Requested: {protocol}"))
+    }
+  }
+}
+
 #[derive(Eq, PartialEq)]
-enum RequestKind {
+pub(crate) enum RequestKind {
+  Hello,
+  Capabilities,
   Fetch_Ticket(String /* issue key */),
   Fetch_Ticket_List,
+  Fetch_Ticket_History(String /* issue key */),
   Fetch_Ticket_Key_Value_Fields(String /* issue key */),
+  Fetch_Tickets_Key_Value_Fields(Vec<String> /* issue keys */),
   Fetch_Attachment_List_For_Ticket(String /* issue key */),
   Fetch_Attachment_Content(String /* attachment uuid */),
+  Fetch_Logged_Time(String /* first and last days */),
+  Fetch_Board_List,
+  Fetch_Board_Sprints(String /* board id */),
+  Fetch_Sprint_Tickets(String /* sprint id */),
+  Fetch_Project_Versions(String /* project key */),
+  Fetch_Version_Tickets(String /* version id */),
+  Fetch_Project_Components(String /* project key */),
+  Fetch_Component_Tickets(String /* component id */),
+  Fetch_Ticket_Status_List,
+  Fetch_Watched_Tickets,
+  Fetch_Ticket_Watchers(String /* issue key */),
+  Fetch_Ticket_Voters(String /* issue key */),
+  Fetch_Ticket_Tree(String /* issue key */),
+  Add_Comment(AddCommentParams),
+  Edit_Field(EditFieldParams),
+  Fetch_Transitions(String /* issue key */),
+  Transition(TransitionParams),
+  Create_Issue(CreateIssueParams),
   Synchronise_Ticket(String /* issue key */),
   Synchronise_Updated,
   Synchronise_All,
+  Subscribe,
+  Set_Offline_Mode(bool /* is offline */),
   Exit_Server_After_Requests,
   Exit_Server_Now,
+  Set_Protocol(Protocol),
+  Cancel(String /* request id */),
   Push_error_message(String),
 }
 
-struct Request {
-  request_id: String,
-  request_kind: RequestKind,
+pub(crate) struct Request {
+  pub(crate) request_id: String,
+  pub(crate) request_kind: RequestKind,
 }
 
 fn is_valid_request_id(candidate: &str) -> bool {
@@ -57,7 +140,7 @@ fn is_valid_request_id(candidate: &str) -> bool {
   }
 }
 
-fn is_valid_issue_key(candidate: &str) -> bool {
+pub(crate) fn is_valid_issue_key(candidate: &str) -> bool {
   // checks that candidate looks like PROJ-123
 
   let chunks = candidate
@@ -67,13 +150,19 @@ fn is_valid_issue_key(candidate: &str) -> bool {
     return false;
   }
 
-  // ensures first part is all uppercase
+  // ensures first part is uppercase letters (project keys may also contain digits and
+  // underscores, but must start with a letter)
   let is_likely_jira_proj = chunks[0]
     .chars()
-    .all(|x| x.is_ascii_uppercase());
+    .next()
+    .is_some_and(|x| x.is_ascii_uppercase())
+    && chunks[0]
+    .chars()
+    .all(|x| x.is_ascii_uppercase() || x.is_ascii_digit() || (x == '_'));
 
-  // ensures first part is all digits
-  let is_likely_ticket_number = chunks[1]
+  // ensures second part is all digits
+  let is_likely_ticket_number = !chunks[1].is_empty()
+    && chunks[1]
     .chars()
     .all(|x| x.is_ascii_digit());
 
@@ -95,6 +184,21 @@ impl Request {
     let command = chunks[1];
     let command_parameter = if nr_chunks == 2 { None } else { Some(chunks[2]) };
 
+    Request::new(candidate_request_id, command, command_parameter)
+  }
+
+  // for requests whose parameters were already parsed, e.g. from the json-lines protocol
+  pub(crate) fn from_kind(candidate_request_id: &str, request_kind: RequestKind) -> Result<Request, String> {
+    if !is_valid_request_id(candidate_request_id) {
+      return Err(String::from("Invalid request. Request id should only contain ascii alphanum characters or dashed"));
+    }
+    Ok(Request {
+      request_id: candidate_request_id.to_string(),
+      request_kind,
+    })
+  }
+
+  pub(crate) fn new(candidate_request_id: &str, command: &str, command_parameter: Option<&str>) -> Result<Request, String> {
     if !is_valid_request_id(candidate_request_id) {
       return Err(String::from("Invalid request. Request id should only contain ascii alphanum characters or dashed"));
     }
@@ -114,6 +218,32 @@ impl Request {
           }
         }
       },
+      "HELLO" => {
+        match command_parameter {
+          None => {
+            Ok(Request {
+              request_id,
+              request_kind: RequestKind::Hello,
+            })
+          },
+          Some(command_parameter) => {
+            Err(format!("Invalid request. Hello doesn't take parameter, but given [{command_parameter}]"))
+          }
+        }
+      },
+      "CAPABILITIES" => {
+        match command_parameter {
+          None => {
+            Ok(Request {
+              request_id,
+              request_kind: RequestKind::Capabilities,
+            })
+          },
+          Some(command_parameter) => {
+            Err(format!("Invalid request. Capabilities doesn't take parameter, but given [{command_parameter}]"))
+          }
+        }
+      },
       "FETCH_TICKET_LIST" => {
         match command_parameter {
           None => {
@@ -127,6 +257,19 @@ impl Request {
           }
         }
       },
+      "FETCH_TICKET_HISTORY" => {
+        match command_parameter {
+          None => {
+            Err(String::from("Invalid request. Fetch_Ticket_History takes a jira issue key as parameter. Something like PROJ-123"))
+          },
+          Some(command_parameter) => {
+            Ok(Request{
+              request_id,
+              request_kind: RequestKind::Fetch_Ticket_History(command_parameter.to_string()),
+            })
+          }
+        }
+      },
       "FETCH_TICKET_KEY_VALUE_FIELDS" => {
         match command_parameter {
           None => {
@@ -140,6 +283,30 @@ impl Request {
           }
         }
       },
+      "FETCH_TICKETS_KEY_VALUE_FIELDS" => {
+        match command_parameter {
+          None => {
+            Err(String::from("Invalid request. Fetch_Tickets_Key_Value_Fields takes a comma separated list of jira issue keys as parameter. Something like PROJ-123,PROJ-456"))
+          },
+          Some(command_parameter) => {
+            let issue_keys = command_parameter
+              .split(',')
+              .map(|x| x.to_string())
+              .collect::<Vec<_>>();
+            match issue_keys.iter().find(|x| !is_valid_issue_key(x)) {
+              Some(invalid_key) => {
+                Err(format!("Invalid request. Fetch_Tickets_Key_Value_Fields takes jira issue keys like PROJ-123. Got [{invalid_key}]"))
+              }
+              None => {
+                Ok(Request {
+                  request_id,
+                  request_kind: RequestKind::Fetch_Tickets_Key_Value_Fields(issue_keys),
+                })
+              }
+            }
+          }
+        }
+      },
       "FETCH_ATTACHMENT_LIST_FOR_TICKET" => {
         match command_parameter {
           None => {
@@ -153,6 +320,240 @@ impl Request {
           }
         }
       }
+      "FETCH_LOGGED_TIME" => {
+        match command_parameter {
+          None => {
+            Err(String::from("Invalid request. Fetch_Logged_Time takes the first and last days of the report as parameters. Something like 2024-01-29,2024-02-04"))
+          },
+          Some(command_parameter) => {
+            Ok(Request{
+              request_id,
+              request_kind: RequestKind::Fetch_Logged_Time(command_parameter.to_string()),
+            })
+          }
+        }
+      },
+      "FETCH_BOARD_LIST" => {
+        match command_parameter {
+          None => {
+            Ok(Request {
+              request_id,
+              request_kind: RequestKind::Fetch_Board_List,
+            })
+          },
+          Some(command_parameter) => {
+            Err(format!("Invalid request. Fetch_Board_List doesn't take parameter. Got [{command_parameter}]"))
+          }
+        }
+      },
+      "FETCH_BOARD_SPRINTS" => {
+        match command_parameter {
+          None => {
+            Err(String::from("Invalid request. Fetch_Board_Sprints takes a board id as parameter. Something like 12"))
+          },
+          Some(command_parameter) => {
+            Ok(Request{
+              request_id,
+              request_kind: RequestKind::Fetch_Board_Sprints(command_parameter.to_string()),
+            })
+          }
+        }
+      },
+      "FETCH_SPRINT_TICKETS" => {
+        match command_parameter {
+          None => {
+            Err(String::from("Invalid request. Fetch_Sprint_Tickets takes a sprint id as parameter. Something like 345"))
+          },
+          Some(command_parameter) => {
+            Ok(Request{
+              request_id,
+              request_kind: RequestKind::Fetch_Sprint_Tickets(command_parameter.to_string()),
+            })
+          }
+        }
+      },
+      "FETCH_PROJECT_VERSIONS" => {
+        match command_parameter {
+          None => {
+            Err(String::from("Invalid request. Fetch_Project_Versions takes a jira project key as parameter. Something like PROJ"))
+          },
+          Some(command_parameter) => {
+            Ok(Request{
+              request_id,
+              request_kind: RequestKind::Fetch_Project_Versions(command_parameter.to_string()),
+            })
+          }
+        }
+      },
+      "FETCH_VERSION_TICKETS" => {
+        match command_parameter {
+          None => {
+            Err(String::from("Invalid request. Fetch_Version_Tickets takes a version id as parameter. Something like 10042"))
+          },
+          Some(command_parameter) => {
+            Ok(Request{
+              request_id,
+              request_kind: RequestKind::Fetch_Version_Tickets(command_parameter.to_string()),
+            })
+          }
+        }
+      },
+      "FETCH_PROJECT_COMPONENTS" => {
+        match command_parameter {
+          None => {
+            Err(String::from("Invalid request. Fetch_Project_Components takes a jira project key as parameter. Something like PROJ"))
+          },
+          Some(command_parameter) => {
+            Ok(Request{
+              request_id,
+              request_kind: RequestKind::Fetch_Project_Components(command_parameter.to_string()),
+            })
+          }
+        }
+      },
+      "FETCH_COMPONENT_TICKETS" => {
+        match command_parameter {
+          None => {
+            Err(String::from("Invalid request. Fetch_Component_Tickets takes a component id as parameter. Something like 10007"))
+          },
+          Some(command_parameter) => {
+            Ok(Request{
+              request_id,
+              request_kind: RequestKind::Fetch_Component_Tickets(command_parameter.to_string()),
+            })
+          }
+        }
+      },
+      "FETCH_TICKET_STATUS_LIST" => {
+        match command_parameter {
+          None => {
+            Ok(Request {
+              request_id,
+              request_kind: RequestKind::Fetch_Ticket_Status_List,
+            })
+          },
+          Some(command_parameter) => {
+            Err(format!("Invalid request. Fetch_Ticket_Status_List doesn't take parameter. Got [{command_parameter}]"))
+          }
+        }
+      },
+      "FETCH_WATCHED_TICKETS" => {
+        match command_parameter {
+          None => {
+            Ok(Request {
+              request_id,
+              request_kind: RequestKind::Fetch_Watched_Tickets,
+            })
+          },
+          Some(command_parameter) => {
+            Err(format!("Invalid request. Fetch_Watched_Tickets doesn't take parameter. Got [{command_parameter}]"))
+          }
+        }
+      },
+      "FETCH_TICKET_WATCHERS" => {
+        match command_parameter {
+          None => {
+            Err(String::from("Invalid request. Fetch_Ticket_Watchers takes a jira issue key as parameter. Something like PROJ-123"))
+          },
+          Some(command_parameter) => {
+            Ok(Request{
+              request_id,
+              request_kind: RequestKind::Fetch_Ticket_Watchers(command_parameter.to_string()),
+            })
+          }
+        }
+      },
+      "FETCH_TICKET_VOTERS" => {
+        match command_parameter {
+          None => {
+            Err(String::from("Invalid request. Fetch_Ticket_Voters takes a jira issue key as parameter. Something like PROJ-123"))
+          },
+          Some(command_parameter) => {
+            Ok(Request{
+              request_id,
+              request_kind: RequestKind::Fetch_Ticket_Voters(command_parameter.to_string()),
+            })
+          }
+        }
+      },
+      "FETCH_TICKET_TREE" => {
+        match command_parameter {
+          None => {
+            Err(String::from("Invalid request. Fetch_Ticket_Tree takes a jira issue key as parameter. Something like PROJ-123"))
+          },
+          Some(command_parameter) => {
+            Ok(Request{
+              request_id,
+              request_kind: RequestKind::Fetch_Ticket_Tree(command_parameter.to_string()),
+            })
+          }
+        }
+      },
+      "ADD_COMMENT" => {
+        match command_parameter {
+          None => {
+            Err(String::from("Invalid request. Add_Comment takes a jira issue key and a base64 encoded markdown comment as parameters. Something like PROJ-123,SGVsbG8="))
+          },
+          Some(command_parameter) => {
+            Ok(Request{
+              request_id,
+              request_kind: RequestKind::Add_Comment(AddCommentParams::from_text_params(command_parameter)?),
+            })
+          }
+        }
+      },
+      "EDIT_FIELD" => {
+        match command_parameter {
+          None => {
+            Err(String::from("Invalid request. Edit_Field takes a jira issue key, a field and a base64 encoded value as parameters. Something like PROJ-123,summary,TmV3IHN1bW1hcnk="))
+          },
+          Some(command_parameter) => {
+            Ok(Request{
+              request_id,
+              request_kind: RequestKind::Edit_Field(EditFieldParams::from_text_params(command_parameter)?),
+            })
+          }
+        }
+      },
+      "FETCH_TRANSITIONS" => {
+        match command_parameter {
+          None => {
+            Err(String::from("Invalid request. Fetch_Transitions takes a jira issue key as parameter. Something like PROJ-123"))
+          },
+          Some(command_parameter) => {
+            Ok(Request{
+              request_id,
+              request_kind: RequestKind::Fetch_Transitions(command_parameter.to_string()),
+            })
+          }
+        }
+      },
+      "TRANSITION" => {
+        match command_parameter {
+          None => {
+            Err(String::from("Invalid request. Transition takes a jira issue key, a transition and optionally the fields of the transition screen as parameters. Something like PROJ-123,Done,resolution:RG9uZQ=="))
+          },
+          Some(command_parameter) => {
+            Ok(Request{
+              request_id,
+              request_kind: RequestKind::Transition(TransitionParams::from_text_params(command_parameter)?),
+            })
+          }
+        }
+      },
+      "CREATE_ISSUE" => {
+        match command_parameter {
+          None => {
+            Err(String::from("Invalid request. Create_Issue takes a project, an issue type, a base64 encoded summary and a base64 encoded markdown description as parameters, optionally followed by other fields. Something like PROJ,Bug,U3VtbWFyeQ==,"))
+          },
+          Some(command_parameter) => {
+            Ok(Request{
+              request_id,
+              request_kind: RequestKind::Create_Issue(CreateIssueParams::from_text_params(command_parameter)?),
+            })
+          }
+        }
+      },
       "FETCH_ATTACHMENT_CONTENT" => {
         match command_parameter {
           None => {
@@ -197,37 +598,99 @@ impl Request {
           None => {
             Ok(Request {
               request_id,
-              request_kind: RequestKind::Synchronise_All,
+              request_kind: RequestKind::Synchronise_All,
+            })
+          },
+          Some(command_parameter) => {
+            Err(format!("Invalid request. Synchronise_All doesn't take parameter. Got [{command_parameter}]"))
+          }
+        }
+      }
+      "SUBSCRIBE" => {
+        match command_parameter {
+          None => {
+            Ok(Request {
+              request_id,
+              request_kind: RequestKind::Subscribe,
+            })
+          },
+          Some(command_parameter) => {
+            Err(format!("Invalid request. Subscribe doesn't take parameter. Got [{command_parameter}]"))
+          }
+        }
+      }
+      "EXIT_SERVER_AFTER_REQUESTS" => {
+        match command_parameter {
+          None => {
+            Ok(Request {
+              request_id,
+              request_kind: RequestKind::Exit_Server_After_Requests,
+            })
+          },
+          Some(command_parameter) => {
+            Err(format!("Invalid request. Exit_Server_After_Requests doesn't take parameter. Got [{command_parameter}]"))
+          }
+        }
+      }
+      "EXIT_SERVER_NOW" => {
+        match command_parameter {
+          None => {
+            Ok(Request {
+              request_id,
+              request_kind: RequestKind::Exit_Server_Now,
+            })
+          },
+          Some(command_parameter) => {
+            Err(format!("Invalid request. Exit_Server_Now doesn't take parameter. Got [{command_parameter}]"))
+          }
+        }
+      }
+      "SET_OFFLINE_MODE" => {
+        match command_parameter {
+          Some("ON") => {
+            Ok(Request {
+              request_id,
+              request_kind: RequestKind::Set_Offline_Mode(true),
+            })
+          },
+          Some("OFF") => {
+            Ok(Request {
+              request_id,
+              request_kind: RequestKind::Set_Offline_Mode(false),
             })
           },
-          Some(command_parameter) => {
-            Err(format!("Invalid request. Synchronise_All doesn't take parameter. Got [{command_parameter}]"))
+          _ => {
+            Err(String::from("Invalid request. Set_Offline_Mode takes either ON or OFF as parameter"))
           }
         }
       }
-      "EXIT_SERVER_AFTER_REQUESTS" => {
+      "SET_PROTOCOL" => {
         match command_parameter {
           None => {
+            Err(String::from("Invalid request. Set_Protocol takes the protocol to use as parameter. Either TEXT or JSON_LINES"))
+          },
+          Some(command_parameter) => {
+            let protocol = Protocol::try_new(command_parameter)?;
             Ok(Request {
               request_id,
-              request_kind: RequestKind::Exit_Server_After_Requests,
+              request_kind: RequestKind::Set_Protocol(protocol),
             })
-          },
-          Some(command_parameter) => {
-            Err(format!("Invalid request. Exit_Server_After_Requests doesn't take parameter. Got [{command_parameter}]"))
           }
         }
       }
-      "EXIT_SERVER_NOW" => {
+      "CANCEL" => {
         match command_parameter {
           None => {
+            Err(String::from("Invalid request. Cancel takes the id of the request to cancel as parameter"))
+          },
+          Some(command_parameter) if !is_valid_request_id(command_parameter) => {
+            Err(format!("Invalid request. Cancel takes a request id as parameter. Got [{command_parameter}]"))
+          },
+          Some(command_parameter) => {
             Ok(Request {
               request_id,
-              request_kind: RequestKind::Exit_Server_Now,
+              request_kind: RequestKind::Cancel(command_parameter.to_string()),
             })
-          },
-          Some(command_parameter) => {
-            Err(format!("Invalid request. Exit_Server_Now doesn't take parameter. Got [{command_parameter}]"))
           }
         }
       }
@@ -236,23 +699,518 @@ impl Request {
   }
 }
 
-pub(crate) struct Reply(pub String);
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum ErrorCode {
+  InvalidRequest,
+  InvalidParameters,
+  LocalDatabase,
+  RemoteServer,
+  EventsLost,
+  Offline,
+}
+
+impl ErrorCode {
+  pub(crate) fn as_str(&self) -> &'static str {
+    match self {
+      ErrorCode::InvalidRequest => "INVALID_REQUEST",
+      ErrorCode::InvalidParameters => "INVALID_PARAMETERS",
+      ErrorCode::LocalDatabase => "LOCAL_DATABASE",
+      ErrorCode::RemoteServer => "REMOTE_SERVER",
+      ErrorCode::EventsLost => "EVENTS_LOST",
+      ErrorCode::Offline => "OFFLINE",
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub(crate) struct KeyValueField {
+  pub(crate) key: String,   // jira field id, like customfield_12345
+  pub(crate) name: String,  // human name, like Summary
+  pub(crate) value: String, // json encoded
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct AttachmentName {
+  pub(crate) uuid: String,
+  pub(crate) filename: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct HistoryItem {
+  pub(crate) author: Option<String>, // display name, none for changes made by jira itself
+  pub(crate) created: String,        // as given by jira, like 2024-01-31T13:37:00.000+0100
+  pub(crate) field: String,          // like status
+  pub(crate) from: Option<String>,   // human readable value before the change
+  pub(crate) to: Option<String>,     // human readable value after the change
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct LoggedTime {
+  pub(crate) author: String, // display name
+  pub(crate) key: String,    // issue key
+  pub(crate) day: String,    // like 2024-01-31
+  pub(crate) seconds: i64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Board {
+  pub(crate) id: i64,
+  pub(crate) name: String,
+  pub(crate) board_type: String,  // scrum, kanban or simple
+  pub(crate) project_key: String, // interesting project the board was found in
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Sprint {
+  pub(crate) id: i64,
+  pub(crate) name: String,
+  pub(crate) state: String,                 // future, active or closed
+  pub(crate) start_date: Option<String>,    // as given by jira, like 2024-01-29T09:00:00.000Z
+  pub(crate) end_date: Option<String>,
+  pub(crate) complete_date: Option<String>, // only for closed sprints
+  pub(crate) goal: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct ProjectVersion {
+  pub(crate) id: i64,
+  pub(crate) name: String,                 // like 4.2
+  pub(crate) description: Option<String>,
+  pub(crate) is_released: bool,
+  pub(crate) is_archived: bool,
+  pub(crate) release_date: Option<String>, // like 2024-01-31
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct ProjectComponent {
+  pub(crate) id: i64,
+  pub(crate) name: String,
+  pub(crate) description: Option<String>,
+  pub(crate) lead: Option<String>, // display name
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct TicketTreeNode {
+  pub(crate) key: String,
+  pub(crate) parent: Option<String>, // issue key, none for the root of the tree
+  pub(crate) depth: i64, // 0 for the root of the tree
+  pub(crate) summary: Option<String>,
+  pub(crate) status: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Transition {
+  pub(crate) id: i64,
+  pub(crate) name: String,                 // like Start progress
+  pub(crate) to_status: Option<String>,    // name of the status the ticket moves to
+  pub(crate) required_fields: Vec<String>, // field ids, like resolution
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Person {
+  pub(crate) account_id: String,
+  pub(crate) display_name: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct TicketStatus {
+  pub(crate) key: String,
+  pub(crate) status: Option<String>,
+  pub(crate) status_category: Option<String>, // new, indeterminate or done
+  pub(crate) priority: Option<String>,
+  pub(crate) resolution: Option<String>,
+}
+
+pub(crate) struct Capabilities {
+  pub(crate) protocol_version: u32,
+  pub(crate) commands: Vec<(&'static str /* name */, &'static [&'static str] /* parameter names */)>,
+  pub(crate) ticket_formats: Vec<&'static str>,
+  pub(crate) features: Vec<&'static str>,
+}
+
+pub(crate) enum ResultData {
+  Hello { server_name: String, server_version: String, protocol_version: u32 },
+  Capabilities(Capabilities),
+  Ticket(String),
+  TicketList(Vec<String>),
+  DeletedTickets(Vec<String> /* keys removed from the local database */),
+  TicketHistory(Vec<HistoryItem>),
+  KeyValueFields(Vec<KeyValueField>),
+  TicketsKeyValueFields(Vec<(String /* issue key */, Vec<KeyValueField>)>),
+  AttachmentList(Vec<AttachmentName>),
+  AttachmentContent { mime_type: Option<String>, content: Vec<u8> },
+  LoggedTime(Vec<LoggedTime>),
+  BoardList(Vec<Board>),
+  SprintList(Vec<Sprint>),
+  VersionList(Vec<ProjectVersion>),
+  ComponentList(Vec<ProjectComponent>),
+  TicketStatusList(Vec<TicketStatus>),
+  PersonList(Vec<Person>),
+  TicketTree(Vec<TicketTreeNode>),
+  CommentId(i64),
+  TransitionList(Vec<Transition>),
+  CreatedTicketKey(String),
+}
+
+pub(crate) enum ReplyKind {
+  Ack,
+  Result(ResultData),
+  Error(ErrorCode, String),
+  Finished,
+  Cancelled,
+  Progress(SyncProgress),
+  Event(TicketChanges),
+  Stale(Option<i64> /* last synchronisation, in seconds since epoch */),
+}
+
+pub(crate) struct Reply {
+  pub(crate) request_id: String,
+  pub(crate) kind: ReplyKind,
+}
+
+impl Reply {
+  pub(crate) fn ack(request_id: &str) -> Reply {
+    Reply { request_id: request_id.to_string(), kind: ReplyKind::Ack }
+  }
+
+  pub(crate) fn result(request_id: &str, data: ResultData) -> Reply {
+    Reply { request_id: request_id.to_string(), kind: ReplyKind::Result(data) }
+  }
+
+  pub(crate) fn error(request_id: &str, code: ErrorCode, message: String) -> Reply {
+    Reply { request_id: request_id.to_string(), kind: ReplyKind::Error(code, message) }
+  }
+
+  pub(crate) fn finished(request_id: &str) -> Reply {
+    Reply { request_id: request_id.to_string(), kind: ReplyKind::Finished }
+  }
+
+  pub(crate) fn cancelled(request_id: &str) -> Reply {
+    Reply { request_id: request_id.to_string(), kind: ReplyKind::Cancelled }
+  }
+
+  pub(crate) fn progress(request_id: &str, progress: SyncProgress) -> Reply {
+    Reply { request_id: request_id.to_string(), kind: ReplyKind::Progress(progress) }
+  }
+
+  pub(crate) fn event(request_id: &str, changes: TicketChanges) -> Reply {
+    Reply { request_id: request_id.to_string(), kind: ReplyKind::Event(changes) }
+  }
+
+  pub(crate) fn stale(request_id: &str, last_synchronisation: Option<i64>) -> Reply {
+    Reply { request_id: request_id.to_string(), kind: ReplyKind::Stale(last_synchronisation) }
+  }
+
+  fn to_text_line(&self) -> String {
+    let request_id = self.request_id.as_str();
+    let (status, data) = match &self.kind {
+      ReplyKind::Ack => ("ACK", String::new()),
+      ReplyKind::Result(data) => ("RESULT", result_data_to_text(data)),
+      ReplyKind::Error(_, message) => ("ERROR", message.clone()),
+      ReplyKind::Finished => ("FINISHED", String::new()),
+      ReplyKind::Cancelled => ("CANCELLED", String::new()),
+      ReplyKind::Progress(progress) => ("PROGRESS", progress_to_text(progress)),
+      ReplyKind::Event(changes) => ("EVENT", ticket_changes_to_text(changes)),
+      ReplyKind::Stale(last_synchronisation) => {
+        ("STALE", last_synchronisation.map(|x| x.to_string()).unwrap_or_default())
+      }
+    };
+
+    if data.is_empty() {
+      format!("{request_id} {status}\n")
+    } else {
+      format!("{request_id} {status} {data}\n")
+    }
+  }
+
+  pub(crate) fn encode(&self, protocol: Protocol) -> String {
+    match protocol {
+      Protocol::Text => self.to_text_line(),
+      Protocol::JsonLines => reply_to_json_line(self),
+    }
+  }
+}
+
+fn progress_to_text(progress: &SyncProgress) -> String {
+  let SyncProgress { project_key, pages_fetched, pages_total, tickets_detailed, tickets_total } = progress;
+  format!("{project_key},{pages_fetched},{pages_total},{tickets_detailed},{tickets_total}")
+}
+
+fn ticket_changes_to_text(changes: &TicketChanges) -> String {
+  let TicketChanges { created, updated, deleted } = changes;
+  let updated = updated
+    .iter()
+    .map(|x| {
+      let mut key_and_fields = vec![x.key.as_str()];
+      key_and_fields.extend(x.fields.iter().map(|x| x.as_str()));
+      key_and_fields.join(":")
+    })
+    .collect::<Vec<_>>();
+  format!("created={created};updated={updated};deleted={deleted}",
+          created = created.join(","),
+          updated = updated.join(","),
+          deleted = deleted.join(","))
+}
+
+fn key_value_fields_to_text(fields: &[KeyValueField]) -> String {
+  let b64 = |x: &[u8]| base64::engine::general_purpose::STANDARD.encode(x);
+  fields
+    .iter()
+    .map(|x| format!("{key}:{value}", key = b64(x.name.as_bytes()), value = b64(x.value.as_bytes())))
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+fn result_data_to_text(data: &ResultData) -> String {
+  let b64 = |x: &[u8]| base64::engine::general_purpose::STANDARD.encode(x);
+  match data {
+    ResultData::Ticket(ticket) => { b64(ticket.as_bytes()) }
+    ResultData::TicketList(keys) => { keys.join(",") }
+    ResultData::DeletedTickets(keys) => { format!("deleted={keys}", keys = keys.join(",")) }
+    ResultData::TicketHistory(history) => {
+      let b64_or_empty = |x: &Option<String>| x.as_deref().map(|x| b64(x.as_bytes())).unwrap_or_default();
+      history
+        .iter()
+        .map(|x| format!("{author}:{created}:{field}:{from}:{to}",
+                         author = b64_or_empty(&x.author),
+                         created = b64(x.created.as_bytes()),
+                         field = b64(x.field.as_bytes()),
+                         from = b64_or_empty(&x.from),
+                         to = b64_or_empty(&x.to)))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+    ResultData::KeyValueFields(fields) => { key_value_fields_to_text(fields) }
+    ResultData::TicketsKeyValueFields(tickets) => {
+      tickets
+        .iter()
+        .map(|(key, fields)| format!("{key}|{fields}", fields = key_value_fields_to_text(fields)))
+        .collect::<Vec<_>>()
+        .join(";")
+    }
+    ResultData::AttachmentList(attachments) => {
+      attachments
+        .iter()
+        .map(|x| format!("{uuid}:{filename}", uuid = x.uuid, filename = b64(x.filename.as_bytes())))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+    ResultData::AttachmentContent { content, .. } => { b64(content.as_slice()) }
+    ResultData::LoggedTime(logged_time) => {
+      logged_time
+        .iter()
+        .map(|x| format!("{author}:{key}:{day}:{seconds}", author = b64(x.author.as_bytes()), key = x.key, day = x.day, seconds = x.seconds))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+    ResultData::BoardList(boards) => {
+      boards
+        .iter()
+        .map(|x| format!("{id}:{name}:{board_type}:{project_key}",
+                         id = x.id, name = b64(x.name.as_bytes()), board_type = x.board_type, project_key = x.project_key))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+    ResultData::SprintList(sprints) => {
+      let b64_or_empty = |x: &Option<String>| x.as_deref().map(|x| b64(x.as_bytes())).unwrap_or_default();
+      sprints
+        .iter()
+        .map(|x| format!("{id}:{name}:{state}:{start}:{end}:{complete}:{goal}",
+                         id = x.id,
+                         name = b64(x.name.as_bytes()),
+                         state = x.state,
+                         start = b64_or_empty(&x.start_date),
+                         end = b64_or_empty(&x.end_date),
+                         complete = b64_or_empty(&x.complete_date),
+                         goal = b64_or_empty(&x.goal)))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+    ResultData::VersionList(versions) => {
+      let b64_or_empty = |x: &Option<String>| x.as_deref().map(|x| b64(x.as_bytes())).unwrap_or_default();
+      versions
+        .iter()
+        .map(|x| format!("{id}:{name}:{is_released}:{is_archived}:{release_date}:{description}",
+                         id = x.id,
+                         name = b64(x.name.as_bytes()),
+                         is_released = x.is_released,
+                         is_archived = x.is_archived,
+                         release_date = x.release_date.as_deref().unwrap_or_default(),
+                         description = b64_or_empty(&x.description)))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+    ResultData::ComponentList(components) => {
+      let b64_or_empty = |x: &Option<String>| x.as_deref().map(|x| b64(x.as_bytes())).unwrap_or_default();
+      components
+        .iter()
+        .map(|x| format!("{id}:{name}:{lead}:{description}",
+                         id = x.id,
+                         name = b64(x.name.as_bytes()),
+                         lead = b64_or_empty(&x.lead),
+                         description = b64_or_empty(&x.description)))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+    ResultData::TicketStatusList(tickets) => {
+      let b64_or_empty = |x: &Option<String>| x.as_deref().map(|x| b64(x.as_bytes())).unwrap_or_default();
+      tickets
+        .iter()
+        .map(|x| format!("{key}:{status}:{category}:{priority}:{resolution}",
+                         key = x.key,
+                         status = b64_or_empty(&x.status),
+                         category = x.status_category.as_deref().unwrap_or_default(),
+                         priority = b64_or_empty(&x.priority),
+                         resolution = b64_or_empty(&x.resolution)))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+    ResultData::PersonList(people) => {
+      // account ids may contain colons
+      people
+        .iter()
+        .map(|x| format!("{account_id}:{display_name}",
+                         account_id = b64(x.account_id.as_bytes()),
+                         display_name = b64(x.display_name.as_bytes())))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+    ResultData::CommentId(id) => { id.to_string() }
+    ResultData::CreatedTicketKey(key) => { key.clone() }
+    ResultData::TransitionList(transitions) => {
+      let b64_or_empty = |x: &Option<String>| x.as_deref().map(|x| b64(x.as_bytes())).unwrap_or_default();
+      transitions
+        .iter()
+        .map(|x| format!("{id}:{name}:{to_status}:{required_fields}",
+                         id = x.id,
+                         name = b64(x.name.as_bytes()),
+                         to_status = b64_or_empty(&x.to_status),
+                         required_fields = x.required_fields.join(";")))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+    ResultData::TicketTree(nodes) => {
+      let b64_or_empty = |x: &Option<String>| x.as_deref().map(|x| b64(x.as_bytes())).unwrap_or_default();
+      nodes
+        .iter()
+        .map(|x| format!("{key}:{parent}:{depth}:{summary}:{status}",
+                         key = x.key,
+                         parent = x.parent.as_deref().unwrap_or_default(),
+                         depth = x.depth,
+                         summary = b64_or_empty(&x.summary),
+                         status = b64_or_empty(&x.status)))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+    ResultData::Hello { server_name, server_version, protocol_version } => {
+      format!("{server_name},{server_version},{protocol_version}")
+    }
+    ResultData::Capabilities(capabilities) => {
+      let commands = capabilities.commands
+        .iter()
+        .map(|(name, parameters)| {
+          let mut name_and_parameters = vec![*name];
+          name_and_parameters.extend(parameters.iter());
+          name_and_parameters.join(":")
+        })
+        .collect::<Vec<_>>();
+      format!("protocol_version={version};commands={commands};ticket_formats={formats};features={features}",
+              version = capabilities.protocol_version,
+              commands = commands.join(","),
+              formats = capabilities.ticket_formats.join(","),
+              features = capabilities.features.join(","))
+    }
+  }
+}
 
-async fn serve_request(config: Config, request: Request, out_for_replies: tokio::sync::mpsc::Sender<Reply>, mut db_conn: Pool<Sqlite>) {
+pub(crate) async fn serve_request(config: Config, request: Request, out_for_replies: tokio::sync::mpsc::Sender<Reply>, mut db_conn: Pool<Sqlite>) {
   let Request { request_id, request_kind: request } = request;
   let request_id = request_id.as_str();
   match request {
+    RequestKind::Hello => { serve_hello(request_id, out_for_replies).await }
+    RequestKind::Capabilities => { serve_capabilities(request_id, out_for_replies).await }
     RequestKind::Fetch_Ticket(params) => { serve_fetch_ticket_request(config, request_id, params.as_str(), out_for_replies, &mut db_conn).await }
     RequestKind::Fetch_Ticket_List => {serve_fetch_ticket_list_request(config, request_id, out_for_replies, &mut db_conn).await }
+    RequestKind::Fetch_Ticket_History(params) => {
+      serve_fetch_ticket_history(config, request_id, params.as_str(), out_for_replies, &mut db_conn).await
+    }
     RequestKind::Fetch_Ticket_Key_Value_Fields(params) => {
       serve_fetch_ticket_key_value_fields(config, request_id, params.as_str(), out_for_replies, &mut db_conn).await
     }
+    RequestKind::Fetch_Tickets_Key_Value_Fields(issue_keys) => {
+      serve_fetch_tickets_key_value_fields(config, request_id, issue_keys.as_slice(), out_for_replies, &mut db_conn).await
+    }
     RequestKind::Fetch_Attachment_List_For_Ticket(params) => {
       serve_fetch_ticket_attachment_list(config, request_id, params.as_str(), out_for_replies, &mut db_conn).await
     }
     RequestKind::Fetch_Attachment_Content(params) => {
       serve_fetch_attachment_content(request_id, params.as_str(), out_for_replies, &mut db_conn).await
     }
+    RequestKind::Fetch_Logged_Time(params) => {
+      serve_fetch_logged_time(config, request_id, params.as_str(), out_for_replies, &mut db_conn).await
+    }
+    RequestKind::Fetch_Board_List => {
+      serve_fetch_board_list(config, request_id, out_for_replies, &mut db_conn).await
+    }
+    RequestKind::Fetch_Board_Sprints(params) => {
+      serve_fetch_board_sprints(config, request_id, params.as_str(), out_for_replies, &mut db_conn).await
+    }
+    RequestKind::Fetch_Sprint_Tickets(params) => {
+      serve_fetch_sprint_tickets(config, request_id, params.as_str(), out_for_replies, &mut db_conn).await
+    }
+    RequestKind::Fetch_Project_Versions(params) => {
+      serve_fetch_project_versions(config, request_id, params.as_str(), out_for_replies, &mut db_conn).await
+    }
+    RequestKind::Fetch_Version_Tickets(params) => {
+      serve_fetch_version_tickets(config, request_id, params.as_str(), out_for_replies, &mut db_conn).await
+    }
+    RequestKind::Fetch_Project_Components(params) => {
+      serve_fetch_project_components(config, request_id, params.as_str(), out_for_replies, &mut db_conn).await
+    }
+    RequestKind::Fetch_Component_Tickets(params) => {
+      serve_fetch_component_tickets(config, request_id, params.as_str(), out_for_replies, &mut db_conn).await
+    }
+    RequestKind::Fetch_Ticket_Status_List => {
+      serve_fetch_ticket_status_list(config, request_id, out_for_replies, &mut db_conn).await
+    }
+    RequestKind::Fetch_Watched_Tickets => {
+      serve_fetch_watched_tickets(config, request_id, out_for_replies, &mut db_conn).await
+    }
+    RequestKind::Fetch_Ticket_Watchers(params) => {
+      serve_fetch_ticket_watchers(config, request_id, params.as_str(), out_for_replies, &mut db_conn).await
+    }
+    RequestKind::Fetch_Ticket_Voters(params) => {
+      serve_fetch_ticket_voters(config, request_id, params.as_str(), out_for_replies, &mut db_conn).await
+    }
+    RequestKind::Fetch_Ticket_Tree(params) => {
+      serve_fetch_ticket_tree(config, request_id, params.as_str(), out_for_replies, &mut db_conn).await
+    }
+    RequestKind::Add_Comment(_)
+    | RequestKind::Edit_Field(_)
+    | RequestKind::Transition(_)
+    | RequestKind::Create_Issue(_) if is_offline() => {
+      serve_modification_while_offline(request_id, out_for_replies).await
+    }
+    RequestKind::Add_Comment(params) => {
+      serve_add_comment(config, request_id, params, out_for_replies, &mut db_conn).await
+    }
+    RequestKind::Edit_Field(params) => {
+      serve_edit_field(config, request_id, params, out_for_replies, &mut db_conn).await
+    }
+    RequestKind::Fetch_Transitions(params) => {
+      serve_fetch_transitions(config, request_id, params.as_str(), out_for_replies, &mut db_conn).await
+    }
+    RequestKind::Transition(params) => {
+      serve_transition(config, request_id, params, out_for_replies, &mut db_conn).await
+    }
+    RequestKind::Create_Issue(params) => {
+      serve_create_issue(config, request_id, params, out_for_replies, &mut db_conn).await
+    }
+    RequestKind::Synchronise_Ticket(_)
+    | RequestKind::Synchronise_Updated
+    | RequestKind::Synchronise_All if is_offline() => {
+      serve_synchronise_while_offline(request_id, out_for_replies).await
+    }
     RequestKind::Synchronise_Ticket(params) => {
       serve_synchronise_ticket(config, request_id, params.as_str(), out_for_replies, &mut db_conn).await
     }
@@ -262,15 +1220,47 @@ async fn serve_request(config: Config, request: Request, out_for_replies: tokio:
     RequestKind::Synchronise_All => {
       serve_synchronise_all(config, request_id, out_for_replies, &mut db_conn).await
     }
+    RequestKind::Subscribe => {
+      serve_subscribe(request_id, out_for_replies).await
+    }
+    RequestKind::Set_Offline_Mode(offline) => {
+      serve_set_offline_mode(request_id, offline, out_for_replies).await
+    }
     RequestKind::Exit_Server_After_Requests => { return }
     RequestKind::Exit_Server_Now => { return }
+    RequestKind::Set_Protocol(_) | RequestKind::Cancel(_) => { /* answered directly in process_events */ }
     RequestKind::Push_error_message(s) => {
-      let err_msg = if s.is_empty() {
-        format!("{request_id} ERROR\n")
-      } else {
-        format!("{request_id} ERROR {s}\n")
-      };
-      let _ = out_for_replies.send(Reply(err_msg)).await;
+      let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::InvalidRequest, s)).await;
+    }
+  }
+}
+
+fn cancel_request(request_id: &str,
+                  id_to_cancel: &str,
+                  running_requests: &mut HashMap<String, AbortHandle>,
+                  out_for_replies: &tokio::sync::mpsc::Sender<Reply>) {
+  let _ = out_for_replies.try_send(Reply::ack(request_id));
+
+  match running_requests.remove(id_to_cancel) {
+    Some(handle) if !handle.is_finished() => {
+      handle.abort();
+      // the cancelled request won't send a FINISHED reply. CANCELLED is its last reply.
+      let _ = out_for_replies.try_send(Reply::cancelled(id_to_cancel));
+    }
+    _ => {
+      let err_msg = format!("No on-going request with id {id_to_cancel} to cancel");
+      let _ = out_for_replies.try_send(Reply::error(request_id, ErrorCode::InvalidParameters, err_msg));
+    }
+  }
+
+  let _ = out_for_replies.try_send(Reply::finished(request_id));
+}
+
+// a cancelled task was aborted on purpose, only panics are worth mentioning
+pub(crate) fn log_task_failure(task_name: &str, res: Result<(), tokio::task::JoinError>) {
+  if let Err(e) = res {
+    if !e.is_cancelled() {
+      eprintln!("Error: a {task_name} task panicked. Err: {e:?}");
     }
   }
 }
@@ -283,66 +1273,95 @@ async fn process_events(config: Config,
   let mut exit_immediately_requested = false;
 
   let mut handles = JoinSet::new();
+  let mut running_requests = HashMap::new();
+  let mut subscriptions = Vec::new();
   let mut id_of_exit_request = String::new();
   let mut id_of_exit_immediate_request = String::new();
 
   while !exit_requested {
-    let event = events_to_process.try_recv();
-    match event {
-      Ok(request) => {
-        match request.request_kind {
-          RequestKind::Exit_Server_After_Requests => {
-            exit_requested = true;
-            let _ = out_for_replies.try_send(Reply(format!("{id} ACK\n", id = request.request_id)));
-            id_of_exit_request = request.request_id;
+    tokio::select! {
+      request = events_to_process.recv() => {
+        match request {
+          Some(request) => {
+            match request.request_kind {
+              RequestKind::Exit_Server_After_Requests => {
+                exit_requested = true;
+                let _ = out_for_replies.try_send(Reply::ack(request.request_id.as_str()));
+                id_of_exit_request = request.request_id;
+              }
+              RequestKind::Exit_Server_Now => {
+                exit_requested = true;
+                exit_immediately_requested = true;
+                let _ = out_for_replies.try_send(Reply::ack(request.request_id.as_str()));
+                id_of_exit_immediate_request = request.request_id;
+              },
+              RequestKind::Set_Protocol(_) => {
+                // the connection already switched protocol when reading the request
+                let _ = out_for_replies.try_send(Reply::ack(request.request_id.as_str()));
+                let _ = out_for_replies.try_send(Reply::finished(request.request_id.as_str()));
+              }
+              RequestKind::Cancel(id_to_cancel) => {
+                cancel_request(request.request_id.as_str(), id_to_cancel.as_str(), &mut running_requests, &out_for_replies);
+              }
+              _ => {
+                let request_id = request.request_id.clone();
+                if matches!(request.request_kind, RequestKind::Subscribe) {
+                  subscriptions.push(request_id.clone());
+                }
+                let handle = handles.spawn(serve_request(config.clone(), request, out_for_replies.clone(), db_conn.clone()));
+                running_requests.insert(request_id, handle);
+              }
+            }
           }
-          RequestKind::Exit_Server_Now => {
+          None => {
             exit_requested = true;
-            exit_immediately_requested = true;
-            let _ = out_for_replies.try_send(Reply(format!("{id} ACK\n", id = request.request_id)));
-            id_of_exit_immediate_request = request.request_id;
-          },
-          _ => {
-            handles.spawn(serve_request(config.clone(), request, out_for_replies.clone(), db_conn.clone()));
           }
         }
       }
-      Err(tokio::sync::mpsc::error::TryRecvError::Empty) => {
-        tokio::time::sleep(Duration::from_millis(100)).await;
-      }
-      Err(tokio::sync::mpsc::error::TryRecvError::Disconnected) => {
-        exit_requested = true;
+      Some(res) = handles.join_next(), if !handles.is_empty() => {
+        log_task_failure("request", res);
       }
     }
 
     // remove handles of finished task from set
-    while let Some(Ok(_)) = handles.try_join_next() {
+    while let Some(res) = handles.try_join_next() {
+      log_task_failure("request", res);
     }
+    running_requests.retain(|_, handle| !handle.is_finished());
   }
 
-  while (!exit_immediately_requested) && (!handles.is_empty()) {
-    // remove handles of finished task from set
-    while let Some(Ok(_)) = handles.try_join_next() {
+  // subscriptions never finish on their own. Waiting for them would prevent the server from exiting.
+  for id in subscriptions {
+    if let Some(handle) = running_requests.remove(&id) {
+      handle.abort();
+      let _ = out_for_replies.try_send(Reply::finished(id.as_str()));
     }
+  }
 
-    let event = events_to_process.try_recv();
-    match event {
-      Ok(Request { request_id: id, request_kind: RequestKind::Exit_Server_Now }) => {
-        exit_immediately_requested = true;
-        let _ = out_for_replies.try_send(Reply(format!("{id} ACK\n")));
-        id_of_exit_immediate_request = id;
-      },
-      Err(tokio::sync::mpsc::error::TryRecvError::Empty) => {
-        if !handles.is_empty() {
-          eprintln!("There are still events to be processed apparently");
-          tokio::time::sleep(Duration::from_millis(100)).await;
+  while (!exit_immediately_requested) && (!handles.is_empty()) {
+    tokio::select! {
+      request = events_to_process.recv() => {
+        match request {
+          Some(Request { request_id: id, request_kind: RequestKind::Exit_Server_Now }) => {
+            exit_immediately_requested = true;
+            let _ = out_for_replies.try_send(Reply::ack(id.as_str()));
+            id_of_exit_immediate_request = id;
+          },
+          Some(Request { request_id: id, request_kind: RequestKind::Cancel(id_to_cancel) }) => {
+            // cancelling a request gives a way to exit sooner without losing the other on-going ones
+            cancel_request(id.as_str(), id_to_cancel.as_str(), &mut running_requests, &out_for_replies);
+          },
+          Some(_) => {}
+          None => {
+            exit_immediately_requested = true;
+          },
         }
       }
-      Err(tokio::sync::mpsc::error::TryRecvError::Disconnected) => {
-        exit_immediately_requested = true;
-      },
-      _ => {}
+      Some(res) = handles.join_next() => {
+        log_task_failure("request", res);
+      }
     }
+    running_requests.retain(|_, handle| !handle.is_finished());
   }
 
   drop(events_to_process);
@@ -350,48 +1369,61 @@ async fn process_events(config: Config,
 
   handles.abort_all();
   if !id_of_exit_request.is_empty() {
-    let _ = out_for_replies.try_send(Reply(format!("{id_of_exit_request} FINISHED\n")));
+    let _ = out_for_replies.try_send(Reply::finished(id_of_exit_request.as_str()));
   }
   if !id_of_exit_immediate_request.is_empty() {
-    let _ = out_for_replies.try_send(Reply(format!("{id_of_exit_immediate_request} FINISHED\n")));
+    let _ = out_for_replies.try_send(Reply::finished(id_of_exit_immediate_request.as_str()));
   }
 
   drop(out_for_replies);
 }
 
-fn is_stdin_closed() -> bool {
-  // checking if stdin is closed is based on this answer:
-  // https://unix.stackexchange.com/a/626425
-  //
-  // unfortunately this doesn't work because of earlier calls to openat
-  // see https://github.com/rust-lang/libc/issues/3907 for details
-  // might as well return false directly.
-  // The issue here is that if stdin is closed, the server can't do
-  // anything useful and should quit. Instead, it takes 100% CPU when
-  // that happens. :-(
-  false
-
-  /*
-  let mut pollfds_input = libc::pollfd {
-    fd: 0, // stdin
-    events: 0,
-    revents: libc::POLLHUP,
-  };
-  let pollfds_input_ptr = addr_of_mut!(pollfds_input);
+// read_line returns Ok(0) when reaching the end of stdin. On a terminal, this only means the
+// user pressed ctrl+D, and they can keep typing requests afterwards. For a pipe or a file however,
+// the input is gone for good (e.g. the parent process crashed or closed it). The server then
+// can't receive any further request and should exit instead of running forever on its own.
+fn is_end_of_stdin_final() -> bool {
+  !io::stdin().is_terminal()
+}
 
-  let poll_res = unsafe {
-     libc::poll( pollfds_input_ptr, 1, 0)
+fn line_to_request(line: &str, protocol: &RwLock<Protocol>, is_first_line: bool) -> Request {
+  let current_protocol = *protocol.read().unwrap();
+  let request = match current_protocol {
+    Protocol::Text => { Request::from(line) }
+    Protocol::JsonLines => { request_from_json_line(line) }
   };
-  let is_stdin_closed = poll_res > 0;
-  is_stdin_closed
-  */
+
+  match request {
+    Ok(Request { request_id, request_kind: RequestKind::Set_Protocol(new_protocol) }) => {
+      // The protocol is negotiated at the beginning of the connection only. Switching it later
+      // would make it impossible for the client to tell in which format the replies to
+      // on-going requests are.
+      let request_kind = if is_first_line {
+        *protocol.write().unwrap() = new_protocol;
+        RequestKind::Set_Protocol(new_protocol)
+      } else {
+        Push_error_message(String::from("SET_PROTOCOL is only accepted as the very first request of a connection"))
+      };
+      Request { request_id, request_kind }
+    }
+    Ok(v) => { v }
+    Err(e) => {
+      let request_kind = Push_error_message(format!("Failed to get a request out of [{line}]: Err: {e}"));
+      Request {
+        request_id: String::from("_"),
+        request_kind
+      }
+    }
+  }
 }
 
-fn stdin_to_request(request_queue: tokio::sync::mpsc::Sender<Request>) {
+fn stdin_to_request(request_queue: tokio::sync::mpsc::Sender<Request>, protocol: Arc<RwLock<Protocol>>) {
   let mut stdin_input: String = Default::default();
   let mut nag_user_about_blocking_stdin = true;
+  let mut is_first_line = true;
+  let mut is_stdin_closed = false;
 
-  while (!request_queue.is_closed()) && (!is_stdin_closed()) {
+  while (!request_queue.is_closed()) && (!is_stdin_closed) {
     // When changing code here, make sure that a request to exit the server doesn't require
     // the user to first type enter a second time for the request to be processed. This can
     // easily happen when the blocking call to read from stdin is done on the same thread
@@ -402,25 +1434,12 @@ fn stdin_to_request(request_queue: tokio::sync::mpsc::Sender<Request>) {
     let read_line_ret = io::stdin().read_line(&mut stdin_input);
     match read_line_ret {
       Ok(0) => {
-        // workaround for not being able to detect closed stdin. In case stdin is
-        // closed, ideally the server would gracefully shutdown. Unfortunately
-        // detecting if stdin is closed or not doesn't work at the moment.
-        // When stdin is closed, read_line returns immediately saying it read 0 bytes.
-        // This leads to this loop becoming a busy loop and the program starts taking
-        // up 100% CPU.
-        // However, read_line can also return Ok(0) with stdin still being open,
-        // namely when a user sends an EOF (ctrl+D on the terminal). Therefore, we
-        // can't just rely on receiving Ok(0) to detect closed stdin.
-        // One way to deal with this would be to change the communication protocol
-        // and say receiving an EOF is similar to an exit-after-requests request.
-        // There might however be genuine situation where EOF can be received and
-        // thus this isn't a feasible situation.
-        // Another possibility would be to check the rate of receiving Ok(0). If we
-        // are receiving say 1000 consecutive Ok(0) in less than 0.5 seconds, chances
-        // are that stdin is closed and not that someone is flooding the server
-        // with EOF. However, here we will keep things simple and just sleep a bit to
-        // avoid busy looping.
-        thread::sleep(Duration::from_millis(50));
+        if is_end_of_stdin_final() {
+          is_stdin_closed = true;
+        } else {
+          // avoid busy looping in case the user keeps sending EOF on the terminal.
+          thread::sleep(Duration::from_millis(50));
+        }
       }
       Ok(_) => {
         let without_suffix = stdin_input.strip_suffix('\n');
@@ -430,18 +1449,8 @@ fn stdin_to_request(request_queue: tokio::sync::mpsc::Sender<Request>) {
         };
 
         if !without_suffix.is_empty() {
-          let request = Request::from(without_suffix);
-          let request = match request {
-            Ok(v) => { v }
-            Err(e) => {
-              let request_kind = Push_error_message(format!("Failed to get a request out of [{without_suffix}]: Err: {e}"));
-              let request = Request {
-                request_id: String::from("_"),
-                request_kind
-              };
-              request
-            }
-          };
+          let request = line_to_request(without_suffix, &protocol, is_first_line);
+          is_first_line = false;
           let _ = request_queue.blocking_send(request);
         }
       }
@@ -461,7 +1470,7 @@ fn stdin_to_request(request_queue: tokio::sync::mpsc::Sender<Request>) {
     }
   }
 
-  if (is_stdin_closed()) && (!request_queue.is_closed()) {
+  if is_stdin_closed && (!request_queue.is_closed()) {
     let request = Request {
       request_id: "_exit-after-requests-due-to-closed-stdin".to_string(),
       request_kind: RequestKind::Exit_Server_After_Requests
@@ -480,16 +1489,22 @@ async fn update_jira_schema(config: &Config, db_conn: &Pool<Sqlite>) {
             update_issue_types_in_db(&config, &mut db_issue_type_handle),
             update_fields_in_db(&config, &mut db_fields_handle),
             update_issue_link_types_in_db(&config, &mut db_link_types_handles),
-            update_project_list_in_db(&config, &mut db_project_list_handle)
+            update_project_list_in_db(&config, &mut db_project_list_handle),
+            update_statuses_priorities_and_resolutions_in_db(config, db_conn),
+            update_current_user_in_db(config, db_conn)
     );
+    // versions and components reference the projects
+    update_versions_and_components_in_db(config, db_conn).await;
 }
 
 async fn background_project_update(config: Config, mut db_conn: Pool<Sqlite>) {
   let wait_before_loop_iteration = Duration::from_secs(90);
 
   loop {
+    wait_until_online().await;
     update_jira_schema(&config, &db_conn).await;
-    update_interesting_projects_in_db(&config, &mut db_conn).await;
+    update_interesting_projects_in_db(&config, &mut db_conn, &ProgressReporter::disabled()).await;
+    update_boards_and_sprints_in_db(&config, &db_conn).await;
     tokio::time::sleep(wait_before_loop_iteration).await;
   }
 }
@@ -498,8 +1513,12 @@ async fn background_full_initialise_project(config: Config, mut db_conn: Pool<Sq
   let wait_before_loop_iteration = Duration::from_secs(7200);
 
   loop {
+    wait_until_online().await;
     update_jira_schema(&config, &db_conn).await;
-    initialise_interesting_projects_in_db(&config, &mut db_conn).await;
+    // one request per project and issue type, and the screens rarely change
+    update_create_metadata_in_db(&config, &db_conn).await;
+    initialise_interesting_projects_in_db(&config, &mut db_conn, &ProgressReporter::disabled()).await;
+    reconcile_interesting_projects_in_db(&config, &db_conn).await;
     tokio::time::sleep(wait_before_loop_iteration).await;
   }
 }
@@ -512,35 +1531,45 @@ async fn background_tasks(config: Config, mut db_conn: Pool<Sqlite>) {
   let _ = full_initialise_project.await;
 }
 
-pub(crate)
-async fn server_request_loop(config: &Config, db_conn: &Pool<Sqlite>) {
-
-  let background_tasks_handle = tokio::spawn(background_tasks(config.clone(), db_conn.clone()));
-
+async fn serve_stdin(config: &Config, db_conn: &Pool<Sqlite>) {
   let (request_to_processor_sender, request_receiver) = tokio::sync::mpsc::channel(1000);
   let (reply_sender, mut reply_receiver) = tokio::sync::mpsc::channel(1000);
 
   let event_processor_handle = tokio::spawn(process_events(config.clone(), request_receiver, reply_sender, db_conn.clone()));
 
+  let protocol = Arc::new(RwLock::new(Protocol::Text));
+  let protocol_for_stdin = protocol.clone();
+
   let (request_on_stdin_sender, mut request_on_stdin_receiver) = tokio::sync::mpsc::channel(1000);
   let stdin_to_req_handle = std::thread::spawn(move || {
-    stdin_to_request(request_on_stdin_sender)
+    stdin_to_request(request_on_stdin_sender, protocol_for_stdin)
   });
 
   eprintln!("Ready to accept requests");
 
+  // the parent process may be gone along with its end of the pipe. Failing to write a reply
+  // mustn't crash the server before it could cleanly shut down.
+  let write_reply = |reply: Reply| {
+    let mut stdout = io::stdout();
+    let _ = stdout.write_all(reply.encode(*protocol.read().unwrap()).as_bytes());
+    let _ = stdout.flush();
+  };
+
+  let mut is_stdin_open = true;
   while !reply_receiver.is_closed() {
     tokio::select! {
-      req = request_on_stdin_receiver.recv() => {
+      req = request_on_stdin_receiver.recv(), if is_stdin_open => {
         match req {
-          None => {},
+          // the stdin thread stopped after sending the exit request. The sender to process_events
+          // is kept alive, so that on-going requests still get finished.
+          None => { is_stdin_open = false; },
           Some(req) => { let _ = request_to_processor_sender.try_send(req); }
         }
       },
       reply = reply_receiver.recv() => {
         match reply {
           None => {},
-          Some(reply) => { print!("{}", reply.0) }
+          Some(reply) => { write_reply(reply) }
         }
       }
     }
@@ -548,13 +1577,195 @@ async fn server_request_loop(config: &Config, db_conn: &Pool<Sqlite>) {
 
   if !reply_receiver.is_empty() {
     while let Ok(reply) = reply_receiver.try_recv() {
-      print!("{}", reply.0)
+      write_reply(reply)
     }
   }
 
   request_on_stdin_receiver.close();
   let _ = event_processor_handle.abort();
   drop(stdin_to_req_handle);
+}
+
+async fn socket_to_request(socket_input: tokio::net::unix::OwnedReadHalf,
+                           request_queue: tokio::sync::mpsc::Sender<Request>,
+                           protocol: Arc<RwLock<Protocol>>) {
+  let mut lines = BufReader::new(socket_input).lines();
+  let mut is_first_line = true;
+
+  loop {
+    let line = lines.next_line().await;
+    match line {
+      Ok(Some(line)) => {
+        if !line.is_empty() {
+          let request = line_to_request(line.as_str(), &protocol, is_first_line);
+          is_first_line = false;
+          if request_queue.send(request).await.is_err() {
+            // the request processor is gone, no need to read more requests.
+            return;
+          }
+        }
+      }
+      Ok(None) => {
+        // Contrary to stdin, a closed socket can be reliably detected. The client might only
+        // have shut down its writing side and still wait for replies, so we gracefully finish
+        // the on-going requests before closing the connection.
+        break;
+      }
+      Err(e) => {
+        eprintln!("Failed to read line from unix socket: {e:?}");
+        break;
+      }
+    }
+  }
+
+  let request = Request {
+    request_id: "_exit-after-requests-due-to-closed-connection".to_string(),
+    request_kind: RequestKind::Exit_Server_After_Requests
+  };
+  let _ = request_queue.send(request).await;
+
+  // process_events considers a disconnected request queue as a request to exit immediately.
+  // Keep it alive until the on-going requests are finished.
+  request_queue.closed().await;
+}
+
+async fn serve_unix_connection(config: Config, stream: UnixStream, db_conn: Pool<Sqlite>) {
+  // Each connection gets its own request processor. Request ids are therefore only required
+  // to be unique per connection, and an exit request only closes the connection it came from.
+  let (socket_input, mut socket_output) = stream.into_split();
+
+  let (request_sender, request_receiver) = tokio::sync::mpsc::channel(1000);
+  let (reply_sender, mut reply_receiver) = tokio::sync::mpsc::channel::<Reply>(1000);
+
+  let protocol = Arc::new(RwLock::new(Protocol::Text));
+
+  let event_processor_handle = tokio::spawn(process_events(config, request_receiver, reply_sender, db_conn));
+  let socket_to_req_handle = tokio::spawn(socket_to_request(socket_input, request_sender, protocol.clone()));
+
+  while let Some(reply) = reply_receiver.recv().await {
+    let reply = reply.encode(*protocol.read().unwrap());
+    let write_res = socket_output.write_all(reply.as_bytes()).await;
+    if let Err(e) = write_res {
+      eprintln!("Failed to write reply to unix socket, closing the connection. Err: {e:?}");
+      break;
+    }
+  }
+
+  socket_to_req_handle.abort();
+  event_processor_handle.abort();
+}
+
+fn remove_stale_socket(socket_path: &Path) -> Result<(), String> {
+  if !socket_path.exists() {
+    return Ok(());
+  }
+
+  // a server left a socket behind if it got killed. Only remove the file if nobody
+  // listens on it, otherwise we would steal the socket of a running server.
+  match std::os::unix::net::UnixStream::connect(socket_path) {
+    Ok(_) => {
+      Err(format!("Another server is already listening on {socket_path:?}"))
+    }
+    Err(_) => {
+      std::fs::remove_file(socket_path)
+        .map_err(|e| format!("Failed to remove stale socket file {socket_path:?}. Err: {e:?}"))
+    }
+  }
+}
+
+async fn serve_unix_socket(config: &Config, socket_path: &Path, db_conn: &Pool<Sqlite>) {
+  if let Err(e) = remove_stale_socket(socket_path) {
+    eprintln!("Error: {e}");
+    return;
+  }
+
+  let listener = UnixListener::bind(socket_path);
+  let listener = match listener {
+    Ok(v) => { v }
+    Err(e) => {
+      eprintln!("Error: failed to listen on unix socket {socket_path:?}. Err: {e:?}");
+      return;
+    }
+  };
+
+  // the socket gives access to the whole local database. Only the current user shall use it.
+  let permissions = std::fs::Permissions::from_mode(0o600);
+  if let Err(e) = std::fs::set_permissions(socket_path, permissions) {
+    eprintln!("Error: failed to restrict the permissions of {socket_path:?}. Err: {e:?}");
+    let _ = std::fs::remove_file(socket_path);
+    return;
+  }
+
+  eprintln!("Ready to accept connections on {socket_path:?}");
+
+  let mut connections = JoinSet::new();
+  loop {
+    tokio::select! {
+      connection = listener.accept() => {
+        match connection {
+          Ok((stream, _addr)) => {
+            connections.spawn(serve_unix_connection(config.clone(), stream, db_conn.clone()));
+          }
+          Err(e) => {
+            eprintln!("Failed to accept connection on unix socket: {e:?}");
+          }
+        }
+      },
+      _ = tokio::signal::ctrl_c() => {
+        eprintln!("Received interruption signal, shutting down");
+        break;
+      }
+    }
+
+    // remove handles of finished connections from set
+    while let Some(res) = connections.try_join_next() {
+      log_task_failure("connection", res);
+    }
+  }
+
+  connections.abort_all();
+  let _ = std::fs::remove_file(socket_path);
+}
+
+pub(crate)
+async fn server_request_loop(config: &Config, db_conn: &Pool<Sqlite>) {
+
+  set_offline(config.offline());
+
+  // background tasks are shared by all clients, whatever the transport is.
+  let background_tasks_handle = tokio::spawn(background_tasks(config.clone(), db_conn.clone()));
+
+  match (config.unix_socket(), config.http_api()) {
+    (Some(socket_path), _) => { serve_unix_socket(config, socket_path.as_path(), db_conn).await }
+    (None, Some(address)) => { serve_http_api(config, *address, db_conn).await }
+    (None, None) => { serve_stdin(config, db_conn).await }
+  }
 
   let _ = background_tasks_handle.abort();
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn valid_issue_keys() {
+    assert!(is_valid_issue_key("PROJ-1"));
+    assert!(is_valid_issue_key("PROJ-12345"));
+    assert!(is_valid_issue_key("P2-7"));
+    assert!(is_valid_issue_key("MY_PROJ-42"));
+  }
+
+  #[test]
+  fn invalid_issue_keys() {
+    assert!(!is_valid_issue_key(""));
+    assert!(!is_valid_issue_key("PROJ"));
+    assert!(!is_valid_issue_key("PROJ-"));
+    assert!(!is_valid_issue_key("-12"));
+    assert!(!is_valid_issue_key("proj-12"));
+    assert!(!is_valid_issue_key("2PROJ-12"));
+    assert!(!is_valid_issue_key("_PROJ-12"));
+    assert!(!is_valid_issue_key("PROJ-12a"));
+    assert!(!is_valid_issue_key("PROJ-1-2"));
+  }
+}
@@ -0,0 +1,70 @@
+use sqlx::{Pool, Sqlite};
+use crate::get_config::Config;
+use crate::manage_issue_comments::add_comment_to_issue;
+use crate::server::{is_valid_issue_key, ErrorCode, Reply, ResultData};
+use crate::utils::decode_base64_text;
+
+#[derive(Eq, PartialEq, Debug)]
+pub(crate) struct AddCommentParams {
+  issue_key: String,
+  markdown: String,
+}
+
+impl AddCommentParams {
+  pub(crate) fn new(issue_key: &str, markdown: &str) -> Result<AddCommentParams, String> {
+    if !is_valid_issue_key(issue_key) || markdown.trim().is_empty() {
+      return Err(String::from("Invalid request. ADD_COMMENT needs the ticket key (like PROJ-123) and a non empty comment in markdown"));
+    }
+    Ok(AddCommentParams { issue_key: issue_key.to_string(), markdown: markdown.to_string() })
+  }
+
+  // the markdown is base64 encoded, since it usually contains commas and newlines
+  pub(crate) fn from_text_params(params: &str) -> Result<AddCommentParams, String> {
+    let key_and_markdown = params
+      .split_once(',')
+      .and_then(|(issue_key, markdown)| Some((issue_key, decode_base64_text(markdown)?)));
+    let Some((issue_key, markdown)) = key_and_markdown else {
+      return Err(format!("Invalid request. ADD_COMMENT needs two parameters separated by a comma: the ticket key (like PROJ-123) and the comment in markdown, base64 encoded. Params=[{params}]"));
+    };
+    AddCommentParams::new(issue_key, markdown.as_str())
+  }
+}
+
+pub(crate) async fn serve_add_comment(config: Config,
+                                      request_id: &str,
+                                      params: AddCommentParams,
+                                      out_for_replies: tokio::sync::mpsc::Sender<Reply>,
+                                      db_conn: &mut Pool<Sqlite>) {
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
+
+  let AddCommentParams { issue_key, markdown } = params;
+  match add_comment_to_issue(&config, issue_key.as_str(), markdown.as_str(), db_conn).await {
+    Ok(comment_id) => {
+      let _ = out_for_replies.send(Reply::result(request_id, ResultData::CommentId(comment_id))).await;
+    }
+    Err(e) => {
+      let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::RemoteServer, e)).await;
+    }
+  }
+
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn add_comment_params() {
+    assert!(AddCommentParams::new("PROJ-1", "Looks *good*").is_ok());
+    assert!(AddCommentParams::new("PROJ-1", " \n").is_err());
+    assert!(AddCommentParams::new("", "Looks *good*").is_err());
+  }
+
+  #[test]
+  fn add_comment_params_with_invalid_key() {
+    // the key ends up in the url of the request sent to jira
+    assert!(AddCommentParams::new("PROJ-1/../../../rest/api/3/issue/PROJ-2", "Looks *good*").is_err());
+    assert!(AddCommentParams::new("PROJ-1#", "Looks *good*").is_err());
+  }
+}
@@ -0,0 +1,31 @@
+use crate::capabilities::{PROTOCOL_VERSION, SERVER_NAME, SERVER_VERSION, SUPPORTED_COMMANDS, SUPPORTED_FEATURES, SUPPORTED_TICKET_FORMATS};
+use crate::server::{Capabilities, Reply, ResultData};
+
+pub(crate) async fn serve_hello(request_id: &str,
+                                out_for_replies: tokio::sync::mpsc::Sender<Reply>) {
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
+
+  let hello = ResultData::Hello {
+    server_name: SERVER_NAME.to_string(),
+    server_version: SERVER_VERSION.to_string(),
+    protocol_version: PROTOCOL_VERSION,
+  };
+  let _ = out_for_replies.send(Reply::result(request_id, hello)).await;
+
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
+}
+
+pub(crate) async fn serve_capabilities(request_id: &str,
+                                       out_for_replies: tokio::sync::mpsc::Sender<Reply>) {
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
+
+  let capabilities = Capabilities {
+    protocol_version: PROTOCOL_VERSION,
+    commands: SUPPORTED_COMMANDS.to_vec(),
+    ticket_formats: SUPPORTED_TICKET_FORMATS.to_vec(),
+    features: SUPPORTED_FEATURES.to_vec(),
+  };
+  let _ = out_for_replies.send(Reply::result(request_id, ResultData::Capabilities(capabilities))).await;
+
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
+}
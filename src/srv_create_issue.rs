@@ -0,0 +1,75 @@
+use sqlx::{Pool, Sqlite};
+use crate::create_issue::{create_issue, get_new_issue};
+use crate::get_config::Config;
+use crate::server::{ErrorCode, Reply, ResultData};
+use crate::utils::{decode_base64_text, get_fields_from_text_params};
+
+#[derive(Eq, PartialEq, Debug)]
+pub(crate) struct CreateIssueParams {
+  project_key: String,
+  issue_type: String,
+  summary: String,
+  description: String, // markdown, may be empty
+  fields: Vec<(String /* field */, String /* value */)>,
+}
+
+impl CreateIssueParams {
+  pub(crate) fn new(project_key: &str,
+                    issue_type: &str,
+                    summary: &str,
+                    description: &str,
+                    fields: Vec<(String, String)>) -> Result<CreateIssueParams, String> {
+    if project_key.is_empty() || issue_type.is_empty() || fields.iter().any(|(field, _)| field.is_empty()) {
+      return Err(String::from("Invalid request. CREATE_ISSUE needs the project (like PROJ), the issue type (like Bug), the summary and the description in markdown, optionally along with other fields"));
+    }
+    Ok(CreateIssueParams {
+      project_key: project_key.to_string(),
+      issue_type: issue_type.to_string(),
+      summary: summary.to_string(),
+      description: description.to_string(),
+      fields,
+    })
+  }
+
+  // summary and description are base64 encoded, the extra fields come after them as
+  // <field>:<base64 value>, like for TRANSITION
+  pub(crate) fn from_text_params(params: &str) -> Result<CreateIssueParams, String> {
+    let mut parts = params.split(',');
+    let project_key = parts.next();
+    let issue_type = parts.next();
+    let summary = parts.next().and_then(decode_base64_text);
+    let description = parts.next().and_then(decode_base64_text);
+    let fields = get_fields_from_text_params(parts);
+    let (Some(project_key), Some(issue_type), Some(summary), Some(description), Some(fields)) = (project_key, issue_type, summary, description, fields) else {
+      return Err(format!("Invalid request. CREATE_ISSUE needs the project (like PROJ), the issue type (like Bug), the summary and the description in markdown, base64 encoded, separated by commas, optionally followed by other fields as <field>:<base64 encoded value>. Params=[{params}]"));
+    };
+    CreateIssueParams::new(project_key, issue_type, summary.as_str(), description.as_str(), fields)
+  }
+}
+
+pub(crate) async fn serve_create_issue(config: Config,
+                                       request_id: &str,
+                                       params: CreateIssueParams,
+                                       out_for_replies: tokio::sync::mpsc::Sender<Reply>,
+                                       db_conn: &mut Pool<Sqlite>) {
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
+
+  let CreateIssueParams { project_key, issue_type, summary, description, fields } = params;
+  match get_new_issue(project_key.as_str(), issue_type.as_str(), summary.as_str(), description.as_str(), fields, db_conn).await {
+    Err(e) => {
+      let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::InvalidParameters, e)).await;
+    }
+    Ok(new_issue) => {
+      match create_issue(&config, &new_issue, db_conn).await {
+        Ok(issue_key) => {
+          let _ = out_for_replies.send(Reply::result(request_id, ResultData::CreatedTicketKey(issue_key))).await;
+        }
+        Err(e) => {
+          let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::RemoteServer, e)).await;
+        }
+      }
+    }
+  }
+
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
+}
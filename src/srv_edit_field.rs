@@ -0,0 +1,86 @@
+use sqlx::{Pool, Sqlite};
+use crate::edit_issue_field::{edit_field_of_issue, find_field_in_db, get_field_value};
+use crate::get_config::Config;
+use crate::server::{is_valid_issue_key, ErrorCode, Reply, ResultData};
+use crate::utils::decode_base64_text;
+
+#[derive(Eq, PartialEq, Debug)]
+pub(crate) struct EditFieldParams {
+  issue_key: String,
+  field: String,
+  value: String, // an empty value clears the field
+}
+
+impl EditFieldParams {
+  pub(crate) fn new(issue_key: &str, field: &str, value: &str) -> Result<EditFieldParams, String> {
+    if !is_valid_issue_key(issue_key) || field.is_empty() {
+      return Err(String::from("Invalid request. EDIT_FIELD needs the ticket key (like PROJ-123), the field (like summary or Story_Points) and its new value"));
+    }
+    Ok(EditFieldParams { issue_key: issue_key.to_string(), field: field.to_string(), value: value.to_string() })
+  }
+
+  // the value is base64 encoded, since it may contain commas, spaces and newlines
+  pub(crate) fn from_text_params(params: &str) -> Result<EditFieldParams, String> {
+    let mut parts = params.splitn(3, ',');
+    let key_field_and_value = match (parts.next(), parts.next(), parts.next().and_then(decode_base64_text)) {
+      (Some(issue_key), Some(field), Some(value)) => { Some((issue_key, field, value)) }
+      _ => { None }
+    };
+    let Some((issue_key, field, value)) = key_field_and_value else {
+      return Err(format!("Invalid request. EDIT_FIELD needs three parameters separated by commas: the ticket key (like PROJ-123), the field (like summary or Story_Points) and the new value, base64 encoded. Params=[{params}]"));
+    };
+    EditFieldParams::new(issue_key, field, value.as_str())
+  }
+}
+
+pub(crate) async fn serve_edit_field(config: Config,
+                                     request_id: &str,
+                                     params: EditFieldParams,
+                                     out_for_replies: tokio::sync::mpsc::Sender<Reply>,
+                                     db_conn: &mut Pool<Sqlite>) {
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
+
+  let EditFieldParams { issue_key, field, value } = params;
+  let field_value = match find_field_in_db(field.as_str(), db_conn).await {
+    Ok(field) => { get_field_value(field, value.as_str(), db_conn).await }
+    Err(e) => { Err(e) }
+  };
+
+  match field_value {
+    Err(e) => {
+      let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::InvalidParameters, e)).await;
+    }
+    Ok(field_value) => {
+      match edit_field_of_issue(&config, issue_key.as_str(), &field_value, db_conn).await {
+        Ok(key_value_field) => {
+          let _ = out_for_replies.send(Reply::result(request_id, ResultData::KeyValueFields(vec![key_value_field]))).await;
+        }
+        Err(e) => {
+          let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::RemoteServer, e)).await;
+        }
+      }
+    }
+  }
+
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn edit_field_params() {
+    assert!(EditFieldParams::new("PROJ-1", "summary", "New summary").is_ok());
+    assert!(EditFieldParams::new("PROJ-1", "Story_Points", "").is_ok());
+    assert!(EditFieldParams::new("PROJ-1", "", "New summary").is_err());
+    assert!(EditFieldParams::new("", "summary", "New summary").is_err());
+  }
+
+  #[test]
+  fn edit_field_params_with_invalid_key() {
+    // the key ends up in the url of the request sent to jira
+    assert!(EditFieldParams::new("PROJ-1/../../../rest/api/3/user", "summary", "New summary").is_err());
+    assert!(EditFieldParams::new("PROJ-1?notifyUsers=false", "summary", "New summary").is_err());
+  }
+}
@@ -1,9 +1,11 @@
-use base64::Engine;
 use sqlx::{Error, FromRow, Pool, Sqlite};
 use crate::find_issues_that_need_updating::update_interesting_projects_in_db;
 use crate::get_config::Config;
+use crate::offline_mode::is_offline;
+use crate::sync_progress::ProgressReporter;
+use crate::srv_offline_mode::send_stale_reply;
 use crate::get_issue_details::{add_details_to_issue_in_db, get_ticket_attachment_list_from_json, IssueAttachment};
-use crate::server::Reply;
+use crate::server::{AttachmentName, ErrorCode, Reply, ResultData};
 
 
 #[derive(FromRow)]
@@ -134,7 +136,7 @@ async fn get_ticket_attachments_uuid_and_name_from_remote(issue_key: &str, confi
     }
   }
 
-  let _ = update_interesting_projects_in_db(&config, &db_conn).await;
+  let _ = update_interesting_projects_in_db(&config, &db_conn, &ProgressReporter::disabled()).await;
 
   let with_uuid = add_uuid_to_names(attachment_list.as_slice(),
                                     issue_key, db_conn).await;
@@ -163,18 +165,14 @@ fn are_attachment_names_equal(param1: &[attachment_name_in_db], param2: &[attach
 
   is_same
 }
-fn format_attachment_list(attachment_list: &[attachment_name_in_db]) -> String {
-    let base_64_encoded = attachment_list
+fn format_attachment_list(attachment_list: &[attachment_name_in_db]) -> Vec<AttachmentName> {
+    attachment_list
         .iter()
-        .map(|x| {
-          let uuid = &x.uuid;
-          let filename_as_base64 = base64::engine::general_purpose::STANDARD.encode(x.filename.as_bytes());
-          format!("{uuid}:{filename_as_base64}")
+        .map(|x| AttachmentName {
+          uuid: x.uuid.clone(),
+          filename: x.filename.clone(),
         })
-        .reduce(|a, b| format!("{a},{b}"))
-        .unwrap_or_default();
-
-    base_64_encoded
+        .collect::<Vec<_>>()
 }
 
 pub(crate) async fn serve_fetch_ticket_attachment_list(config: Config,
@@ -182,7 +180,7 @@ pub(crate) async fn serve_fetch_ticket_attachment_list(config: Config,
                                                         params: &str,
                                                         out_for_replies: tokio::sync::mpsc::Sender<Reply>,
                                                         db_conn: &mut Pool<Sqlite>) {
-  let _ = out_for_replies.send(Reply(format!("{request_id} ACK\n"))).await;
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
 
   let splitted_params = params
     .split(',')
@@ -190,8 +188,8 @@ pub(crate) async fn serve_fetch_ticket_attachment_list(config: Config,
 
   let nr_params = splitted_params.len();
   if nr_params != 1 {
-    let err_msg = format!("{request_id} ERROR invalid parameters. FETCH_ATTACHMENT_LIST_FOR_TICKET need one parameter (the ticket id, like PROJ-123) but got {nr_params} instead. Params=[{params}]\n");
-    let _ = out_for_replies.send(Reply(err_msg)).await;
+    let err_msg = format!("invalid parameters. FETCH_ATTACHMENT_LIST_FOR_TICKET need one parameter (the ticket id, like PROJ-123) but got {nr_params} instead. Params=[{params}]");
+    let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::InvalidParameters, err_msg)).await;
   } else {
     let issue_key = splitted_params[0];
 
@@ -199,33 +197,29 @@ pub(crate) async fn serve_fetch_ticket_attachment_list(config: Config,
     match &old_data {
       Ok(data) => {
         let formatted = format_attachment_list(data.as_slice());
-        if formatted.is_empty() {
-          let _ = out_for_replies.send(Reply(format!("{request_id} RESULT\n"))).await;
-        } else {
-          let _ = out_for_replies.send(Reply(format!("{request_id} RESULT {formatted}\n"))).await;
-        }
+        let _ = out_for_replies.send(Reply::result(request_id, ResultData::AttachmentList(formatted))).await;
       }
       Err(e) => {
-        let _ = out_for_replies.send(Reply(format!("{request_id} ERROR {e}\n"))).await;
+        let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::LocalDatabase, e.to_string())).await;
       }
     }
 
-    let new_data = get_ticket_attachments_uuid_and_name_from_remote(issue_key, &config, db_conn).await;
-    match (&new_data, &old_data) {
-      (Ok(new_data), Ok(old_data)) if are_attachment_names_equal(new_data, old_data) => {}
-      (Ok(new_data), _) => {
-        let formatted = format_attachment_list(new_data.as_slice());
-        if formatted.is_empty() {
-          let _ = out_for_replies.send(Reply(format!("{request_id} RESULT\n"))).await;
-        } else {
-          let _ = out_for_replies.send(Reply(format!("{request_id} RESULT {formatted}\n"))).await;
+    if is_offline() {
+      send_stale_reply(&config, request_id, &out_for_replies, db_conn).await;
+    } else {
+      let new_data = get_ticket_attachments_uuid_and_name_from_remote(issue_key, &config, db_conn).await;
+      match (new_data, &old_data) {
+        (Ok(new_data), Ok(old_data)) if are_attachment_names_equal(&new_data, old_data) => {}
+        (Ok(new_data), _) => {
+          let formatted = format_attachment_list(new_data.as_slice());
+          let _ = out_for_replies.send(Reply::result(request_id, ResultData::AttachmentList(formatted))).await;
+          // todo: run a background synchronisation since we know there has been changes
+        },
+        (Err(e), _) => {
+          let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::RemoteServer, e)).await;
         }
-        // todo: run a background synchronisation since we know there has been changes
-      },
-      (Err(e), _) => {
-        let _ = out_for_replies.send(Reply(format!("{request_id} ERROR {e}\n"))).await;
       }
     }
   }
-  let _ = out_for_replies.send(Reply(format!("{request_id} FINISHED\n"))).await;
-}
\ No newline at end of file
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
+}
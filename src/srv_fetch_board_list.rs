@@ -0,0 +1,29 @@
+use sqlx::{Pool, Sqlite};
+use crate::get_config::Config;
+use crate::manage_boards_and_sprints::get_boards_from_db;
+use crate::offline_mode::is_offline;
+use crate::server::{ErrorCode, Reply, ResultData};
+use crate::srv_offline_mode::send_stale_reply;
+
+pub(crate) async fn serve_fetch_board_list(config: Config,
+                                           request_id: &str,
+                                           out_for_replies: tokio::sync::mpsc::Sender<Reply>,
+                                           db_conn: &mut Pool<Sqlite>) {
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
+
+  // boards are synchronised in the background, the request only reads the local database.
+  match get_boards_from_db(db_conn).await {
+    Ok(boards) => {
+      let _ = out_for_replies.send(Reply::result(request_id, ResultData::BoardList(boards))).await;
+    }
+    Err(e) => {
+      let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::LocalDatabase, e)).await;
+    }
+  }
+
+  if is_offline() {
+    send_stale_reply(&config, request_id, &out_for_replies, db_conn).await;
+  }
+
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
+}
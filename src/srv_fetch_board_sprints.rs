@@ -0,0 +1,38 @@
+use sqlx::{Pool, Sqlite};
+use crate::get_config::Config;
+use crate::manage_boards_and_sprints::get_sprints_of_board_from_db;
+use crate::offline_mode::is_offline;
+use crate::server::{ErrorCode, Reply, ResultData};
+use crate::srv_offline_mode::send_stale_reply;
+
+pub(crate) async fn serve_fetch_board_sprints(config: Config,
+                                              request_id: &str,
+                                              params: &str,
+                                              out_for_replies: tokio::sync::mpsc::Sender<Reply>,
+                                              db_conn: &mut Pool<Sqlite>) {
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
+
+  match params.parse::<i64>() {
+    Err(_) => {
+      let err_msg = format!("invalid parameters. FETCH_BOARD_SPRINTS needs one parameter (the board id, like 12). Params=[{params}]");
+      let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::InvalidParameters, err_msg)).await;
+    }
+    Ok(board_id) => {
+      // sprints are synchronised in the background, the request only reads the local database.
+      match get_sprints_of_board_from_db(board_id, db_conn).await {
+        Ok(sprints) => {
+          let _ = out_for_replies.send(Reply::result(request_id, ResultData::SprintList(sprints))).await;
+        }
+        Err(e) => {
+          let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::LocalDatabase, e)).await;
+        }
+      }
+
+      if is_offline() {
+        send_stale_reply(&config, request_id, &out_for_replies, db_conn).await;
+      }
+    }
+  }
+
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
+}
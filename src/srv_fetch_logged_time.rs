@@ -0,0 +1,59 @@
+use sqlx::{Pool, Sqlite};
+use crate::get_config::Config;
+use crate::manage_issue_worklogs::get_logged_time_from_db;
+use crate::offline_mode::is_offline;
+use crate::server::{ErrorCode, Reply, ResultData};
+use crate::srv_offline_mode::send_stale_reply;
+
+fn is_valid_day(candidate: &str) -> bool {
+  // checks that candidate looks like 2024-01-31
+  let chunks = candidate
+    .split('-')
+    .collect::<Vec<_>>();
+
+  chunks.len() == 3
+    && chunks[0].len() == 4
+    && chunks[1].len() == 2
+    && chunks[2].len() == 2
+    && chunks.iter().all(|x| x.chars().all(|c| c.is_ascii_digit()))
+}
+
+pub(crate) async fn serve_fetch_logged_time(config: Config,
+                                            request_id: &str,
+                                            params: &str,
+                                            out_for_replies: tokio::sync::mpsc::Sender<Reply>,
+                                            db_conn: &mut Pool<Sqlite>) {
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
+
+  let splitted_params = params
+    .split(',')
+    .collect::<Vec<_>>();
+
+  let nr_params = splitted_params.len();
+  if nr_params != 2 {
+    let err_msg = format!("invalid parameters. FETCH_LOGGED_TIME needs two parameters (the first and last days, like 2024-01-29,2024-02-04) but got {nr_params} instead. Params=[{params}]");
+    let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::InvalidParameters, err_msg)).await;
+  } else if let Some(invalid_day) = splitted_params.iter().find(|x| !is_valid_day(x)) {
+    let err_msg = format!("invalid parameters. FETCH_LOGGED_TIME takes days like 2024-01-31. Got [{invalid_day}]");
+    let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::InvalidParameters, err_msg)).await;
+  } else {
+    let first_day = splitted_params[0];
+    let last_day = splitted_params[1];
+
+    // worklogs are synchronised along with the tickets, the report only reads the local database.
+    match get_logged_time_from_db(first_day, last_day, db_conn).await {
+      Ok(logged_time) => {
+        let _ = out_for_replies.send(Reply::result(request_id, ResultData::LoggedTime(logged_time))).await;
+      }
+      Err(e) => {
+        let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::LocalDatabase, e)).await;
+      }
+    }
+
+    if is_offline() {
+      send_stale_reply(&config, request_id, &out_for_replies, db_conn).await;
+    }
+  }
+
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
+}
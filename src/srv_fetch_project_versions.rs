@@ -0,0 +1,42 @@
+use sqlx::{Pool, Sqlite};
+use crate::get_config::Config;
+use crate::manage_versions_and_components::get_versions_of_project_from_db;
+use crate::offline_mode::is_offline;
+use crate::server::{ErrorCode, Reply, ResultData};
+use crate::srv_offline_mode::send_stale_reply;
+
+pub(crate) async fn serve_fetch_project_versions(config: Config,
+                                                 request_id: &str,
+                                                 params: &str,
+                                                 out_for_replies: tokio::sync::mpsc::Sender<Reply>,
+                                                 db_conn: &mut Pool<Sqlite>) {
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
+
+  let splitted_params = params
+    .split(',')
+    .collect::<Vec<_>>();
+
+  let nr_params = splitted_params.len();
+  if nr_params != 1 {
+    let err_msg = format!("invalid parameters. FETCH_PROJECT_VERSIONS needs one parameter (the project key, like PROJ) but got {nr_params} instead. Params=[{params}]");
+    let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::InvalidParameters, err_msg)).await;
+  } else {
+    let project_key = splitted_params[0];
+
+    // versions are synchronised in the background, the request only reads the local database.
+    match get_versions_of_project_from_db(project_key, db_conn).await {
+      Ok(versions) => {
+        let _ = out_for_replies.send(Reply::result(request_id, ResultData::VersionList(versions))).await;
+      }
+      Err(e) => {
+        let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::LocalDatabase, e)).await;
+      }
+    }
+
+    if is_offline() {
+      send_stale_reply(&config, request_id, &out_for_replies, db_conn).await;
+    }
+  }
+
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
+}
@@ -1,5 +1,4 @@
 use std::collections::HashMap;
-use base64::Engine;
 use serde_json::{json, Map, Value};
 use sqlx::{Error, FromRow, Pool, Sqlite};
 use sqlx::types::JsonValue;
@@ -10,7 +9,12 @@ use crate::find_issues_that_need_updating::update_interesting_projects_in_db;
 use crate::get_config::Config;
 use crate::get_issue_details::{add_details_to_issue_in_db, get_json_for_issue};
 use crate::manage_field_table::get_fields_from_database;
-use crate::server::Reply;
+use crate::manage_issue_hierarchy::{get_children_from_db, get_hierarchy_from_db, get_hierarchy_from_json, Hierarchy};
+use crate::capabilities::SUPPORTED_TICKET_FORMATS;
+use crate::manage_issue_history::{get_history_of_issue_from_db, get_history_of_issue_from_server};
+use crate::server::{ErrorCode, HistoryItem, Reply, ResultData};
+use crate::offline_mode::is_offline;
+use crate::srv_offline_mode::send_stale_reply;
 
 #[derive(FromRow, Debug)]
 struct Relations {
@@ -136,6 +140,26 @@ fn format_links_for_html(inward_links: &[Relations], outward_links: &[Relations]
   links_str
 }
 
+fn format_hierarchy_for_html(hierarchy: &Hierarchy) -> String {
+  let hierarchy_str = hierarchy.parent
+    .iter()
+    .map(|x| ("parent", x))
+    .chain(hierarchy.children.iter().map(|x| ("child", x)))
+    .map(|(relation, x)| {
+      let other_key = html_escape::encode_safe(x.key.as_str());
+      let summary = html_escape::encode_safe(x.summary.as_deref().unwrap_or(""));
+      format!(
+"<div class=\"{relation}\">
+  <div class=\"other_key\">{other_key}</div>
+  <div class=\"key_summary\">{summary}</div>
+</div>")
+    })
+    .reduce(|a, b| { format!("{a}\n{b}")})
+    .unwrap_or(String::from("No parent or child issue found"));
+
+  hierarchy_str
+}
+
 fn format_comments_for_html(comments: &[Comment], db_conn: &Pool<Sqlite>) -> String {
   let comments = comments
     .iter()
@@ -160,6 +184,30 @@ fn format_comments_for_html(comments: &[Comment], db_conn: &Pool<Sqlite>) -> Str
   comments
 }
 
+fn format_history_for_html(history: &[HistoryItem]) -> String {
+  let history = history
+    .iter()
+    .map(|x| {
+      let author = html_escape::encode_safe(x.author.as_deref().unwrap_or("jira"));
+      let created = html_escape::encode_safe(x.created.as_str());
+      let field = html_escape::encode_safe(x.field.as_str());
+      let from = html_escape::encode_safe(x.from.as_deref().unwrap_or(""));
+      let to = html_escape::encode_safe(x.to.as_deref().unwrap_or(""));
+      format!(
+"<div class=\"history_item\">
+  <div class=\"history_created\">{created}</div>
+  <div class=\"history_author\">{author}</div>
+  <div class=\"history_field\">{field}</div>
+  <div class=\"history_from\">{from}</div>
+  <div class=\"history_to\">{to}</div>
+</div>")
+    })
+    .reduce(|a, b| format!("{a}\n{b}"))
+    .unwrap_or(String::from("no history found"));
+
+  history
+}
+
 fn get_summary<'a>(hashed_system_fields: &HashMap<&str, &'a Field>) -> &'a str {
   let summary = hashed_system_fields.get("Summary")
     .and_then(|x| x.value.as_str())
@@ -195,7 +243,9 @@ fn format_ticket_for_html(issue_key: &str,
                           custom_fields: &[Field],
                           inward_links: &[Relations],
                           outward_links: &[Relations],
+                          hierarchy: &Hierarchy,
                           comments: &[Comment],
+                          history: Option<&[HistoryItem]>,
                           db_conn: &Pool<Sqlite>) -> Result<String, String> {
 
   let hashed_system_fields = system_fields
@@ -208,14 +258,30 @@ fn format_ticket_for_html(issue_key: &str,
 
   let links_str = format_links_for_html(inward_links.as_ref(),
                                         outward_links.as_ref());
+  let hierarchy_str = format_hierarchy_for_html(hierarchy);
 
   let comments = format_comments_for_html(comments.as_ref(),
                                           db_conn);
 
   let description = indent_with(description.as_str(), "      ");
   let links_str = indent_with(links_str.as_str(), "      ");
+  let hierarchy_str = indent_with(hierarchy_str.as_str(), "      ");
   let comments = indent_with(comments.as_str(), "      ");
 
+  let history = match history {
+    None => { String::new() }
+    Some(history) => {
+      let history = format_history_for_html(history);
+      let history = indent_with(history.as_str(), "      ");
+      format!(r###"
+
+    <h2>History:</h2>
+    <div class="history">
+{history}
+    </div>"###)
+    }
+  };
+
   let res = format!(
 r###"<!DOCTYPE html>
 <html lang="en-GB">
@@ -238,10 +304,15 @@ r###"<!DOCTYPE html>
 {links_str}
     </div>
 
+    <h2>Hierarchy:</h2>
+    <div class="hierarchy">
+{hierarchy_str}
+    </div>
+
     <h2>Comments:</h2>
     <div class="comments">
 {comments}
-    </div>
+    </div>{history}
   </body>
 </html>
 "###);
@@ -268,6 +339,22 @@ fn format_links_for_markdown(inward_links: &[Relations], outward_links: &[Relati
   links_str
 }
 
+fn format_hierarchy_for_markdown(hierarchy: &Hierarchy) -> String {
+  let hierarchy_str = hierarchy.parent
+    .iter()
+    .map(|x| ("parent", x))
+    .chain(hierarchy.children.iter().map(|x| ("child", x)))
+    .map(|(relation, x)| {
+      let other_key = x.key.as_str();
+      let summary = x.summary.as_deref().unwrap_or("");
+      format!("{relation} {other_key}: {summary}")
+    })
+    .reduce(|a, b| { format!("{a}\n{b}")})
+    .unwrap_or(String::from("No parent or child issue found"));
+
+  hierarchy_str
+}
+
 fn format_comments_for_markdown(comments: &[Comment]) -> String {
   let comments = comments
     .iter()
@@ -286,12 +373,31 @@ last edited on: {last_modification}
   comments
 }
 
+fn format_history_for_markdown(history: &[HistoryItem]) -> String {
+  let history = history
+    .iter()
+    .map(|x| {
+      let author = x.author.as_deref().unwrap_or("jira");
+      let created = &x.created;
+      let field = &x.field;
+      let from = x.from.as_deref().unwrap_or("");
+      let to = x.to.as_deref().unwrap_or("");
+      format!("{created} {author} changed {field}: [{from}] -> [{to}]")
+    })
+    .reduce(|a, b| format!("{a}\n{b}"))
+    .unwrap_or(String::from("no history found"));
+
+  history
+}
+
 fn format_ticket_for_markdown(issue_key: &str,
                               system_fields: &[Field],
                               custom_fields: &[Field],
                               inward_links: &[Relations],
                               outward_links: &[Relations],
-                              comments: &[Comment]) -> Result<String, String> {
+                              hierarchy: &Hierarchy,
+                              comments: &[Comment],
+                              history: Option<&[HistoryItem]>) -> Result<String, String> {
 
   let hashed_system_fields = system_fields
     .iter()
@@ -303,6 +409,19 @@ fn format_ticket_for_markdown(issue_key: &str,
 
   let comments = format_comments_for_markdown(comments.as_ref());
   let links_str = format_links_for_markdown(inward_links.as_ref(), outward_links.as_ref());
+  let hierarchy_str = format_hierarchy_for_markdown(hierarchy);
+
+  let history = match history {
+    None => { String::new() }
+    Some(history) => {
+      let history = format_history_for_markdown(history);
+      format!("
+History:
+-----
+{history}
+")
+    }
+  };
 
   let res = format!(
     "{issue_key}: {summary}
@@ -316,10 +435,14 @@ Links:
 ----
 {links_str}
 
+Hierarchy:
+----
+{hierarchy_str}
+
 Comments:
 -----
 {comments}
-");
+{history}");
 
   Ok(res)
 
@@ -330,6 +453,8 @@ Comments:
 enum output_format {
   MARKDOWN,
   HTML,
+  MARKDOWN_WITH_HISTORY,
+  HTML_WITH_HISTORY,
 }
 
 impl output_format {
@@ -337,9 +462,16 @@ impl output_format {
     match format {
       "MARKDOWN" => Ok(output_format::MARKDOWN),
       "HTML" => Ok(output_format::HTML),
-      _ => Err(format!("Unknown format for ticket output. Supported: MARKDOWN and HTML. Requested: {format}"))
+      "MARKDOWN_WITH_HISTORY" => Ok(output_format::MARKDOWN_WITH_HISTORY),
+      "HTML_WITH_HISTORY" => Ok(output_format::HTML_WITH_HISTORY),
+      _ => Err(format!("Unknown format for ticket output. Supported: {supported}. Requested: {format}",
+                       supported = SUPPORTED_TICKET_FORMATS.join(", ")))
     }
   }
+
+  fn has_history(&self) -> bool {
+    matches!(self, output_format::MARKDOWN_WITH_HISTORY | output_format::HTML_WITH_HISTORY)
+  }
 }
 
 async fn get_jira_ticket_from_db(format: &output_format, issue_key: &str, db_conn: &Pool<Sqlite>) -> Result<String, String> {
@@ -359,6 +491,8 @@ async fn get_jira_ticket_from_db(format: &output_format, issue_key: &str, db_con
     Err(e) => { return Err(e.to_string()) }
   };
 
+  let hierarchy = get_hierarchy_from_db(issue_key, db_conn).await?;
+
   let custom_fields = get_fields_from_db(issue_key, true, db_conn).await;
   let custom_fields = match custom_fields {
     Ok(v) => { v }
@@ -381,15 +515,23 @@ async fn get_jira_ticket_from_db(format: &output_format, issue_key: &str, db_con
     }
   };
 
+  let history = if format.has_history() {
+    Some(get_history_of_issue_from_db(issue_key, db_conn).await?)
+  } else {
+    None
+  };
+
   let res = format_ticket(
                           issue_key,
                           format,
                           db_conn,
                           outward_links.as_slice(),
                           inward_links.as_slice(),
+                          &hierarchy,
                           custom_fields.as_slice(),
                           system_fields.as_slice(),
-                          comments.as_slice());
+                          comments.as_slice(),
+                          history.as_deref());
 
   res
 }
@@ -670,6 +812,10 @@ async fn get_jira_ticket_from_remote(format: &output_format, issue_key: &str, co
     Err(e) => { return Err(e.to_string()) }
   };
 
+  // children of epics aren't in the json of the epic
+  let children_in_db = get_children_from_db(issue_key, db_conn).await?;
+  let hierarchy = get_hierarchy_from_json(json_of_issue, children_in_db);
+
   let fields = get_fields_from_json(json_of_issue, db_conn).await;
   let fields = match fields {
     Ok(v) => { v }
@@ -685,14 +831,23 @@ async fn get_jira_ticket_from_remote(format: &output_format, issue_key: &str, co
     }
   };
 
+  // the changelog embedded in the issue is truncated, it has its own endpoint
+  let history = if format.has_history() {
+    Some(get_history_of_issue_from_server(config, issue_key).await?)
+  } else {
+    None
+  };
+
   let res = format_ticket(issue_key,
                           format,
                           db_conn,
                           outward_links.as_slice(),
                           inward_links.as_slice(),
+                          &hierarchy,
                           fields.custom_fields.as_slice(),
                           fields.system_fields.as_slice(),
-                          comments.as_slice());
+                          comments.as_slice(),
+                          history.as_deref());
 
   res
 }
@@ -702,25 +857,31 @@ fn format_ticket(issue_key: &str,
                  db_conn: &Pool<Sqlite>,
                  outward_links: &[Relations],
                  inward_links: &[Relations],
+                 hierarchy: &Hierarchy,
                  custom_fields: &[Field],
                  system_fields: &[Field],
-                 comments: &[Comment]) -> Result<String, String> {
+                 comments: &[Comment],
+                 history: Option<&[HistoryItem]>) -> Result<String, String> {
   let res = match format {
-    output_format::MARKDOWN => {
+    output_format::MARKDOWN | output_format::MARKDOWN_WITH_HISTORY => {
       format_ticket_for_markdown(issue_key,
                                  system_fields,
                                  custom_fields,
                                  inward_links,
                                  outward_links,
-                                 comments)
+                                 hierarchy,
+                                 comments,
+                                 history)
     }
-    output_format::HTML => {
+    output_format::HTML | output_format::HTML_WITH_HISTORY => {
       format_ticket_for_html(issue_key,
                              system_fields,
                              custom_fields,
                              inward_links,
                              outward_links,
+                             hierarchy,
                              comments,
+                             history,
                              db_conn)
     }
   };
@@ -732,7 +893,7 @@ pub(crate) async fn serve_fetch_ticket_request(config: Config,
                                                request_id: &str,
                                                params: &str,
                                                out_for_replies: tokio::sync::mpsc::Sender<Reply>, db_conn: &mut Pool<Sqlite>) {
-  let _ = out_for_replies.send(Reply(format!("{request_id} ACK\n"))).await;
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
 
   let splitted_params = params
     .split(',')
@@ -741,8 +902,8 @@ pub(crate) async fn serve_fetch_ticket_request(config: Config,
 
   let nr_params = splitted_params.len();
   if nr_params != 2 {
-    let err_msg = format!("{request_id} ERROR invalid parameters. FETCH_TICKET needs two parameters separated by commas but got {nr_params} instead. Params=[{params}]\n");
-    let _ = out_for_replies.send(Reply(err_msg)).await;
+    let err_msg = format!("invalid parameters. FETCH_TICKET needs two parameters separated by commas but got {nr_params} instead. Params=[{params}]");
+    let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::InvalidParameters, err_msg)).await;
   } else {
 
     let issue_key = &splitted_params[0];
@@ -753,44 +914,39 @@ pub(crate) async fn serve_fetch_ticket_request(config: Config,
       Ok(format) => {
         let old_data = get_jira_ticket_from_db(&format, issue_key, db_conn).await;
         match &old_data {
-          Ok(data) if data.is_empty() => {
-            // shouldn't happen since get_jira_ticket should at least give back the issue id
-            // in the reply
-            let _ = out_for_replies.send(Reply(format!("{request_id} RESULT\n"))).await;
-          }
           Ok(data) => {
-            let data = base64::engine::general_purpose::STANDARD.encode(data.as_bytes());
-            let _ = out_for_replies.send(Reply(format!("{request_id} RESULT {data}\n"))).await;
+            // data shouldn't be empty since get_jira_ticket should at least give back the issue id
+            // in the reply
+            let _ = out_for_replies.send(Reply::result(request_id, ResultData::Ticket(data.clone()))).await;
           }
           Err(e) => {
-            let _ = out_for_replies.send(Reply(format!("{request_id} ERROR {e}\n"))).await;
+            let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::LocalDatabase, e.to_string())).await;
           }
         }
 
-        let newest_data = get_jira_ticket_from_remote(&format, issue_key, &config, db_conn).await;
-        match (newest_data, old_data) {
-          (Ok(newest_data), Ok(old_data)) if newest_data == old_data => {}
-          (Ok(newest_data), _) => if newest_data.is_empty() {
-            // shouldn't happen since get_jira_ticket should at least give back the issue id
-            // in the reply
-            let _ = out_for_replies.send(Reply(format!("{request_id} RESULT\n"))).await;
-            // todo spawn an update_interesting_projects_in_db in background as we know some data is out of data
-          },
-          (Ok(newest_data), _) => {
-            let data = base64::engine::general_purpose::STANDARD.encode(newest_data.as_bytes());
-            let _ = out_for_replies.send(Reply(format!("{request_id} RESULT {data}\n"))).await;
-            // todo spawn an update_interesting_projects_in_db in background as we know some data is out of data
-          },
-          (Err(e), _) => {
-            let _ = out_for_replies.send(Reply(format!("{request_id} ERROR failed to get data from remote to see if local data is up to date or note: Err {e:?}\n"))).await;
-          }
-        };
+        if is_offline() {
+          send_stale_reply(&config, request_id, &out_for_replies, db_conn).await;
+        } else {
+          let newest_data = get_jira_ticket_from_remote(&format, issue_key, &config, db_conn).await;
+          match (newest_data, old_data) {
+            (Ok(newest_data), Ok(old_data)) if newest_data == old_data => {}
+            (Ok(newest_data), _) => {
+              let _ = out_for_replies.send(Reply::result(request_id, ResultData::Ticket(newest_data))).await;
+              // todo spawn an update_interesting_projects_in_db in background as we know some data is out of data
+            },
+            (Err(e), _) => {
+              let err_msg = format!("failed to get data from remote to see if local data is up to date or note: Err {e:?}");
+              let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::RemoteServer, err_msg)).await;
+            }
+          };
+        }
       },
       Err(e) => {
-        let _ = out_for_replies.send(Reply(format!("{request_id} ERROR failed to find a suitable format. Err: {e}\n"))).await;
+        let err_msg = format!("failed to find a suitable format. Err: {e}");
+        let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::InvalidParameters, err_msg)).await;
       }
     }
   }
 
-  let _ = out_for_replies.send(Reply(format!("{request_id} FINISHED\n"))).await;
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
 }
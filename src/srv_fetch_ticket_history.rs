@@ -0,0 +1,55 @@
+use sqlx::{Pool, Sqlite};
+use crate::get_config::Config;
+use crate::manage_issue_history::{get_history_of_issue_from_db, get_history_of_issue_from_server};
+use crate::offline_mode::is_offline;
+use crate::server::{ErrorCode, Reply, ResultData};
+use crate::srv_offline_mode::send_stale_reply;
+
+pub(crate) async fn serve_fetch_ticket_history(config: Config,
+                                               request_id: &str,
+                                               params: &str,
+                                               out_for_replies: tokio::sync::mpsc::Sender<Reply>,
+                                               db_conn: &mut Pool<Sqlite>) {
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
+
+  let splitted_params = params
+    .split(',')
+    .collect::<Vec<_>>();
+
+  let nr_params = splitted_params.len();
+  if nr_params != 1 {
+    let err_msg = format!("invalid parameters. FETCH_TICKET_HISTORY needs one parameter (the ticket id, like PROJ-123) but got {nr_params} instead. Params=[{params}]");
+    let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::InvalidParameters, err_msg)).await;
+  } else {
+    let issue_key = splitted_params[0];
+
+    let old_data = get_history_of_issue_from_db(issue_key, db_conn).await;
+    match old_data {
+      Ok(ref data) => {
+        let _ = out_for_replies.send(Reply::result(request_id, ResultData::TicketHistory(data.clone()))).await;
+      }
+      Err(ref e) => {
+        let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::LocalDatabase, e.to_string())).await;
+      }
+    }
+
+    if is_offline() {
+      send_stale_reply(&config, request_id, &out_for_replies, db_conn).await;
+    } else {
+      let new_data = get_history_of_issue_from_server(&config, issue_key).await;
+      match (new_data, old_data) {
+        (Ok(new_data), Ok(old_data)) if new_data == old_data => {}
+        (Ok(new_data), _) => {
+          let _ = out_for_replies.send(Reply::result(request_id, ResultData::TicketHistory(new_data))).await;
+          // todo: launch a background synchronisation since we know things changed
+        }
+        (Err(e), _) => {
+          let err_msg = format!("failed to get the history from remote to see if local data is up to date or not: Err {e}");
+          let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::RemoteServer, err_msg)).await;
+        }
+      }
+    }
+  }
+
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
+}
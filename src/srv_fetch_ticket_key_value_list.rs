@@ -1,13 +1,14 @@
 use std::collections::{HashMap, HashSet};
-use base64::Engine;
 use serde_json::{Map, Value};
 use sqlx::{Error, FromRow, Pool, Sqlite};
 use crate::get_config::Config;
 use crate::get_issue_details::{get_json_for_issue, IssueAttachment};
-use crate::server::Reply;
+use crate::offline_mode::is_offline;
+use crate::server::{ErrorCode, KeyValueField, Reply, ResultData};
+use crate::srv_offline_mode::send_stale_reply;
 
 #[derive(FromRow, Debug, Hash, PartialEq, Eq)]
-struct key_value_in_db {
+pub(crate) struct key_value_in_db {
   field_key: String,
   field_value: String,
 }
@@ -19,7 +20,7 @@ struct key_human_name {
   human_name: String,
 }
 
-async fn get_key_human_hash_from_db(db_conn: &Pool<Sqlite>) -> Result<HashMap<String, String>, String> {
+pub(crate) async fn get_key_human_hash_from_db(db_conn: &Pool<Sqlite>) -> Result<HashMap<String, String>, String> {
   // we need to get the uuid from the database.
 
   let query_str =
@@ -42,7 +43,7 @@ async fn get_key_human_hash_from_db(db_conn: &Pool<Sqlite>) -> Result<HashMap<St
   Ok(res)
 }
 
-fn format_key_value_list<'a>(kv_list: &'a [key_value_in_db], key_to_human: &'a HashMap<String, String>) -> String {
+pub(crate) fn format_key_value_list<'a>(kv_list: &'a [key_value_in_db], key_to_human: &'a HashMap<String, String>) -> Vec<KeyValueField> {
 
   let get_human_name = |key: &'a str| {
     let v = key_to_human.get(key);
@@ -59,12 +60,13 @@ fn format_key_value_list<'a>(kv_list: &'a [key_value_in_db], key_to_human: &'a H
     .iter()
     .map(|x| {
       let human_name = get_human_name(x.field_key.as_str());
-      let key_as_bas64 = base64::engine::general_purpose::STANDARD.encode(human_name);
-      let value_as_base64 = base64::engine::general_purpose::STANDARD.encode(x.field_value.as_bytes());
-      format!("{key_as_bas64}:{value_as_base64}")
+      KeyValueField {
+        key: x.field_key.clone(),
+        name: human_name.to_string(),
+        value: x.field_value.clone(),
+      }
     })
-    .reduce(|a, b| format!("{a},{b}"))
-    .unwrap_or_default();
+    .collect::<Vec<_>>();
 
   res
 }
@@ -126,7 +128,7 @@ fn is_same_key_value_vector(param1: &[key_value_in_db], param2: &[key_value_in_d
   res
 }
 
-async fn get_ticket_key_value_list_from_db(issue_key: &str, db_conn: &Pool<Sqlite>) -> Result<Vec<key_value_in_db>, String> {
+pub(crate) async fn get_ticket_key_value_list_from_db(issue_key: &str, db_conn: &Pool<Sqlite>) -> Result<Vec<key_value_in_db>, String> {
   let query_str =
     "SELECT field_id AS field_key, field_value
      FROM IssueField
@@ -152,7 +154,7 @@ pub(crate) async fn serve_fetch_ticket_key_value_fields(config: Config,
                                                     params: &str,
                                                     out_for_replies: tokio::sync::mpsc::Sender<Reply>,
                                                     db_conn: &mut Pool<Sqlite>) {
-  let _ = out_for_replies.send(Reply(format!("{request_id} ACK\n"))).await;
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
 
   let splitted_params = params
     .split(',')
@@ -160,53 +162,49 @@ pub(crate) async fn serve_fetch_ticket_key_value_fields(config: Config,
 
   let nr_params = splitted_params.len();
   if nr_params != 1 {
-    let err_msg = format!("{request_id} ERROR invalid parameters. FETCH_TICKET_KEY_VALUE_FIELDS need one parameter (the ticket id, like PROJ-123) but got {nr_params} instead. Params=[{params}]\n");
-    let _ = out_for_replies.send(Reply(err_msg)).await;
+    let err_msg = format!("invalid parameters. FETCH_TICKET_KEY_VALUE_FIELDS need one parameter (the ticket id, like PROJ-123) but got {nr_params} instead. Params=[{params}]");
+    let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::InvalidParameters, err_msg)).await;
   } else {
     let issue_key = splitted_params[0];
 
     let key_to_human = get_key_human_hash_from_db(db_conn).await;
     match key_to_human {
       Err(e) => {
-        let _ = out_for_replies.send(Reply(format!("{request_id} ERROR failed to get the mapping jira field key to human key from local db. Err: {e}\n"))).await;
+        let err_msg = format!("failed to get the mapping jira field key to human key from local db. Err: {e}");
+        let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::LocalDatabase, err_msg)).await;
       }
       Ok(key_to_human) => {
         let old_data = get_ticket_key_value_list_from_db(issue_key, db_conn).await;
         match &old_data {
           Ok(data) => {
-            let base_64_encoded = format_key_value_list(data.as_slice(), &key_to_human);
-            if base_64_encoded.is_empty() {
-              // shouldn't happen since some key are necessary, e.g. "last updated", "summary", ...
-              let _ = out_for_replies.send(Reply(format!("{request_id} RESULT\n"))).await;
-            } else {
-              let _ = out_for_replies.send(Reply(format!("{request_id} RESULT {base_64_encoded}\n"))).await;
-            }
+            // data shouldn't be empty since some key are necessary, e.g. "last updated", "summary", ...
+            let fields = format_key_value_list(data.as_slice(), &key_to_human);
+            let _ = out_for_replies.send(Reply::result(request_id, ResultData::KeyValueFields(fields))).await;
           }
           Err(e) => {
-            let _ = out_for_replies.send(Reply(format!("{request_id} ERROR {e}\n"))).await;
+            let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::LocalDatabase, e.to_string())).await;
           }
         }
 
-        let new_data = get_ticket_key_value_list_from_json(&config, issue_key).await;
-
-        match (&new_data, &old_data) {
-          (Ok(new_data), Ok(old_data)) if is_same_key_value_vector(new_data, old_data) => {}
-          (Ok(new_data), _) => {
-            let base_64_encoded = format_key_value_list(new_data.as_slice(), &key_to_human);
-            if base_64_encoded.is_empty() {
-              // shouldn't happen since some key are necessary, e.g. "last updated", "summary", ...
-              let _ = out_for_replies.send(Reply(format!("{request_id} RESULT\n"))).await;
-            } else {
-              let _ = out_for_replies.send(Reply(format!("{request_id} RESULT {base_64_encoded}\n"))).await;
+        if is_offline() {
+          send_stale_reply(&config, request_id, &out_for_replies, db_conn).await;
+        } else {
+          let new_data = get_ticket_key_value_list_from_json(&config, issue_key).await;
+
+          match (&new_data, &old_data) {
+            (Ok(new_data), Ok(old_data)) if is_same_key_value_vector(new_data, old_data) => {}
+            (Ok(new_data), _) => {
+              let fields = format_key_value_list(new_data.as_slice(), &key_to_human);
+              let _ = out_for_replies.send(Reply::result(request_id, ResultData::KeyValueFields(fields))).await;
+              // todo: launch a background synchronisation since we know things changed
+            }
+            (Err(e), _) => {
+              let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::RemoteServer, e.to_string())).await;
             }
-            // todo: launch a background synchronisation since we know things changed
-          }
-          (Err(e), _) => {
-            let _ = out_for_replies.send(Reply(format!("{request_id} ERROR {e}\n"))).await;
           }
         }
       }
     }
   }
-  let _ = out_for_replies.send(Reply(format!("{request_id} FINISHED\n"))).await;
-}
\ No newline at end of file
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
+}
@@ -1,28 +1,34 @@
 use sqlx::{Error, FromRow, Pool, Sqlite};
 use crate::find_issues_that_need_updating::update_interesting_projects_in_db;
 use crate::get_config::Config;
-use crate::server::Reply;
+use crate::offline_mode::is_offline;
+use crate::sync_progress::ProgressReporter;
+use crate::srv_offline_mode::send_stale_reply;
+use crate::server::{ErrorCode, Reply, ResultData};
 
 #[derive(FromRow)]
-struct keys_in_db {
-  keys: String,
+struct key_in_db {
+  key: String,
 }
 
-async fn get_ticket_list(db_conn: &mut Pool<Sqlite>) -> Result<String, String> {
+async fn get_ticket_list(db_conn: &mut Pool<Sqlite>) -> Result<Vec<String>, String> {
   let query_str =
-    "SELECT group_concat(key, ',') AS keys
-     FROM (
-       SELECT key
-       FROM Issue
-       ORDER BY jira_id ASC
-     );"; // ordering used so it is easy to check for changes
-                         // in the db
-  let query_res = sqlx::query_as::<_, keys_in_db>(query_str)
-    .fetch_one(&*db_conn)
+    "SELECT key
+     FROM Issue
+     ORDER BY jira_id ASC;"; // ordering used so it is easy to check for changes
+                             // in the db
+  let query_res = sqlx::query_as::<_, key_in_db>(query_str)
+    .fetch_all(&*db_conn)
     .await;
-  
+
   match query_res {
-    Ok(v) => { Ok(v.keys) }
+    Ok(v) => {
+      let keys = v
+        .into_iter()
+        .map(|x| x.key)
+        .collect::<Vec<_>>();
+      Ok(keys)
+    }
     Err(e) => {
       Err(format!("Error occurred while querying the db for the list of jira keys. Err: {e:?}"))
     }
@@ -33,40 +39,39 @@ pub(crate) async fn serve_fetch_ticket_list_request(config: Config,
                                                     request_id: &str,
                                                     out_for_replies: tokio::sync::mpsc::Sender<Reply>,
                                                     db_conn: &mut Pool<Sqlite>) {
-  let _ = out_for_replies.send(Reply(format!("{request_id} ACK\n"))).await;
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
 
+  // an empty list happens when we didn't synchronise to the remote even once, or all
+  // tickets are private, or none of the interesting projects exist
   let old_data = get_ticket_list(db_conn).await;
   match &old_data {
-    Ok(data) if data.is_empty() => {
-      // case where we didn't synchronise to the remote even once, or all tickets are
-      // private, or none of the interesting projects exist
-      let _ = out_for_replies.send(Reply(format!("{request_id} RESULT\n"))).await;
-    }
     Ok(data) => {
-      let _ = out_for_replies.send(Reply(format!("{request_id} RESULT {data}\n"))).await;
+      let _ = out_for_replies.send(Reply::result(request_id, ResultData::TicketList(data.clone()))).await;
     }
     Err(e) => {
-      let _ = out_for_replies.send(Reply(format!("{request_id} ERROR {e}\n"))).await;
+      let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::LocalDatabase, e.to_string())).await;
     }
   }
 
+  if is_offline() {
+    send_stale_reply(&config, request_id, &out_for_replies, db_conn).await;
+    let _ = out_for_replies.send(Reply::finished(request_id)).await;
+    return;
+  }
+
   let mut db_conn = db_conn;
-  let _ = update_interesting_projects_in_db(&config, &mut db_conn).await;
+  let _ = update_interesting_projects_in_db(&config, &mut db_conn, &ProgressReporter::disabled()).await;
 
   let new_data = get_ticket_list(db_conn).await;
-  match (&new_data, &old_data) {
-    (Ok(new_data), Ok(old_data)) if new_data == old_data => {}
-    (Ok(new_data), _) if new_data.is_empty() => {
-      // case where everything got deleted
-      let _ = out_for_replies.send(Reply(format!("{request_id} RESULT\n"))).await;
-    },
+  match (new_data, &old_data) {
+    (Ok(new_data), Ok(old_data)) if new_data == *old_data => {}
     (Ok(new_data), _) => {
-      let _ = out_for_replies.send(Reply(format!("{request_id} RESULT {new_data}\n"))).await;
+      let _ = out_for_replies.send(Reply::result(request_id, ResultData::TicketList(new_data))).await;
     }
     (Err(e), _) => {
-      let _ = out_for_replies.send(Reply(format!("{request_id} ERROR {e}\n"))).await;
+      let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::LocalDatabase, e)).await;
     }
   }
 
-  let _ = out_for_replies.send(Reply(format!("{request_id} FINISHED\n"))).await;
-}
\ No newline at end of file
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
+}
@@ -0,0 +1,28 @@
+use sqlx::{Pool, Sqlite};
+use crate::get_config::Config;
+use crate::manage_status_priority_resolution_tables::get_ticket_statuses_from_db;
+use crate::offline_mode::is_offline;
+use crate::server::{ErrorCode, Reply, ResultData};
+use crate::srv_offline_mode::send_stale_reply;
+
+pub(crate) async fn serve_fetch_ticket_status_list(config: Config,
+                                                   request_id: &str,
+                                                   out_for_replies: tokio::sync::mpsc::Sender<Reply>,
+                                                   db_conn: &mut Pool<Sqlite>) {
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
+
+  match get_ticket_statuses_from_db(db_conn).await {
+    Ok(tickets) => {
+      let _ = out_for_replies.send(Reply::result(request_id, ResultData::TicketStatusList(tickets))).await;
+    }
+    Err(e) => {
+      let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::LocalDatabase, e)).await;
+    }
+  }
+
+  if is_offline() {
+    send_stale_reply(&config, request_id, &out_for_replies, db_conn).await;
+  }
+
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
+}
@@ -0,0 +1,35 @@
+use sqlx::{Pool, Sqlite};
+use crate::get_config::Config;
+use crate::manage_issue_watchers::get_watchers_of_ticket_from_db;
+use crate::offline_mode::is_offline;
+use crate::server::{ErrorCode, Reply, ResultData};
+use crate::srv_offline_mode::send_stale_reply;
+
+pub(crate) async fn serve_fetch_ticket_watchers(config: Config,
+                                                request_id: &str,
+                                                params: &str,
+                                                out_for_replies: tokio::sync::mpsc::Sender<Reply>,
+                                                db_conn: &mut Pool<Sqlite>) {
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
+
+  if params.is_empty() || params.contains(',') {
+    let err_msg = format!("invalid parameters. FETCH_TICKET_WATCHERS needs one parameter (the ticket key, like PROJ-123). Params=[{params}]");
+    let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::InvalidParameters, err_msg)).await;
+  } else {
+    // the watchers are synchronised along with the ticket, the request only reads the local database.
+    match get_watchers_of_ticket_from_db(params, db_conn).await {
+      Ok(people) => {
+        let _ = out_for_replies.send(Reply::result(request_id, ResultData::PersonList(people))).await;
+      }
+      Err(e) => {
+        let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::LocalDatabase, e)).await;
+      }
+    }
+
+    if is_offline() {
+      send_stale_reply(&config, request_id, &out_for_replies, db_conn).await;
+    }
+  }
+
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
+}
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use serde_json::Value;
+use sqlx::{Pool, Sqlite};
+use tokio::task::JoinSet;
+use crate::get_config::Config;
+use crate::get_issue_details::add_details_to_issue_in_db;
+use crate::get_json_from_url::get_json_from_url;
+use crate::offline_mode::is_offline;
+use crate::server::{ErrorCode, KeyValueField, Reply, ResultData};
+use crate::srv_offline_mode::send_stale_reply;
+use crate::srv_fetch_ticket_key_value_list::{format_key_value_list, get_key_human_hash_from_db, get_ticket_key_value_list_from_db};
+use crate::sync_progress::get_number_of_pages;
+use crate::utils::get_str_without_surrounding_quotes;
+
+// about 1KiB of url for the keys, far from the limits of jira and of the proxies in front of it
+const MAX_KEYS_PER_SEARCH: usize = 50;
+
+async fn get_one_page_of_last_updates(config: &Config,
+                                      jql: &str,
+                                      start: i64,
+                                      max_result_per_query: i64) -> Result<Value, String> {
+  // validateQuery=warn makes jira ignore keys it doesn't know instead of failing the whole search
+  let query = format!("/rest/api/3/search?jql={jql}&fields=updated&validateQuery=warn&startAt={start}&maxResults={max_result_per_query}");
+  get_json_from_url(config, query.as_str()).await.map_err(String::from)
+}
+
+fn get_last_updates_from_json(json: &Value) -> Vec<(String /* issue key */, String /* last update */)> {
+  json
+    .get("issues")
+    .and_then(|x| x.as_array())
+    .map(|issues| {
+      issues
+        .iter()
+        .filter_map(|x| {
+          let key = x.get("key").and_then(|x| x.as_str());
+          let updated = x
+            .get("fields")
+            .and_then(|x| x.get("updated"))
+            .and_then(|x| x.as_str());
+          match (key, updated) {
+            (Some(key), Some(updated)) => { Some((key.to_string(), updated.to_string())) }
+            _ => {
+              eprintln!("Error: search result is missing the key or the last update time of an issue: {x}");
+              None
+            }
+          }
+        })
+        .collect::<Vec<_>>()
+    })
+    .unwrap_or_default()
+}
+
+async fn get_remote_last_updates_of_chunk(config: &Config, issue_keys: &[&str]) -> Result<HashMap<String, String>, String> {
+  // issue keys were validated when parsing the request, they are safe to put in the jql as is.
+  let jql = format!("key+in+%28{keys}%29", keys = issue_keys.join("%2C"));
+
+  let max_result_per_query = -1; // -1 is a special value telling jira "no limit"
+                                 // the returned json will tell us what is the configured limit
+  let first_json = get_one_page_of_last_updates(config, jql.as_str(), 0, max_result_per_query).await;
+  let first_json = match first_json {
+    Ok(v) => { v }
+    Err(e) => { return Err(format!("Failed to get the last update time of tickets {keys} from the jira server. Err: {e}", keys = issue_keys.join(","))) }
+  };
+
+  let max_result_per_query = first_json
+    .get("maxResults")
+    .and_then(|x| x.as_i64())
+    .unwrap_or(100);
+  let total = first_json
+    .get("total")
+    .and_then(|x| x.as_i64());
+
+  let mut res = get_last_updates_from_json(&first_json)
+    .into_iter()
+    .collect::<HashMap<_, _>>();
+
+  let nr_pages = get_number_of_pages(total, max_result_per_query) as i64;
+  for i in 1..nr_pages {
+    let start = max_result_per_query * i;
+    let next_json = get_one_page_of_last_updates(config, jql.as_str(), start, max_result_per_query).await;
+    match next_json {
+      Ok(next_json) => {
+        res.extend(get_last_updates_from_json(&next_json));
+      }
+      Err(e) => {
+        return Err(format!("Failed to get the last update time of tickets {keys} starting from {start} from the jira server. Err: {e}", keys = issue_keys.join(",")));
+      }
+    }
+  }
+
+  Ok(res)
+}
+
+// Asks the jira server for the last update time of the tickets with one search per chunk of keys,
+// instead of querying them one by one. Chunks keep the url of the search short.
+async fn get_remote_last_updates(config: &Config, issue_keys: &[&str]) -> Result<HashMap<String, String>, String> {
+  let mut res = HashMap::new();
+  for chunk in issue_keys.chunks(MAX_KEYS_PER_SEARCH) {
+    res.extend(get_remote_last_updates_of_chunk(config, chunk).await?);
+  }
+  Ok(res)
+}
+
+async fn get_tickets_key_value_fields_from_db(issue_keys: &[&str],
+                                              key_to_human: &HashMap<String, String>,
+                                              db_conn: &Pool<Sqlite>) -> Result<Vec<(String, Vec<KeyValueField>)>, String> {
+  let mut res = Vec::with_capacity(issue_keys.len());
+  for issue_key in issue_keys {
+    let data = get_ticket_key_value_list_from_db(issue_key, db_conn).await?;
+    // tickets have a few mandatory fields, e.g. "last updated", "summary", ...
+    // no field at all means the ticket isn't in the local database.
+    if !data.is_empty() {
+      res.push((issue_key.to_string(), format_key_value_list(data.as_slice(), key_to_human)));
+    }
+  }
+  Ok(res)
+}
+
+fn get_local_last_update(fields: &[KeyValueField]) -> Option<&str> {
+  fields
+    .iter()
+    .find(|x| x.key == "updated")
+    .map(|x| get_str_without_surrounding_quotes(x.value.as_str()))
+}
+
+pub(crate) async fn serve_fetch_tickets_key_value_fields(config: Config,
+                                                         request_id: &str,
+                                                         issue_keys: &[String],
+                                                         out_for_replies: tokio::sync::mpsc::Sender<Reply>,
+                                                         db_conn: &mut Pool<Sqlite>) {
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
+
+  let key_to_human = get_key_human_hash_from_db(db_conn).await;
+  let key_to_human = match key_to_human {
+    Ok(v) => { v }
+    Err(e) => {
+      let err_msg = format!("failed to get the mapping jira field key to human key from local db. Err: {e}");
+      let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::LocalDatabase, err_msg)).await;
+      let _ = out_for_replies.send(Reply::finished(request_id)).await;
+      return;
+    }
+  };
+
+  let issue_keys = issue_keys
+    .iter()
+    .map(|x| x.as_str())
+    .collect::<Vec<_>>();
+
+  let local_tickets = get_tickets_key_value_fields_from_db(issue_keys.as_slice(), &key_to_human, db_conn).await;
+  let local_tickets = match local_tickets {
+    Ok(v) => { v }
+    Err(e) => {
+      let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::LocalDatabase, e)).await;
+      let _ = out_for_replies.send(Reply::finished(request_id)).await;
+      return;
+    }
+  };
+
+  let missing_keys = issue_keys
+    .iter()
+    .filter(|key| !local_tickets.iter().any(|(x, _)| x == *key))
+    .copied()
+    .collect::<Vec<_>>();
+  if !missing_keys.is_empty() {
+    let err_msg = format!("The following tickets are not in the local database: {keys}", keys = missing_keys.join(","));
+    let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::LocalDatabase, err_msg)).await;
+  }
+
+  if local_tickets.is_empty() {
+    let _ = out_for_replies.send(Reply::finished(request_id)).await;
+    return;
+  }
+
+  let local_last_updates = local_tickets
+    .iter()
+    .map(|(key, fields)| (key.clone(), get_local_last_update(fields).map(|x| x.to_string())))
+    .collect::<Vec<_>>();
+  let _ = out_for_replies.send(Reply::result(request_id, ResultData::TicketsKeyValueFields(local_tickets))).await;
+
+  if is_offline() {
+    send_stale_reply(&config, request_id, &out_for_replies, db_conn).await;
+    let _ = out_for_replies.send(Reply::finished(request_id)).await;
+    return;
+  }
+
+  let local_keys = local_last_updates
+    .iter()
+    .map(|(key, _)| key.as_str())
+    .collect::<Vec<_>>();
+  let remote_last_updates = get_remote_last_updates(&config, local_keys.as_slice()).await;
+  let remote_last_updates = match remote_last_updates {
+    Ok(v) => { v }
+    Err(e) => {
+      let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::RemoteServer, e)).await;
+      let _ = out_for_replies.send(Reply::finished(request_id)).await;
+      return;
+    }
+  };
+
+  let outdated_keys = local_last_updates
+    .iter()
+    .filter(|(key, local_last_update)| {
+      match (remote_last_updates.get(key), local_last_update) {
+        (Some(remote), Some(local)) => { remote != local }
+        (Some(_), None) => { true }
+        (None, _) => { false }
+      }
+    })
+    .map(|(key, _)| key.as_str())
+    .collect::<Vec<_>>();
+
+  if !outdated_keys.is_empty() {
+    // the number of requests actually sent to jira at once is limited by the http client
+    let mut tasks = outdated_keys
+      .iter()
+      .map(|issue_key| {
+        let config = config.clone();
+        let db_conn = db_conn.clone();
+        let issue_key = issue_key.to_string();
+        async move { add_details_to_issue_in_db(&config, issue_key.as_str(), &db_conn).await }
+      })
+      .collect::<JoinSet<_>>();
+    while let Some(res) = tasks.join_next().await {
+      match res {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => { eprintln!("{e}") }
+        Err(e) => { eprintln!("Task getting the details of a ticket failed. Err: {e:?}") }
+      }
+    }
+
+    let updated_tickets = get_tickets_key_value_fields_from_db(outdated_keys.as_slice(), &key_to_human, db_conn).await;
+    match updated_tickets {
+      Ok(updated_tickets) => {
+        let _ = out_for_replies.send(Reply::result(request_id, ResultData::TicketsKeyValueFields(updated_tickets))).await;
+      }
+      Err(e) => {
+        let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::LocalDatabase, e)).await;
+      }
+    }
+  }
+
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
+}
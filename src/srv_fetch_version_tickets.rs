@@ -0,0 +1,39 @@
+use sqlx::{Pool, Sqlite};
+use crate::get_config::Config;
+use crate::manage_versions_and_components::get_tickets_of_version_from_db;
+use crate::offline_mode::is_offline;
+use crate::server::{ErrorCode, Reply, ResultData};
+use crate::srv_offline_mode::send_stale_reply;
+
+pub(crate) async fn serve_fetch_version_tickets(config: Config,
+                                                request_id: &str,
+                                                params: &str,
+                                                out_for_replies: tokio::sync::mpsc::Sender<Reply>,
+                                                db_conn: &mut Pool<Sqlite>) {
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
+
+  match params.parse::<i64>() {
+    Err(_) => {
+      let err_msg = format!("invalid parameters. FETCH_VERSION_TICKETS needs one parameter (the version id, like 10042). Params=[{params}]");
+      let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::InvalidParameters, err_msg)).await;
+    }
+    Ok(version_id) => {
+      // the fix versions of the tickets are synchronised along with the tickets, the request only
+      // reads the local database.
+      match get_tickets_of_version_from_db(version_id, db_conn).await {
+        Ok(keys) => {
+          let _ = out_for_replies.send(Reply::result(request_id, ResultData::TicketList(keys))).await;
+        }
+        Err(e) => {
+          let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::LocalDatabase, e)).await;
+        }
+      }
+
+      if is_offline() {
+        send_stale_reply(&config, request_id, &out_for_replies, db_conn).await;
+      }
+    }
+  }
+
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
+}
@@ -0,0 +1,29 @@
+use sqlx::{Pool, Sqlite};
+use crate::get_config::Config;
+use crate::manage_issue_watchers::get_watched_tickets_from_db;
+use crate::offline_mode::is_offline;
+use crate::server::{ErrorCode, Reply, ResultData};
+use crate::srv_offline_mode::send_stale_reply;
+
+pub(crate) async fn serve_fetch_watched_tickets(config: Config,
+                                                request_id: &str,
+                                                out_for_replies: tokio::sync::mpsc::Sender<Reply>,
+                                                db_conn: &mut Pool<Sqlite>) {
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
+
+  // the watchers are synchronised along with the tickets, the request only reads the local database.
+  match get_watched_tickets_from_db(db_conn).await {
+    Ok(keys) => {
+      let _ = out_for_replies.send(Reply::result(request_id, ResultData::TicketList(keys))).await;
+    }
+    Err(e) => {
+      let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::LocalDatabase, e)).await;
+    }
+  }
+
+  if is_offline() {
+    send_stale_reply(&config, request_id, &out_for_replies, db_conn).await;
+  }
+
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
+}
@@ -0,0 +1,38 @@
+use sqlx::{Pool, Sqlite};
+use crate::get_config::Config;
+use crate::manage_synchronisation_table::get_oldest_synchronisation;
+use crate::offline_mode::set_offline;
+use crate::server::{ErrorCode, Reply};
+
+pub(crate) async fn serve_set_offline_mode(request_id: &str,
+                                           offline: bool,
+                                           out_for_replies: tokio::sync::mpsc::Sender<Reply>) {
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
+  set_offline(offline);
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
+}
+
+pub(crate) async fn serve_synchronise_while_offline(request_id: &str,
+                                                    out_for_replies: tokio::sync::mpsc::Sender<Reply>) {
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
+  let err_msg = String::from("Can't synchronise with the jira server while in offline mode. Send SET_OFFLINE_MODE OFF first");
+  let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::Offline, err_msg)).await;
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
+}
+
+pub(crate) async fn serve_modification_while_offline(request_id: &str,
+                                                    out_for_replies: tokio::sync::mpsc::Sender<Reply>) {
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
+  let err_msg = String::from("Can't modify tickets on the jira server while in offline mode. Send SET_OFFLINE_MODE OFF first");
+  let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::Offline, err_msg)).await;
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
+}
+
+// handlers answering from the local database only tell clients the data may be out of date
+pub(crate) async fn send_stale_reply(config: &Config,
+                                     request_id: &str,
+                                     out_for_replies: &tokio::sync::mpsc::Sender<Reply>,
+                                     db_conn: &Pool<Sqlite>) {
+  let last_synchronisation = get_oldest_synchronisation(config.interesting_projects(), db_conn).await;
+  let _ = out_for_replies.send(Reply::stale(request_id, last_synchronisation)).await;
+}
@@ -0,0 +1,27 @@
+use tokio::sync::broadcast::error::RecvError;
+use crate::server::{ErrorCode, Reply};
+use crate::ticket_events::subscribe_to_ticket_changes;
+
+pub(crate) async fn serve_subscribe(request_id: &str,
+                                    out_for_replies: tokio::sync::mpsc::Sender<Reply>) {
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
+
+  // a subscription stays open until it gets cancelled, or the server exits.
+  let mut ticket_changes = subscribe_to_ticket_changes();
+  loop {
+    let reply = match ticket_changes.recv().await {
+      Ok(changes) => { Reply::event(request_id, changes) }
+      Err(RecvError::Lagged(nr_lost_events)) => {
+        let err_msg = format!("{nr_lost_events} events were lost because the client didn't read them fast enough. Fetch the tickets again to get up-to-date data.");
+        Reply::error(request_id, ErrorCode::EventsLost, err_msg)
+      }
+      Err(RecvError::Closed) => { break }
+    };
+
+    if out_for_replies.send(reply).await.is_err() {
+      break;
+    }
+  }
+
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
+}
@@ -1,16 +1,25 @@
 use sqlx::{Pool, Sqlite};
 use crate::get_config::Config;
+use crate::manage_boards_and_sprints::update_boards_and_sprints_in_db;
+use crate::manage_deleted_issues::reconcile_interesting_projects_in_db;
 use crate::manage_interesting_projects::initialise_interesting_projects_in_db;
-use crate::server::Reply;
+use crate::server::{Reply, ResultData};
+use crate::sync_progress::ProgressReporter;
 
 pub(crate) async fn serve_synchronise_all(config: Config,
                                              request_id: &str,
                                              out_for_replies: tokio::sync::mpsc::Sender<Reply>,
                                              db_conn: &mut Pool<Sqlite>) {
-  let _ = out_for_replies.send(Reply(format!("{request_id} ACK\n"))).await;
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
 
   let mut db_conn = db_conn;
-  initialise_interesting_projects_in_db(&config, &mut db_conn).await;
+  let progress = ProgressReporter::new(request_id, out_for_replies.clone());
+  initialise_interesting_projects_in_db(&config, &mut db_conn, &progress).await;
+  let deleted_keys = reconcile_interesting_projects_in_db(&config, db_conn).await;
+  update_boards_and_sprints_in_db(&config, db_conn).await;
+  if !deleted_keys.is_empty() {
+    let _ = out_for_replies.send(Reply::result(request_id, ResultData::DeletedTickets(deleted_keys))).await;
+  }
 
-  let _ = out_for_replies.send(Reply(format!("{request_id} FINISHED\n"))).await;
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
 }
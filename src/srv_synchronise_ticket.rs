@@ -1,15 +1,16 @@
 use sqlx::{Pool, Sqlite};
 use crate::find_issues_that_need_updating::update_interesting_projects_in_db;
 use crate::get_config::Config;
+use crate::sync_progress::ProgressReporter;
 use crate::get_issue_details::add_details_to_issue_in_db;
-use crate::server::Reply;
+use crate::server::{ErrorCode, Reply};
 
 pub(crate) async fn serve_synchronise_ticket(config: Config,
                                                request_id: &str,
                                                params: &str,
                                                out_for_replies: tokio::sync::mpsc::Sender<Reply>,
                                              db_conn: &mut Pool<Sqlite>) {
-  let _ = out_for_replies.send(Reply(format!("{request_id} ACK\n"))).await;
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
 
   let splitted_params = params
     .split(',')
@@ -17,8 +18,8 @@ pub(crate) async fn serve_synchronise_ticket(config: Config,
 
   let nr_params = splitted_params.len();
   if nr_params != 1 {
-    let err_msg = format!("{request_id} ERROR invalid parameters. SYNCHRONISE_TICKET needs one parameter (a jira issue like PROJ-123) but got {nr_params} instead. Params=[{params}]\n");
-    let _ = out_for_replies.send(Reply(err_msg)).await;
+    let err_msg = format!("invalid parameters. SYNCHRONISE_TICKET needs one parameter (a jira issue like PROJ-123) but got {nr_params} instead. Params=[{params}]");
+    let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::InvalidParameters, err_msg)).await;
   } else {
     let issue_key = splitted_params[0];
 
@@ -33,14 +34,15 @@ pub(crate) async fn serve_synchronise_ticket(config: Config,
     // this request. From a user point of view, this request is finished when the given
     // ticket is guaranteed to be up to date.
     let mut db_conn = db_conn;
-    update_interesting_projects_in_db(&config, &mut db_conn).await;
+    update_interesting_projects_in_db(&config, &mut db_conn, &ProgressReporter::disabled()).await;
 
-    //Ideally we would simply call add_details_to_issue_in_db, but the function update_interesting_projects_in_db
-    // relies on tickets not being updated alone in order to find out which ticket to update and which not.
-    // when running the synchronise_updated request.
-    //
-    //    add_details_to_issue_in_db(&config, issue_key, &mut db_conn).await;
+    // synchronisation queries the tickets updated since the last synchronisation point, so
+    // refreshing a single ticket doesn't prevent the next SYNCHRONISE_UPDATED from seeing
+    // the other updated tickets.
+    if let Err(e) = add_details_to_issue_in_db(&config, issue_key, db_conn).await {
+      let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::RemoteServer, e)).await;
+    }
   }
 
-  let _ = out_for_replies.send(Reply(format!("{request_id} FINISHED\n"))).await;
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
 }
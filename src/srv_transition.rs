@@ -0,0 +1,142 @@
+use sqlx::{Pool, Sqlite};
+use crate::edit_issue_field::{find_field_in_db, get_field_value, FieldValue};
+use crate::get_config::Config;
+use crate::manage_issue_transitions::{find_transition_of_issue, transition_issue};
+use crate::server::{is_valid_issue_key, ErrorCode, Reply, ResultData};
+use crate::utils::get_fields_from_text_params;
+
+#[derive(Eq, PartialEq, Debug)]
+pub(crate) struct TransitionParams {
+  issue_key: String,
+  transition: String,
+  fields: Vec<(String /* field */, String /* value */)>,
+}
+
+impl TransitionParams {
+  pub(crate) fn new(issue_key: &str, transition: &str, fields: Vec<(String, String)>) -> Result<TransitionParams, String> {
+    if !is_valid_issue_key(issue_key) || transition.is_empty() || fields.iter().any(|(field, _)| field.is_empty()) {
+      return Err(String::from("Invalid request. TRANSITION needs the ticket key (like PROJ-123) and the transition (like Done), optionally along with the fields of the transition screen"));
+    }
+    Ok(TransitionParams { issue_key: issue_key.to_string(), transition: transition.to_string(), fields })
+  }
+
+  // the fields of the transition screen come after the transition, as <field>:<base64 value>
+  pub(crate) fn from_text_params(params: &str) -> Result<TransitionParams, String> {
+    let mut parts = params.split(',');
+    let params_and_fields = match (parts.next(), parts.next(), get_fields_from_text_params(parts)) {
+      (Some(issue_key), Some(transition), Some(fields)) => { Some((issue_key, transition, fields)) }
+      _ => { None }
+    };
+    let Some((issue_key, transition, fields)) = params_and_fields else {
+      return Err(format!("Invalid request. TRANSITION needs the ticket key (like PROJ-123) and the transition (like Done), separated by a comma, optionally followed by the fields of the transition screen as <field>:<base64 encoded value>. Params=[{params}]"));
+    };
+    TransitionParams::new(issue_key, transition, fields)
+  }
+}
+
+async fn get_field_values(fields: Vec<(String, String)>, db_conn: &Pool<Sqlite>) -> Result<Vec<FieldValue>, String> {
+  let mut res = Vec::new();
+  for (field, value) in fields {
+    let field = find_field_in_db(field.as_str(), db_conn).await?;
+    res.push(get_field_value(field, value.as_str(), db_conn).await?);
+  }
+  Ok(res)
+}
+
+pub(crate) async fn serve_transition(config: Config,
+                                     request_id: &str,
+                                     params: TransitionParams,
+                                     out_for_replies: tokio::sync::mpsc::Sender<Reply>,
+                                     db_conn: &mut Pool<Sqlite>) {
+  let _ = out_for_replies.send(Reply::ack(request_id)).await;
+
+  let TransitionParams { issue_key, transition, fields } = params;
+  let (issue_key, transition) = (issue_key.as_str(), transition.as_str());
+
+  let field_values = match get_field_values(fields, db_conn).await {
+    Ok(v) => { v }
+    Err(e) => {
+      let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::InvalidParameters, e)).await;
+      let _ = out_for_replies.send(Reply::finished(request_id)).await;
+      return;
+    }
+  };
+
+  let found_transition = match find_transition_of_issue(&config, issue_key, transition, db_conn).await {
+    Ok(v) => { v }
+    Err(e) => {
+      let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::RemoteServer, e)).await;
+      let _ = out_for_replies.send(Reply::finished(request_id)).await;
+      return;
+    }
+  };
+  let Some(found_transition) = found_transition else {
+    let err_msg = format!("Transition {transition} isn't available for issue {issue_key}. FETCH_TRANSITIONS lists the available ones");
+    let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::InvalidParameters, err_msg)).await;
+    let _ = out_for_replies.send(Reply::finished(request_id)).await;
+    return;
+  };
+
+  let missing_fields = found_transition.required_fields
+    .iter()
+    .filter(|x| !field_values.iter().any(|field_value| field_value.field.jira_id == **x))
+    .map(|x| x.as_str())
+    .collect::<Vec<_>>();
+  if !missing_fields.is_empty() {
+    let err_msg = format!("Transition {name} of issue {issue_key} needs the following fields: {fields}",
+                          name = found_transition.name, fields = missing_fields.join(", "));
+    let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::InvalidParameters, err_msg)).await;
+    let _ = out_for_replies.send(Reply::finished(request_id)).await;
+    return;
+  }
+
+  match transition_issue(&config, issue_key, &found_transition, field_values.as_slice(), db_conn).await {
+    Ok(fields) => {
+      let _ = out_for_replies.send(Reply::result(request_id, ResultData::KeyValueFields(fields))).await;
+    }
+    Err(e) => {
+      let _ = out_for_replies.send(Reply::error(request_id, ErrorCode::RemoteServer, e)).await;
+    }
+  }
+
+  let _ = out_for_replies.send(Reply::finished(request_id)).await;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn transition_params_without_fields() {
+    let params = TransitionParams::from_text_params("PROJ-1,Done").unwrap();
+    assert_eq!(params, TransitionParams::new("PROJ-1", "Done", vec![]).unwrap());
+  }
+
+  #[test]
+  fn transition_params_with_fields() {
+    // Rml4ZWQ= is Fixed, SmFuZSBEb2U= is Jane Doe
+    let params = TransitionParams::from_text_params("PROJ-1,Done,resolution:Rml4ZWQ=,assignee:SmFuZSBEb2U=").unwrap();
+    let fields = vec![
+      ("resolution".to_string(), "Fixed".to_string()),
+      ("assignee".to_string(), "Jane Doe".to_string()),
+    ];
+    assert_eq!(params, TransitionParams::new("PROJ-1", "Done", fields).unwrap());
+  }
+
+  #[test]
+  fn invalid_transition_params() {
+    assert!(TransitionParams::from_text_params("").is_err());
+    assert!(TransitionParams::from_text_params("PROJ-1").is_err());
+    assert!(TransitionParams::from_text_params("PROJ-1,").is_err());
+    assert!(TransitionParams::from_text_params(",Done").is_err());
+    assert!(TransitionParams::from_text_params("PROJ-1,Done,resolution").is_err());
+    assert!(TransitionParams::from_text_params("PROJ-1,Done,:Rml4ZWQ=").is_err());
+    assert!(TransitionParams::from_text_params("PROJ-1,Done,resolution:not base64").is_err());
+  }
+
+  #[test]
+  fn transition_params_with_invalid_key() {
+    assert!(TransitionParams::new("PROJ-1/../../../rest/api/3/project", "Done", vec![]).is_err());
+    assert!(TransitionParams::new("proj-1", "Done", vec![]).is_err());
+  }
+}
@@ -0,0 +1,114 @@
+use crate::server::Reply;
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SyncProgress {
+  pub(crate) project_key: String,
+  pub(crate) pages_fetched: usize,
+  pub(crate) pages_total: usize,
+  pub(crate) tickets_detailed: usize,
+  pub(crate) tickets_total: usize,
+}
+
+// Sends PROGRESS replies while synchronising a project. Background synchronisations
+// aren't tied to any request, they use a disabled reporter which doesn't send anything.
+#[derive(Clone)]
+pub(crate) struct ProgressReporter {
+  destination: Option<(String /* request id */, tokio::sync::mpsc::Sender<Reply>)>,
+  progress: SyncProgress,
+}
+
+impl ProgressReporter {
+  pub(crate) fn new(request_id: &str, out_for_replies: tokio::sync::mpsc::Sender<Reply>) -> ProgressReporter {
+    ProgressReporter {
+      destination: Some((request_id.to_string(), out_for_replies)),
+      progress: SyncProgress::default(),
+    }
+  }
+
+  pub(crate) fn disabled() -> ProgressReporter {
+    ProgressReporter {
+      destination: None,
+      progress: SyncProgress::default(),
+    }
+  }
+
+  // projects are synchronised in parallel, each one reports its own progress.
+  pub(crate) fn for_project(&self, project_key: &str) -> ProgressReporter {
+    ProgressReporter {
+      destination: self.destination.clone(),
+      progress: SyncProgress {
+        project_key: project_key.to_string(),
+        ..SyncProgress::default()
+      },
+    }
+  }
+
+  pub(crate) async fn pages(&mut self, pages_fetched: usize, pages_total: usize) {
+    self.progress.pages_fetched = pages_fetched;
+    self.progress.pages_total = pages_total;
+    self.send().await
+  }
+
+  // the number of pages is only an upper bound when the synchronisation stops as soon as
+  // it finds an up-to-date ticket. Once done fetching pages, the total is what got fetched.
+  pub(crate) fn all_pages_fetched(&mut self) {
+    self.progress.pages_total = self.progress.pages_fetched;
+  }
+
+  pub(crate) async fn tickets(&mut self, tickets_detailed: usize, tickets_total: usize) {
+    self.progress.tickets_detailed = tickets_detailed;
+    self.progress.tickets_total = tickets_total;
+    self.send().await
+  }
+
+  async fn send(&self) {
+    if let Some((request_id, out_for_replies)) = &self.destination {
+      let _ = out_for_replies.send(Reply::progress(request_id.as_str(), self.progress.clone())).await;
+    }
+  }
+}
+
+// jira returns the first page along with the total number of tickets and the page size.
+// The remaining pages are then queried one after the other, or in parallel.
+pub(crate) fn get_number_of_pages(total: Option<i64>, max_result_per_query: i64) -> usize {
+  match total {
+    Some(total) if max_result_per_query > 0 && total > max_result_per_query => {
+      ((total + max_result_per_query - 1) / max_result_per_query) as usize
+    }
+    _ => { 1 }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn number_of_pages_without_total() {
+    assert_eq!(get_number_of_pages(None, 50), 1);
+  }
+
+  #[test]
+  fn number_of_pages_fitting_in_first_page() {
+    assert_eq!(get_number_of_pages(Some(0), 50), 1);
+    assert_eq!(get_number_of_pages(Some(1), 50), 1);
+    assert_eq!(get_number_of_pages(Some(50), 50), 1);
+  }
+
+  #[test]
+  fn number_of_pages_with_partial_last_page() {
+    assert_eq!(get_number_of_pages(Some(51), 50), 2);
+    assert_eq!(get_number_of_pages(Some(5), 2), 3);
+  }
+
+  #[test]
+  fn number_of_pages_with_full_last_page() {
+    assert_eq!(get_number_of_pages(Some(100), 50), 2);
+    assert_eq!(get_number_of_pages(Some(4), 2), 2);
+  }
+
+  #[test]
+  fn number_of_pages_with_invalid_page_size() {
+    assert_eq!(get_number_of_pages(Some(100), 0), 1);
+  }
+}
@@ -0,0 +1,50 @@
+use std::sync::OnceLock;
+use tokio::sync::broadcast;
+
+#[derive(Clone, Debug)]
+pub(crate) struct UpdatedTicket {
+  pub(crate) key: String,
+  pub(crate) fields: Vec<String>, // jira field ids, like customfield_12345
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TicketChanges {
+  pub(crate) created: Vec<String>,
+  pub(crate) updated: Vec<UpdatedTicket>,
+  pub(crate) deleted: Vec<String>,
+}
+
+impl TicketChanges {
+  pub(crate) fn is_empty(&self) -> bool {
+    self.created.is_empty() && self.updated.is_empty() && self.deleted.is_empty()
+  }
+}
+
+// the database gets updated from many places (background synchronisation, requests, ...).
+// Rather than passing a sender down to each of them, changes are published on a single
+// channel that every SUBSCRIBE request listens to.
+fn get_channel() -> &'static broadcast::Sender<TicketChanges> {
+  static CHANNEL: OnceLock<broadcast::Sender<TicketChanges>> = OnceLock::new();
+  CHANNEL.get_or_init(|| {
+    let (sender, _) = broadcast::channel(1024);
+    sender
+  })
+}
+
+pub(crate) fn publish_ticket_changes(changes: TicketChanges) {
+  if changes.is_empty() {
+    return;
+  }
+
+  // an error only means nobody subscribed, which is fine.
+  let _ = get_channel().send(changes);
+}
+
+// lets publishers skip gathering changes nobody listens to
+pub(crate) fn has_subscribers() -> bool {
+  get_channel().receiver_count() > 0
+}
+
+pub(crate) fn subscribe_to_ticket_changes() -> broadcast::Receiver<TicketChanges> {
+  get_channel().subscribe()
+}
@@ -1,3 +1,4 @@
+use base64::Engine;
 use std::collections::HashSet;
 use std::hash::Hash;
 
@@ -72,4 +73,22 @@ pub fn get_str_without_surrounding_quotes(input: &str) -> &str {
   } else {
     input
   }
-}
\ No newline at end of file
+}
+// the text protocol encodes free text in base64, since it may contain commas, spaces and newlines
+pub(crate) fn decode_base64_text(text: &str) -> Option<String> {
+  let text = base64::engine::general_purpose::STANDARD.decode(text).ok()?;
+  String::from_utf8(text).ok()
+}
+
+// fields given along with a request in the text protocol, as <field>:<base64 encoded value>
+pub(crate) fn get_fields_from_text_params<'a>(parts: impl Iterator<Item = &'a str>) -> Option<Vec<(String, String)>> {
+  let mut fields = Vec::new();
+  for part in parts {
+    let (field, value) = part.split_once(':')?;
+    if field.is_empty() {
+      return None;
+    }
+    fields.push((field.to_string(), decode_base64_text(value)?));
+  }
+  Some(fields)
+}